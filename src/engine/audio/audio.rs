@@ -4,9 +4,65 @@ use crate::messaging::Action;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crossbeam::channel::Sender;
 use parking_lot::Mutex;
+use std::ops::DerefMut;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::ops::DerefMut;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * Stereo devices get left and right as-is.
+     */
+
+    #[test]
+    fn stereo_output() {
+        let mut frame = [0.0; 2];
+        write_frame(&mut frame, Frame::new(0.25, -0.5));
+        assert_eq!(frame, [0.25, -0.5]);
+    }
+
+    /*
+     * Mono devices get the average of both channels.
+     */
+
+    #[test]
+    fn mono_output() {
+        let mut frame = [0.0; 1];
+        write_frame(&mut frame, Frame::new(0.25, -0.5));
+        assert_eq!(frame, [-0.125]);
+    }
+
+    /*
+     * Devices with more than two channels get left and
+     * right on the front pair, and silence elsewhere.
+     */
+
+    #[test]
+    fn multichannel_output() {
+        let mut frame = [1.0; 6];
+        write_frame(&mut frame, Frame::new(0.25, -0.5));
+        assert_eq!(frame, [0.25, -0.5, 0.0, 0.0, 0.0, 0.0]);
+    }
+}
+
+/*
+ * Map a stereo frame onto one frame of the device's
+ * interleaved output, whatever its channel count.
+ */
+
+fn write_frame(frame: &mut [f32], output: Frame) {
+    match frame {
+        [] => {}
+        [mono] => *mono = output.to_mono(),
+        [left, right, rest @ ..] => {
+            *left = output.left;
+            *right = output.right;
+            rest.fill(0.0);
+        }
+    }
+}
 
 pub struct Audio {
     device: cpal::Device,
@@ -62,24 +118,25 @@ impl Audio {
     pub fn start(&mut self) {
         let instrument_manager = self.instrument_manager.clone();
         let sequencer = self.sequencer.clone();
-        let sample_rate = self.sample_rate as f32;
+        let channels = (self.config.channels as usize).max(1);
 
         let is_playing = Arc::clone(&self.is_playing);
         let is_playing_error = Arc::clone(&self.is_playing);
 
-        instrument_manager.lock().note_on();
-
         let stream = self
             .device
             .build_output_stream(
                 &self.config,
                 move |data: &mut [f32], _| {
-                    for frame in data.chunks_mut(2) {
+                    let bpm = sequencer.lock().get_bpm();
+                    instrument_manager.lock().set_bpm(bpm);
+
+                    for frame in data.chunks_mut(channels) {
                         is_playing.store(true, Ordering::Release);
-                        sequencer.lock().deref_mut().tick();
-                        let sample = instrument_manager.lock().next();
-                        frame[0] = sample;
-                        frame[1] = 0.2 * sample;
+                        let mut manager = instrument_manager.lock();
+                        sequencer.lock().tick(manager.deref_mut());
+                        let output = manager.next();
+                        write_frame(frame, output);
                     }
                 },
                 move |err| {
@@ -0,0 +1,127 @@
+use std::f32::consts::PI;
+
+/*
+ * Second-order IIR filter with coefficients from the
+ * RBJ Audio EQ Cookbook. Mono; use one per channel.
+ */
+
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+}
+
+impl Biquad {
+    fn set(&mut self, b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) {
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+
+    fn omega(sample_rate: f32, freq: f32) -> (f32, f32) {
+        let freq = freq.clamp(10.0, 0.49 * sample_rate);
+        let w = 2.0 * PI * freq / sample_rate;
+        (w.cos(), w.sin())
+    }
+
+    pub fn lowpass(&mut self, sample_rate: f32, freq: f32, q: f32) {
+        let (cos, sin) = Self::omega(sample_rate, freq);
+        let alpha = sin / (2.0 * q);
+        self.set(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        );
+    }
+
+    pub fn peaking(&mut self, sample_rate: f32, freq: f32, q: f32, gain_db: f32) {
+        let (cos, sin) = Self::omega(sample_rate, freq);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let alpha = sin / (2.0 * q);
+        self.set(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        );
+    }
+
+    pub fn low_shelf(&mut self, sample_rate: f32, freq: f32, gain_db: f32) {
+        let (cos, sin) = Self::omega(sample_rate, freq);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        // Shelf slope of 1
+        let alpha = sin / 2.0 * 2.0f32.sqrt();
+        let root = 2.0 * a.sqrt() * alpha;
+        self.set(
+            a * ((a + 1.0) - (a - 1.0) * cos + root),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - root),
+            (a + 1.0) + (a - 1.0) * cos + root,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - root,
+        );
+    }
+
+    pub fn high_shelf(&mut self, sample_rate: f32, freq: f32, gain_db: f32) {
+        let (cos, sin) = Self::omega(sample_rate, freq);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let alpha = sin / 2.0 * 2.0f32.sqrt();
+        let root = 2.0 * a.sqrt() * alpha;
+        self.set(
+            a * ((a + 1.0) + (a - 1.0) * cos + root),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - root),
+            (a + 1.0) - (a - 1.0) * cos + root,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - root,
+        );
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+}
@@ -0,0 +1,127 @@
+use crate::engine::audio::{Effect, Frame, ParamId, param};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * With 2 bits there are only a handful of levels.
+     */
+
+    #[test]
+    fn bits_quantise() {
+        let mut crusher = Bitcrusher::new();
+        crusher.configure(2.0, 1.0, 1.0);
+
+        let mut levels: Vec<i32> = (0..200)
+            .map(|n| {
+                let x = -1.0 + n as f32 / 100.0;
+                (crusher.process(Frame::mono(x)).left * 1000.0).round() as i32
+            })
+            .collect();
+        levels.sort();
+        levels.dedup();
+
+        assert!(levels.len() <= 5, "{levels:?}");
+    }
+
+    /*
+     * Downsampling by 4 holds each sample for 4 frames.
+     */
+
+    #[test]
+    fn downsample_holds() {
+        let mut crusher = Bitcrusher::new();
+        crusher.configure(16.0, 4.0, 1.0);
+
+        let out: Vec<f32> = (0..8)
+            .map(|n| crusher.process(Frame::mono(n as f32 * 0.1)).left)
+            .collect();
+
+        assert!(out[0..4].iter().all(|x| (x - out[0]).abs() < 1e-6));
+        assert!(out[4..8].iter().all(|x| (x - out[4]).abs() < 1e-6));
+        assert!((out[4] - 0.4).abs() < 1e-3);
+    }
+}
+
+/*
+ * Bit depth and sample rate reduction for
+ * lo-fi and chip-style sounds.
+ */
+
+pub struct Bitcrusher {
+    bits: f32,
+    downsample: f32,
+    mix: f32,
+    held: Frame,
+    counter: f32,
+}
+
+impl Bitcrusher {
+    const MIN_BITS: f32 = 1.0;
+    const MAX_BITS: f32 = 16.0;
+    const MAX_DOWNSAMPLE: f32 = 64.0;
+
+    pub fn new() -> Self {
+        Self {
+            bits: Self::MAX_BITS,
+            downsample: 1.0,
+            mix: 1.0,
+            held: Frame::SILENCE,
+            counter: 0.0,
+        }
+    }
+
+    pub fn configure(&mut self, bits: f32, downsample: f32, mix: f32) {
+        self.set_param(param::BITS, bits);
+        self.set_param(param::DOWNSAMPLE, downsample);
+        self.set_param(param::MIX, mix);
+    }
+
+    fn quantise(&self, x: f32) -> f32 {
+        let steps = 2.0f32.powf(self.bits - 1.0);
+        (x * steps).round() / steps
+    }
+}
+
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn process(&mut self, input: Frame) -> Frame {
+        // Sample-and-hold; fractional factors are allowed
+        if self.counter <= 0.0 {
+            self.counter += self.downsample;
+            self.held = Frame::new(self.quantise(input.left), self.quantise(input.right));
+        }
+        self.counter -= 1.0;
+
+        input * (1.0 - self.mix) + self.held * self.mix
+    }
+
+    fn get_param(&self, param: ParamId) -> f32 {
+        match param {
+            param::BITS => self.bits,
+            param::DOWNSAMPLE => self.downsample,
+            param::MIX => self.mix,
+            _ => 0.0,
+        }
+    }
+
+    fn set_param(&mut self, param: ParamId, value: f32) {
+        match param {
+            param::BITS => self.bits = value.clamp(Self::MIN_BITS, Self::MAX_BITS),
+            param::DOWNSAMPLE => self.downsample = value.clamp(1.0, Self::MAX_DOWNSAMPLE),
+            param::MIX => self.mix = value.clamp(0.0, 1.0),
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        self.held = Frame::SILENCE;
+        self.counter = 0.0;
+    }
+}
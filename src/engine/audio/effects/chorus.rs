@@ -0,0 +1,185 @@
+use crate::engine::audio::{Effect, Frame, ParamId, param};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * With the mix at zero the input comes through untouched.
+     */
+
+    #[test]
+    fn dry_mix_is_passthrough() {
+        let mut chorus = Chorus::new(48000.0);
+        chorus.configure(1.0, 3.0, 10.0, 0.0, 0.0);
+
+        for n in 0..100 {
+            let x = (n as f32 * 0.1).sin();
+            assert_eq!(chorus.process(Frame::mono(x)), Frame::mono(x));
+        }
+    }
+
+    /*
+     * The wet signal is the input delayed by around
+     * the base delay time, and the two sides differ.
+     */
+
+    #[test]
+    fn wet_is_delayed_and_wide() {
+        let mut chorus = Chorus::new(48000.0);
+        chorus.configure(0.5, 2.0, 10.0, 0.0, 1.0);
+
+        let mut output = vec![chorus.process(Frame::mono(1.0))];
+        for _ in 0..2000 {
+            output.push(chorus.process(Frame::SILENCE));
+        }
+
+        let arrival = output.iter().position(|f| f.left.abs() > 0.1).unwrap();
+        let ms = arrival as f32 / 48.0;
+        assert!((10.0..=12.5).contains(&ms), "arrived after {ms}ms");
+        assert!(output.iter().any(|f| (f.left - f.right).abs() > 0.1));
+    }
+
+    /*
+     * Flanger-style feedback stays stable.
+     */
+
+    #[test]
+    fn feedback_is_stable() {
+        let mut chorus = Chorus::new(48000.0);
+        chorus.configure(0.2, 1.0, 1.0, 0.95, 0.5);
+
+        for n in 0..48000 {
+            let x = (n as f32 * 0.05).sin();
+            let out = chorus.process(Frame::mono(x));
+            assert!(out.left.abs() < 20.0 && out.right.abs() < 20.0);
+        }
+    }
+}
+
+/*
+ * Chorus and flanger: the input is mixed with a copy
+ * read from a delay line whose length is swept by a
+ * sine LFO, a quarter cycle apart on each side for
+ * width. Short delays with feedback give a flanger;
+ * longer ones without give a chorus.
+ *
+ * Rate is in Hz, depth and delay in milliseconds.
+ */
+
+pub struct Chorus {
+    sample_rate: f32,
+    rate: f32,
+    depth: f32,
+    delay: f32,
+    feedback: f32,
+    mix: f32,
+    phase: f32,
+    left: Vec<f32>,
+    right: Vec<f32>,
+    write: usize,
+}
+
+impl Chorus {
+    const MAX_RATE: f32 = 10.0;
+    const MAX_DEPTH: f32 = 20.0;
+    const MIN_DELAY: f32 = 0.1;
+    const MAX_DELAY: f32 = 30.0;
+    const MAX_FEEDBACK: f32 = 0.95;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let length = ((Self::MAX_DELAY + Self::MAX_DEPTH) * sample_rate / 1000.0) as usize + 2;
+
+        Self {
+            sample_rate,
+            rate: 0.5,
+            depth: 2.0,
+            delay: 10.0,
+            feedback: 0.0,
+            mix: 0.5,
+            phase: 0.0,
+            left: vec![0.0; length],
+            right: vec![0.0; length],
+            write: 0,
+        }
+    }
+
+    pub fn configure(&mut self, rate: f32, depth: f32, delay: f32, feedback: f32, mix: f32) {
+        self.set_param(param::RATE, rate);
+        self.set_param(param::DEPTH, depth);
+        self.set_param(param::DELAY, delay);
+        self.set_param(param::FEEDBACK, feedback);
+        self.set_param(param::MIX, mix);
+    }
+
+    fn read(buffer: &[f32], write: usize, delay: f32) -> f32 {
+        let length = buffer.len();
+        let position = write as f32 + length as f32 - delay;
+        let index = position.floor();
+        let fraction = position - index;
+        let a = buffer[index as usize % length];
+        let b = buffer[(index as usize + 1) % length];
+        a + (b - a) * fraction
+    }
+
+    // Delay in samples for an LFO value between -1 and 1
+    fn delay_samples(&self, lfo: f32) -> f32 {
+        let ms = self.delay + self.depth * 0.5 * (1.0 + lfo);
+        (ms * self.sample_rate / 1000.0).max(1.0)
+    }
+}
+
+impl Effect for Chorus {
+    fn process(&mut self, input: Frame) -> Frame {
+        self.phase += self.rate / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+
+        let angle = 2.0 * PI * self.phase;
+        let wet = Frame::new(
+            Self::read(&self.left, self.write, self.delay_samples(angle.sin())),
+            Self::read(
+                &self.right,
+                self.write,
+                self.delay_samples((angle + FRAC_PI_2).sin()),
+            ),
+        );
+
+        self.left[self.write] = input.left + wet.left * self.feedback;
+        self.right[self.write] = input.right + wet.right * self.feedback;
+        self.write = (self.write + 1) % self.left.len();
+
+        input * (1.0 - self.mix) + wet * self.mix
+    }
+
+    fn get_param(&self, param: ParamId) -> f32 {
+        match param {
+            param::RATE => self.rate,
+            param::DEPTH => self.depth,
+            param::DELAY => self.delay,
+            param::FEEDBACK => self.feedback,
+            param::MIX => self.mix,
+            _ => 0.0,
+        }
+    }
+
+    fn set_param(&mut self, param: ParamId, value: f32) {
+        match param {
+            param::RATE => self.rate = value.clamp(0.0, Self::MAX_RATE),
+            param::DEPTH => self.depth = value.clamp(0.0, Self::MAX_DEPTH),
+            param::DELAY => self.delay = value.clamp(Self::MIN_DELAY, Self::MAX_DELAY),
+            param::FEEDBACK => self.feedback = value.clamp(-Self::MAX_FEEDBACK, Self::MAX_FEEDBACK),
+            param::MIX => self.mix = value.clamp(0.0, 1.0),
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        self.left.fill(0.0);
+        self.right.fill(0.0);
+        self.phase = 0.0;
+        self.write = 0;
+    }
+}
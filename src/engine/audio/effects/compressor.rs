@@ -0,0 +1,221 @@
+use crate::engine::audio::{Effect, Frame, ParamId, db_to_gain, param};
+use crate::types::TrackId;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn settle(compressor: &mut Compressor, input: Frame) -> Frame {
+        let mut output = Frame::SILENCE;
+        for _ in 0..SAMPLE_RATE as usize {
+            output = compressor.process(input);
+        }
+        output
+    }
+
+    /*
+     * Above the threshold the level only rises by
+     * 1/ratio dB for every dB of input.
+     */
+
+    #[test]
+    fn ratio_above_threshold() {
+        let mut compressor = Compressor::new(SAMPLE_RATE);
+        compressor.configure(None, -20.0, 4.0, 1.0, 50.0, 0.0);
+
+        settle(&mut compressor, Frame::mono(1.0));
+        assert!((compressor.gain_reduction() - 15.0).abs() < 0.1);
+
+        let mut compressor = Compressor::new(SAMPLE_RATE);
+        compressor.configure(None, -20.0, 4.0, 1.0, 50.0, 0.0);
+        let quiet = settle(&mut compressor, Frame::mono(0.05));
+        assert_eq!(compressor.gain_reduction(), 0.0);
+        assert!((quiet.left - 0.05).abs() < 1e-6);
+    }
+
+    /*
+     * With a key track the detector ignores the input:
+     * a loud key ducks a quiet input, and a silent key
+     * leaves a loud one alone.
+     */
+
+    #[test]
+    fn key_ducks_input() {
+        let mut compressor = Compressor::new(SAMPLE_RATE);
+        compressor.configure(Some(0), -20.0, 10.0, 1.0, 50.0, 0.0);
+
+        compressor.set_key(Frame::mono(1.0));
+        let ducked = settle(&mut compressor, Frame::mono(0.05));
+        assert!(ducked.left < 0.01);
+
+        compressor.set_key(Frame::SILENCE);
+        let released = settle(&mut compressor, Frame::mono(1.0));
+        assert!((released.left - 1.0).abs() < 1e-3);
+    }
+
+    /*
+     * Makeup gain is applied whether or not the
+     * compressor is reducing.
+     */
+
+    #[test]
+    fn makeup_gain() {
+        let mut compressor = Compressor::new(SAMPLE_RATE);
+        compressor.configure(None, 0.0, 4.0, 1.0, 50.0, 6.0);
+
+        let out = settle(&mut compressor, Frame::mono(0.1));
+        assert!((out.left - 0.1 * db_to_gain(6.0)).abs() < 1e-4);
+    }
+}
+
+/*
+ * Feed-forward compressor with a hard knee. The
+ * detector follows the peak of either the input or,
+ * for sidechain ducking, a key track, and the gain
+ * reduction is smoothed in dB by the attack and
+ * release times.
+ *
+ * Threshold and makeup are in dB, attack and release
+ * in milliseconds.
+ */
+
+pub struct Compressor {
+    sample_rate: f32,
+    key_track: Option<TrackId>,
+    threshold: f32,
+    ratio: f32,
+    attack: f32,
+    release: f32,
+    makeup: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    key: Frame,
+    // Current gain reduction in dB, positive
+    reduction: f32,
+}
+
+impl Compressor {
+    const MIN_THRESHOLD: f32 = -60.0;
+    const MAX_RATIO: f32 = 20.0;
+    const MIN_ATTACK: f32 = 0.1;
+    const MAX_ATTACK: f32 = 200.0;
+    const MIN_RELEASE: f32 = 10.0;
+    const MAX_RELEASE: f32 = 2000.0;
+    const MAX_MAKEUP: f32 = 24.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let mut compressor = Self {
+            sample_rate,
+            key_track: None,
+            threshold: -12.0,
+            ratio: 4.0,
+            attack: 5.0,
+            release: 100.0,
+            makeup: 0.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            key: Frame::SILENCE,
+            reduction: 0.0,
+        };
+        compressor.update();
+        compressor
+    }
+
+    pub fn configure(
+        &mut self,
+        key_track: Option<TrackId>,
+        threshold: f32,
+        ratio: f32,
+        attack: f32,
+        release: f32,
+        makeup: f32,
+    ) {
+        self.set_key_track(key_track);
+        self.set_param(param::THRESHOLD, threshold);
+        self.set_param(param::RATIO, ratio);
+        self.set_param(param::ATTACK, attack);
+        self.set_param(param::RELEASE, release);
+        self.set_param(param::MAKEUP, makeup);
+    }
+
+    pub fn set_key_track(&mut self, key_track: Option<TrackId>) {
+        self.key_track = key_track;
+    }
+
+    fn coefficient(&self, ms: f32) -> f32 {
+        1.0 - (-1000.0 / (ms * self.sample_rate)).exp()
+    }
+
+    fn update(&mut self) {
+        self.attack_coeff = self.coefficient(self.attack);
+        self.release_coeff = self.coefficient(self.release);
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, input: Frame) -> Frame {
+        let detect = match self.key_track {
+            Some(_) => self.key,
+            None => input,
+        };
+
+        let level = detect.left.abs().max(detect.right.abs()).max(1e-6);
+        let over = 20.0 * level.log10() - self.threshold;
+        let target = if over > 0.0 {
+            over * (1.0 - 1.0 / self.ratio)
+        } else {
+            0.0
+        };
+
+        let coeff = if target > self.reduction {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.reduction += (target - self.reduction) * coeff;
+
+        input * db_to_gain(self.makeup - self.reduction)
+    }
+
+    fn get_param(&self, param: ParamId) -> f32 {
+        match param {
+            param::THRESHOLD => self.threshold,
+            param::RATIO => self.ratio,
+            param::ATTACK => self.attack,
+            param::RELEASE => self.release,
+            param::MAKEUP => self.makeup,
+            _ => 0.0,
+        }
+    }
+
+    fn set_param(&mut self, param: ParamId, value: f32) {
+        match param {
+            param::THRESHOLD => self.threshold = value.clamp(Self::MIN_THRESHOLD, 0.0),
+            param::RATIO => self.ratio = value.clamp(1.0, Self::MAX_RATIO),
+            param::ATTACK => self.attack = value.clamp(Self::MIN_ATTACK, Self::MAX_ATTACK),
+            param::RELEASE => self.release = value.clamp(Self::MIN_RELEASE, Self::MAX_RELEASE),
+            param::MAKEUP => self.makeup = value.clamp(0.0, Self::MAX_MAKEUP),
+            _ => return,
+        }
+        self.update();
+    }
+
+    fn reset(&mut self) {
+        self.key = Frame::SILENCE;
+        self.reduction = 0.0;
+    }
+
+    fn key_track(&self) -> Option<TrackId> {
+        self.key_track
+    }
+
+    fn set_key(&mut self, key: Frame) {
+        self.key = key;
+    }
+
+    fn gain_reduction(&self) -> f32 {
+        self.reduction
+    }
+}
@@ -0,0 +1,209 @@
+use crate::engine::audio::Frame;
+use crate::types::DelaySettings;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NoteDivision;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    // Let any change of delay time finish gliding, then send an impulse
+    fn run(delay: &mut StereoDelay, input: Frame, samples: usize) -> Vec<Frame> {
+        for _ in 0..SAMPLE_RATE as usize {
+            delay.process(Frame::SILENCE);
+        }
+
+        let mut output = vec![delay.process(input)];
+        for _ in 1..samples {
+            output.push(delay.process(Frame::SILENCE));
+        }
+        output
+    }
+
+    fn first_echo(output: &[Frame]) -> Option<usize> {
+        output
+            .iter()
+            .position(|f| f.left.abs() > 0.1 || f.right.abs() > 0.1)
+    }
+
+    /*
+     * At 120 bpm a quarter note is half a second.
+     */
+
+    #[test]
+    fn delay_time_follows_tempo() {
+        let mut delay = StereoDelay::new(SAMPLE_RATE);
+        delay.set_bpm(120.0);
+        delay.configure(DelaySettings {
+            division: NoteDivision::Quarter,
+            ping_pong: false,
+            damping: 0.0,
+            ..Default::default()
+        });
+
+        let output = run(&mut delay, Frame::mono(1.0), SAMPLE_RATE as usize);
+        let echo = first_echo(&output).unwrap();
+        assert!((echo as i64 - 24000).abs() <= 1, "echo at {echo}");
+    }
+
+    /*
+     * Ping-pong echoes start on the left
+     * and then alternate sides.
+     */
+
+    #[test]
+    fn ping_pong_alternates() {
+        let mut delay = StereoDelay::new(SAMPLE_RATE);
+        delay.set_bpm(120.0);
+        delay.configure(DelaySettings {
+            division: NoteDivision::Sixteenth,
+            feedback: 0.5,
+            ping_pong: true,
+            damping: 0.0,
+            return_level: 1.0,
+        });
+
+        let output = run(&mut delay, Frame::mono(1.0), 2 * 6000 + 10);
+        let first = output[6000];
+        let second = output[12000];
+
+        assert!(first.left > 0.5 && first.right.abs() < 1e-6);
+        assert!(second.right > 0.2 && second.left.abs() < 1e-6);
+    }
+
+    /*
+     * With feedback below 1.0 the echoes die away.
+     */
+
+    #[test]
+    fn feedback_decays() {
+        let mut delay = StereoDelay::new(SAMPLE_RATE);
+        delay.set_bpm(240.0);
+        delay.configure(DelaySettings {
+            division: NoteDivision::Sixteenth,
+            feedback: 0.9,
+            ..Default::default()
+        });
+
+        let output = run(&mut delay, Frame::mono(1.0), 10 * SAMPLE_RATE as usize);
+        let tail = &output[output.len() - 1000..];
+        assert!(
+            tail.iter()
+                .all(|f| f.left.abs() < 1e-3 && f.right.abs() < 1e-3)
+        );
+    }
+}
+
+/*
+ * Tempo-synced stereo delay with feedback, optional
+ * ping-pong and a low-pass filter in the feedback path
+ * so repeats get darker. Only the wet signal is
+ * returned.
+ *
+ * The delay line is allocated for the longest time we
+ * allow, and time changes glide rather than jump.
+ */
+
+pub struct StereoDelay {
+    sample_rate: f32,
+    settings: DelaySettings,
+    bpm: f32,
+    left: Vec<f32>,
+    right: Vec<f32>,
+    write: usize,
+    delay: f32,
+    target_delay: f32,
+    damp_left: f32,
+    damp_right: f32,
+}
+
+impl StereoDelay {
+    const MAX_TIME: f32 = 4.0;
+    const MAX_FEEDBACK: f32 = 0.95;
+    // Fraction of the remaining distance covered per sample
+    const GLIDE: f32 = 0.001;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let length = (Self::MAX_TIME * sample_rate) as usize + 2;
+
+        let mut delay = Self {
+            sample_rate,
+            settings: DelaySettings::default(),
+            bpm: 120.0,
+            left: vec![0.0; length],
+            right: vec![0.0; length],
+            write: 0,
+            delay: 0.0,
+            target_delay: 0.0,
+            damp_left: 0.0,
+            damp_right: 0.0,
+        };
+        delay.update_time();
+        delay.delay = delay.target_delay;
+        delay
+    }
+
+    pub fn configure(&mut self, settings: DelaySettings) {
+        self.settings = settings;
+        self.update_time();
+    }
+
+    pub fn settings(&self) -> DelaySettings {
+        self.settings
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) {
+        if bpm != self.bpm {
+            self.bpm = bpm;
+            self.update_time();
+        }
+    }
+
+    fn update_time(&mut self) {
+        let seconds = self.settings.division.seconds(self.bpm).min(Self::MAX_TIME);
+        self.target_delay = (seconds * self.sample_rate).max(1.0);
+    }
+
+    fn read(buffer: &[f32], write: usize, delay: f32) -> f32 {
+        let length = buffer.len();
+        let position = write as f32 + length as f32 - delay;
+        let index = position.floor();
+        let fraction = position - index;
+        let a = buffer[index as usize % length];
+        let b = buffer[(index as usize + 1) % length];
+        a + (b - a) * fraction
+    }
+
+    pub fn process(&mut self, input: Frame) -> Frame {
+        self.delay += (self.target_delay - self.delay) * Self::GLIDE;
+
+        let delayed = Frame::new(
+            Self::read(&self.left, self.write, self.delay),
+            Self::read(&self.right, self.write, self.delay),
+        );
+
+        let feedback = self.settings.feedback.clamp(0.0, Self::MAX_FEEDBACK);
+        let coefficient = 1.0 - 0.95 * self.settings.damping.clamp(0.0, 1.0);
+        self.damp_left += (delayed.left - self.damp_left) * coefficient;
+        self.damp_right += (delayed.right - self.damp_right) * coefficient;
+
+        let (left, right) = if self.settings.ping_pong {
+            (
+                input.to_mono() + self.damp_right * feedback,
+                self.damp_left * feedback,
+            )
+        } else {
+            (
+                input.left + self.damp_left * feedback,
+                input.right + self.damp_right * feedback,
+            )
+        };
+
+        self.left[self.write] = left;
+        self.right[self.write] = right;
+        self.write = (self.write + 1) % self.left.len();
+
+        delayed
+    }
+}
@@ -0,0 +1,151 @@
+use crate::engine::audio::effects::Biquad;
+use crate::engine::audio::{Effect, Frame, ParamId, param};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /*
+     * However hard it's driven, a full-scale input
+     * stays near full scale. The anti-aliasing filter
+     * rings a little on the squared-off edges.
+     */
+
+    #[test]
+    fn output_is_bounded() {
+        let mut distortion = Distortion::new(48000.0);
+        distortion.configure(50.0, 1.0);
+
+        for n in 0..4800 {
+            let x = (2.0 * PI * 1000.0 * n as f32 / 48000.0).sin();
+            let out = distortion.process(Frame::mono(x));
+            assert!(out.left.abs() <= 1.5 && out.right.abs() <= 1.5);
+        }
+    }
+
+    /*
+     * With the mix at zero the input comes through untouched.
+     */
+
+    #[test]
+    fn dry_mix_is_passthrough() {
+        let mut distortion = Distortion::new(48000.0);
+        distortion.configure(20.0, 0.0);
+
+        let out = distortion.process(Frame::new(0.3, -0.2));
+        assert_eq!(out, Frame::new(0.3, -0.2));
+    }
+
+    #[test]
+    fn params() {
+        let mut distortion = Distortion::new(48000.0);
+        distortion.set_param(param::DRIVE, 8.0);
+        distortion.set_param(param::MIX, 0.5);
+        assert_eq!(distortion.get_param(param::DRIVE), 8.0);
+        assert_eq!(distortion.get_param(param::MIX), 0.5);
+    }
+}
+
+/*
+ * Waveshaping distortion. The input is upsampled by
+ * linear interpolation, pushed through a tanh curve at
+ * the higher rate, then low-passed and decimated, so
+ * the harmonics the shaper adds don't fold back down
+ * as aliasing.
+ */
+
+pub struct Distortion {
+    drive: f32,
+    mix: f32,
+    last_input: Frame,
+    // Two cascaded low-passes per channel make a
+    // 4th order Butterworth anti-aliasing filter
+    filters: [[Biquad; 2]; 2],
+}
+
+impl Distortion {
+    const OVERSAMPLING: usize = 4;
+    const MIN_DRIVE: f32 = 1.0;
+    const MAX_DRIVE: f32 = 100.0;
+    const BUTTERWORTH_Q: [f32; 2] = [0.5412, 1.3066];
+
+    pub fn new(sample_rate: f32) -> Self {
+        let mut filters = [[Biquad::default(); 2]; 2];
+        let oversampled_rate = sample_rate * Self::OVERSAMPLING as f32;
+        for channel in &mut filters {
+            for (filter, q) in channel.iter_mut().zip(Self::BUTTERWORTH_Q) {
+                filter.lowpass(oversampled_rate, 0.45 * sample_rate, q);
+            }
+        }
+
+        Self {
+            drive: Self::MIN_DRIVE,
+            mix: 1.0,
+            last_input: Frame::SILENCE,
+            filters,
+        }
+    }
+
+    pub fn configure(&mut self, drive: f32, mix: f32) {
+        self.set_param(param::DRIVE, drive);
+        self.set_param(param::MIX, mix);
+    }
+
+    fn shape(&self, x: f32) -> f32 {
+        (self.drive * x).tanh() / self.drive.tanh()
+    }
+
+    fn process_channel(&mut self, channel: usize, previous: f32, current: f32) -> f32 {
+        let mut output = 0.0;
+        for k in 1..=Self::OVERSAMPLING {
+            let t = k as f32 / Self::OVERSAMPLING as f32;
+            let shaped = self.shape(previous + (current - previous) * t);
+            output = self.filters[channel]
+                .iter_mut()
+                .fold(shaped, |x, filter| filter.process(x));
+        }
+        output
+    }
+}
+
+impl Effect for Distortion {
+    fn process(&mut self, input: Frame) -> Frame {
+        let previous = self.last_input;
+        self.last_input = input;
+
+        if self.mix <= 0.0 {
+            return input;
+        }
+
+        let wet = Frame::new(
+            self.process_channel(0, previous.left, input.left),
+            self.process_channel(1, previous.right, input.right),
+        );
+
+        input * (1.0 - self.mix) + wet * self.mix
+    }
+
+    fn get_param(&self, param: ParamId) -> f32 {
+        match param {
+            param::DRIVE => self.drive,
+            param::MIX => self.mix,
+            _ => 0.0,
+        }
+    }
+
+    fn set_param(&mut self, param: ParamId, value: f32) {
+        match param {
+            param::DRIVE => self.drive = value.clamp(Self::MIN_DRIVE, Self::MAX_DRIVE),
+            param::MIX => self.mix = value.clamp(0.0, 1.0),
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_input = Frame::SILENCE;
+        for filter in self.filters.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+}
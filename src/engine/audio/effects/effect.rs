@@ -0,0 +1,33 @@
+use crate::engine::audio::{Frame, ParamId};
+use crate::types::TrackId;
+use downcast_rs::{Downcast, impl_downcast};
+
+/*
+ * An insert effect in an instrument's chain. Like a
+ * Source, its parameters can be read and written by
+ * ID so modulators can target them.
+ *
+ * Effects keyed from another track report it from
+ * key_track and are handed that track's latest output
+ * through set_key before each process.
+ */
+
+pub trait Effect: Send + Sync + Downcast {
+    fn process(&mut self, input: Frame) -> Frame;
+    fn get_param(&self, param: ParamId) -> f32;
+    fn set_param(&mut self, param: ParamId, value: f32);
+    fn reset(&mut self) {}
+
+    fn key_track(&self) -> Option<TrackId> {
+        None
+    }
+
+    fn set_key(&mut self, _key: Frame) {}
+
+    // Current gain reduction in dB, for metering
+    fn gain_reduction(&self) -> f32 {
+        0.0
+    }
+}
+
+impl_downcast!(Effect);
@@ -0,0 +1,180 @@
+use crate::engine::audio::effects::Biquad;
+use crate::engine::audio::{Effect, Frame, ParamId, param};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    // Peak output level of a sine after the EQ has settled
+    fn level(eq: &mut Eq, freq: f32) -> f32 {
+        let mut peak: f32 = 0.0;
+        for n in 0..SAMPLE_RATE as usize / 2 {
+            let x = (2.0 * PI * freq * n as f32 / SAMPLE_RATE).sin();
+            let out = eq.process(Frame::mono(x));
+            if n > SAMPLE_RATE as usize / 4 {
+                peak = peak.max(out.left.abs());
+            }
+        }
+        peak
+    }
+
+    /*
+     * With every band at 0 dB the EQ is flat.
+     */
+
+    #[test]
+    fn flat_when_gains_are_zero() {
+        for freq in [50.0, 1000.0, 10000.0] {
+            let mut eq = Eq::new(SAMPLE_RATE);
+            assert!((level(&mut eq, freq) - 1.0).abs() < 0.01);
+        }
+    }
+
+    /*
+     * Each band boosts its own region and leaves
+     * the others mostly alone.
+     */
+
+    #[test]
+    fn bands_boost_their_region() {
+        let mut eq = Eq::new(SAMPLE_RATE);
+        eq.configure(200.0, 12.0, 1000.0, 0.0, 5000.0, 0.0);
+        assert!(level(&mut eq, 40.0) > 3.0);
+        let mut eq = Eq::new(SAMPLE_RATE);
+        eq.configure(200.0, 12.0, 1000.0, 0.0, 5000.0, 0.0);
+        assert!(level(&mut eq, 12000.0) < 1.1);
+
+        let mut eq = Eq::new(SAMPLE_RATE);
+        eq.configure(200.0, 0.0, 1000.0, -12.0, 5000.0, 0.0);
+        assert!(level(&mut eq, 1000.0) < 0.3);
+
+        let mut eq = Eq::new(SAMPLE_RATE);
+        eq.configure(200.0, 0.0, 1000.0, 0.0, 5000.0, 12.0);
+        assert!(level(&mut eq, 15000.0) > 3.0);
+    }
+}
+
+/*
+ * Three-band EQ: a low shelf, a peaking mid band and
+ * a high shelf. Frequencies are in Hz and gains in dB.
+ * Coefficients are only recalculated when a parameter
+ * has changed, so modulating one is cheap enough.
+ */
+
+pub struct Eq {
+    sample_rate: f32,
+    low_freq: f32,
+    low_gain: f32,
+    mid_freq: f32,
+    mid_gain: f32,
+    high_freq: f32,
+    high_gain: f32,
+    // [low, mid, high] for each channel
+    bands: [[Biquad; 3]; 2],
+    dirty: bool,
+}
+
+impl Eq {
+    const MID_Q: f32 = 0.7;
+    const MAX_GAIN: f32 = 24.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            low_freq: 200.0,
+            low_gain: 0.0,
+            mid_freq: 1000.0,
+            mid_gain: 0.0,
+            high_freq: 5000.0,
+            high_gain: 0.0,
+            bands: [[Biquad::default(); 3]; 2],
+            dirty: true,
+        }
+    }
+
+    pub fn configure(
+        &mut self,
+        low_freq: f32,
+        low_gain: f32,
+        mid_freq: f32,
+        mid_gain: f32,
+        high_freq: f32,
+        high_gain: f32,
+    ) {
+        self.set_param(param::LOW_FREQ, low_freq);
+        self.set_param(param::LOW_GAIN, low_gain);
+        self.set_param(param::MID_FREQ, mid_freq);
+        self.set_param(param::MID_GAIN, mid_gain);
+        self.set_param(param::HIGH_FREQ, high_freq);
+        self.set_param(param::HIGH_GAIN, high_gain);
+    }
+
+    fn update(&mut self) {
+        for [low, mid, high] in &mut self.bands {
+            low.low_shelf(self.sample_rate, self.low_freq, self.low_gain);
+            mid.peaking(self.sample_rate, self.mid_freq, Self::MID_Q, self.mid_gain);
+            high.high_shelf(self.sample_rate, self.high_freq, self.high_gain);
+        }
+        self.dirty = false;
+    }
+}
+
+impl Effect for Eq {
+    fn process(&mut self, input: Frame) -> Frame {
+        if self.dirty {
+            self.update();
+        }
+
+        let [left, right] = &mut self.bands;
+        Frame::new(
+            left.iter_mut().fold(input.left, |x, band| band.process(x)),
+            right
+                .iter_mut()
+                .fold(input.right, |x, band| band.process(x)),
+        )
+    }
+
+    fn get_param(&self, param: ParamId) -> f32 {
+        match param {
+            param::LOW_FREQ => self.low_freq,
+            param::LOW_GAIN => self.low_gain,
+            param::MID_FREQ => self.mid_freq,
+            param::MID_GAIN => self.mid_gain,
+            param::HIGH_FREQ => self.high_freq,
+            param::HIGH_GAIN => self.high_gain,
+            _ => 0.0,
+        }
+    }
+
+    fn set_param(&mut self, param: ParamId, value: f32) {
+        let nyquist = 0.49 * self.sample_rate;
+        let target = match param {
+            param::LOW_FREQ => &mut self.low_freq,
+            param::LOW_GAIN => &mut self.low_gain,
+            param::MID_FREQ => &mut self.mid_freq,
+            param::MID_GAIN => &mut self.mid_gain,
+            param::HIGH_FREQ => &mut self.high_freq,
+            param::HIGH_GAIN => &mut self.high_gain,
+            _ => return,
+        };
+
+        let value = match param {
+            param::LOW_FREQ | param::MID_FREQ | param::HIGH_FREQ => value.clamp(10.0, nyquist),
+            _ => value.clamp(-Self::MAX_GAIN, Self::MAX_GAIN),
+        };
+
+        if *target != value {
+            *target = value;
+            self.dirty = true;
+        }
+    }
+
+    fn reset(&mut self) {
+        for band in self.bands.iter_mut().flatten() {
+            band.reset();
+        }
+    }
+}
@@ -0,0 +1,19 @@
+pub mod biquad;
+pub mod bitcrusher;
+pub mod chorus;
+pub mod compressor;
+pub mod delay;
+pub mod distortion;
+pub mod effect;
+pub mod eq;
+pub mod reverb;
+
+pub use biquad::Biquad;
+pub use bitcrusher::Bitcrusher;
+pub use chorus::Chorus;
+pub use compressor::Compressor;
+pub use delay::StereoDelay;
+pub use distortion::Distortion;
+pub use effect::Effect;
+pub use eq::Eq;
+pub use reverb::Reverb;
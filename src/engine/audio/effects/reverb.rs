@@ -0,0 +1,265 @@
+use crate::engine::audio::Frame;
+use crate::types::ReverbSettings;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn energy(frames: &[Frame]) -> f32 {
+        frames
+            .iter()
+            .map(|f| f.left * f.left + f.right * f.right)
+            .sum()
+    }
+
+    fn impulse_response(reverb: &mut Reverb, samples: usize) -> Vec<Frame> {
+        let mut output = vec![reverb.process(Frame::mono(1.0))];
+        for _ in 1..samples {
+            output.push(reverb.process(Frame::SILENCE));
+        }
+        output
+    }
+
+    /*
+     * Nothing comes out before the pre-delay, then there's
+     * a tail that dies away.
+     */
+
+    #[test]
+    fn impulse_has_decaying_tail() {
+        let mut reverb = Reverb::new(SAMPLE_RATE);
+        reverb.configure(ReverbSettings {
+            pre_delay: 0.05,
+            ..Default::default()
+        });
+
+        let output = impulse_response(&mut reverb, 4 * SAMPLE_RATE as usize);
+        let pre_delay = (0.05 * SAMPLE_RATE) as usize;
+
+        assert_eq!(energy(&output[..pre_delay]), 0.0);
+
+        let second = SAMPLE_RATE as usize;
+        let early = energy(&output[pre_delay..pre_delay + second]);
+        let late = energy(&output[3 * second..]);
+        assert!(early > 0.0);
+        assert!(late < early * 0.01);
+        assert!(
+            output
+                .iter()
+                .all(|f| f.left.is_finite() && f.right.is_finite())
+        );
+    }
+
+    /*
+     * More decay means a longer tail.
+     */
+
+    #[test]
+    fn decay_lengthens_tail() {
+        let tail = |decay: f32| {
+            let mut reverb = Reverb::new(SAMPLE_RATE);
+            reverb.configure(ReverbSettings {
+                decay,
+                ..Default::default()
+            });
+            let output = impulse_response(&mut reverb, 2 * SAMPLE_RATE as usize);
+            energy(&output[SAMPLE_RATE as usize..])
+        };
+
+        assert!(tail(0.9) > tail(0.1));
+    }
+}
+
+/*
+ * Schroeder/Moorer style reverb in the manner of
+ * Freeverb: eight damped comb filters in parallel into
+ * four series all-passes, per channel, with the right
+ * channel's delays slightly longer for stereo width.
+ *
+ * Size scales the delay lengths, decay sets the comb
+ * feedback and damping the high-frequency loss inside
+ * the combs. Buffers are sized for the largest room up
+ * front. Only the wet signal is returned.
+ */
+
+pub struct Reverb {
+    sample_rate: f32,
+    settings: ReverbSettings,
+    pre_delay: Vec<Frame>,
+    pre_delay_write: usize,
+    pre_delay_length: usize,
+    combs: [[Comb; NUM_COMBS]; 2],
+    allpasses: [[Allpass; NUM_ALLPASSES]; 2],
+}
+
+const NUM_COMBS: usize = 8;
+const NUM_ALLPASSES: usize = 4;
+
+// Freeverb's tunings, in samples at 44.1kHz
+const COMB_LENGTHS: [usize; NUM_COMBS] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_LENGTHS: [usize; NUM_ALLPASSES] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+
+impl Reverb {
+    const MAX_PRE_DELAY: f32 = 0.5;
+    const MIN_SCALE: f32 = 0.5;
+    const MAX_SCALE: f32 = 1.5;
+    const INPUT_GAIN: f32 = 0.015;
+    const WET_GAIN: f32 = 3.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let rate_scale = sample_rate / 44100.0;
+        let max_length = |base: usize, channel: usize| {
+            ((base + channel * STEREO_SPREAD) as f32 * rate_scale * Self::MAX_SCALE) as usize + 1
+        };
+
+        let mut reverb = Self {
+            sample_rate,
+            settings: ReverbSettings::default(),
+            pre_delay: vec![Frame::SILENCE; (Self::MAX_PRE_DELAY * sample_rate) as usize + 1],
+            pre_delay_write: 0,
+            pre_delay_length: 0,
+            combs: std::array::from_fn(|channel| {
+                std::array::from_fn(|i| Comb::new(max_length(COMB_LENGTHS[i], channel)))
+            }),
+            allpasses: std::array::from_fn(|channel| {
+                std::array::from_fn(|i| Allpass::new(max_length(ALLPASS_LENGTHS[i], channel)))
+            }),
+        };
+        reverb.configure(ReverbSettings::default());
+        reverb
+    }
+
+    pub fn configure(&mut self, settings: ReverbSettings) {
+        self.settings = settings;
+
+        let rate_scale = self.sample_rate / 44100.0;
+        let size_scale =
+            Self::MIN_SCALE + (Self::MAX_SCALE - Self::MIN_SCALE) * settings.size.clamp(0.0, 1.0);
+        let length = |base: usize, channel: usize| {
+            ((base + channel * STEREO_SPREAD) as f32 * rate_scale * size_scale) as usize
+        };
+
+        let feedback = 0.7 + 0.28 * settings.decay.clamp(0.0, 1.0);
+        let damping = 0.4 * settings.damping.clamp(0.0, 1.0);
+
+        for (channel, combs) in self.combs.iter_mut().enumerate() {
+            for (i, comb) in combs.iter_mut().enumerate() {
+                comb.set_length(length(COMB_LENGTHS[i], channel));
+                comb.feedback = feedback;
+                comb.damping = damping;
+            }
+        }
+
+        for (channel, allpasses) in self.allpasses.iter_mut().enumerate() {
+            for (i, allpass) in allpasses.iter_mut().enumerate() {
+                allpass.set_length(length(ALLPASS_LENGTHS[i], channel));
+            }
+        }
+
+        let pre_delay = settings.pre_delay.clamp(0.0, Self::MAX_PRE_DELAY);
+        self.pre_delay_length =
+            ((pre_delay * self.sample_rate) as usize).min(self.pre_delay.len() - 1);
+    }
+
+    pub fn settings(&self) -> ReverbSettings {
+        self.settings
+    }
+
+    pub fn process(&mut self, input: Frame) -> Frame {
+        // Pre-delay
+        let length = self.pre_delay.len();
+        self.pre_delay[self.pre_delay_write] = input;
+        let read = (self.pre_delay_write + length - self.pre_delay_length) % length;
+        let delayed = self.pre_delay[read];
+        self.pre_delay_write = (self.pre_delay_write + 1) % length;
+
+        let input = (delayed.left + delayed.right) * Self::INPUT_GAIN;
+
+        let mut output = [0.0; 2];
+        for (channel, out) in output.iter_mut().enumerate() {
+            let mut sum = 0.0;
+            for comb in &mut self.combs[channel] {
+                sum += comb.process(input);
+            }
+            for allpass in &mut self.allpasses[channel] {
+                sum = allpass.process(sum);
+            }
+            *out = sum * Self::WET_GAIN;
+        }
+
+        Frame::new(output[0], output[1])
+    }
+}
+
+struct Comb {
+    buffer: Vec<f32>,
+    length: usize,
+    index: usize,
+    feedback: f32,
+    damping: f32,
+    filter_store: f32,
+}
+
+impl Comb {
+    fn new(capacity: usize) -> Self {
+        Self {
+            buffer: vec![0.0; capacity],
+            length: capacity,
+            index: 0,
+            feedback: 0.0,
+            damping: 0.0,
+            filter_store: 0.0,
+        }
+    }
+
+    fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, self.buffer.len());
+        if self.index >= self.length {
+            self.index = 0;
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filter_store = output * (1.0 - self.damping) + self.filter_store * self.damping;
+        self.buffer[self.index] = input + self.filter_store * self.feedback;
+        self.index = (self.index + 1) % self.length;
+        output
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f32>,
+    length: usize,
+    index: usize,
+}
+
+impl Allpass {
+    const FEEDBACK: f32 = 0.5;
+
+    fn new(capacity: usize) -> Self {
+        Self {
+            buffer: vec![0.0; capacity],
+            length: capacity,
+            index: 0,
+        }
+    }
+
+    fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, self.buffer.len());
+        if self.index >= self.length {
+            self.index = 0;
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buffer[self.index];
+        let output = buffered - input;
+        self.buffer[self.index] = input + buffered * Self::FEEDBACK;
+        self.index = (self.index + 1) % self.length;
+        output
+    }
+}
@@ -0,0 +1,178 @@
+use std::f32::consts::FRAC_PI_4;
+use std::ops::{Add, AddAssign, Mul};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * Constant-power panning should keep the summed
+     * power of the two channels the same at any position.
+     */
+
+    #[test]
+    fn pan_is_constant_power() {
+        for i in 0..=20 {
+            let pan = -1.0 + i as f32 * 0.1;
+            let (left, right) = pan_gains(pan);
+            assert!((left * left + right * right - 1.0).abs() < 1e-5);
+        }
+    }
+
+    /*
+     * Hard left and hard right should silence the
+     * opposite channel; centre should split evenly.
+     */
+
+    #[test]
+    fn pan_extremes() {
+        let left = Frame::mono(1.0).pan(-1.0);
+        assert!((left.left - 1.0).abs() < 1e-5);
+        assert!(left.right.abs() < 1e-5);
+
+        let right = Frame::mono(1.0).pan(1.0);
+        assert!(right.left.abs() < 1e-5);
+        assert!((right.right - 1.0).abs() < 1e-5);
+
+        let centre = Frame::mono(1.0).pan(0.0);
+        assert!((centre.left - centre.right).abs() < 1e-5);
+    }
+
+    /*
+     * Balance leaves a stereo frame alone at centre and
+     * only ever turns down the opposite side.
+     */
+
+    #[test]
+    fn balance_is_unity_at_centre() {
+        let frame = Frame::new(0.5, -0.25);
+        assert_eq!(frame.balance(0.0), frame);
+
+        let left = frame.balance(-0.5);
+        assert!((left.left - 0.5).abs() < 1e-5);
+        assert!((left.right + 0.125).abs() < 1e-5);
+
+        let right = frame.balance(1.0);
+        assert!(right.left.abs() < 1e-5);
+        assert!((right.right + 0.25).abs() < 1e-5);
+    }
+}
+
+/*
+ * A single stereo sample. Everything from a Source
+ * through to the output device is passed around
+ * as frames.
+ */
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Frame {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Frame {
+    pub const SILENCE: Frame = Frame {
+        left: 0.0,
+        right: 0.0,
+    };
+
+    pub fn new(left: f32, right: f32) -> Self {
+        Self { left, right }
+    }
+
+    // The same sample on both channels
+    pub fn mono(sample: f32) -> Self {
+        Self {
+            left: sample,
+            right: sample,
+        }
+    }
+
+    // Average of the two channels, for mono outputs
+    pub fn to_mono(self) -> f32 {
+        0.5 * (self.left + self.right)
+    }
+
+    // Replace NaN or infinite samples with silence
+    pub fn sanitize(self) -> Self {
+        let clean = |sample: f32| if sample.is_finite() { sample } else { 0.0 };
+        Self {
+            left: clean(self.left),
+            right: clean(self.right),
+        }
+    }
+
+    // Pan from -1.0 (hard left) to 1.0 (hard right), for
+    // a mono sample on both channels becoming stereo
+    pub fn pan(self, pan: f32) -> Self {
+        let (left, right) = pan_gains(pan);
+        Self {
+            left: self.left * left,
+            right: self.right * right,
+        }
+    }
+
+    // Balance from -1.0 (hard left) to 1.0 (hard right),
+    // for a frame that's already stereo
+    pub fn balance(self, pan: f32) -> Self {
+        let (left, right) = balance_gains(pan);
+        Self {
+            left: self.left * left,
+            right: self.right * right,
+        }
+    }
+}
+
+/*
+ * Constant-power pan law: the pan position is mapped
+ * onto a quarter circle so that left² + right² = 1
+ * and perceived loudness doesn't dip as a sound
+ * moves across the stereo field.
+ */
+
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
+
+/*
+ * Balance law for stereo signals: centre passes both
+ * channels at unity, and moving off centre turns the
+ * opposite channel down until it's silent at the end.
+ * Panning a stereo signal with the constant-power law
+ * would lose 3 dB at centre every time it was applied.
+ */
+
+pub fn balance_gains(pan: f32) -> (f32, f32) {
+    let pan = pan.clamp(-1.0, 1.0);
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
+impl Add for Frame {
+    type Output = Frame;
+
+    fn add(self, other: Frame) -> Frame {
+        Frame {
+            left: self.left + other.left,
+            right: self.right + other.right,
+        }
+    }
+}
+
+impl AddAssign for Frame {
+    fn add_assign(&mut self, other: Frame) {
+        self.left += other.left;
+        self.right += other.right;
+    }
+}
+
+impl Mul<f32> for Frame {
+    type Output = Frame;
+
+    fn mul(self, gain: f32) -> Frame {
+        Frame {
+            left: self.left * gain,
+            right: self.right * gain,
+        }
+    }
+}
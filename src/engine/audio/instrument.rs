@@ -1,9 +1,203 @@
 use crate::engine::audio::Sine;
 use crate::engine::audio::*;
+use crate::types::NUM_TRACKS;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(nodes: Vec<NodeDef>) -> Patch {
+        Patch {
+            sample_rate: 48000.0,
+            pan: 0.0,
+            table: None,
+            nodes,
+            connections: vec![],
+        }
+    }
+
+    /*
+     * Modulators address effect parameters by patch
+     * node ID, the same way they address sources.
+     */
+
+    #[test]
+    fn lfo_targets_effect_param() {
+        let mut instrument = Instrument::from_patch(&patch(vec![
+            NodeDef::Sine(SineDef {}),
+            NodeDef::Distortion(DistortionDef {
+                drive: 10.0,
+                mix: 1.0,
+            }),
+            NodeDef::Lfo(LfoDef {
+                freq: 5.0,
+                depth: 5.0,
+                offset: 0.0,
+                target_node: 1,
+                target_param: param::DRIVE,
+            }),
+        ]));
+
+        instrument.note_on(69, 127);
+        let mut drives = vec![];
+        for _ in 0..48000 {
+            instrument.next();
+            drives.push(instrument.nodes.get_param(1, param::DRIVE));
+        }
+
+        assert!(drives.iter().any(|d| *d > 14.0));
+        assert!(drives.iter().any(|d| *d < 6.0));
+    }
+
+    /*
+     * A voice reset after playing a note sounds just
+     * like one newly built from the patch, with the
+     * modulated params and effect state put back.
+     */
+
+    #[test]
+    fn reset_matches_a_new_instrument() {
+        let patch = patch(vec![
+            NodeDef::Sine(SineDef {}),
+            NodeDef::Chorus(ChorusDef {
+                rate: 1.0,
+                depth: 2.0,
+                delay: 10.0,
+                feedback: 0.5,
+                mix: 0.5,
+            }),
+            NodeDef::Lfo(LfoDef {
+                freq: 5.0,
+                depth: 0.2,
+                offset: 0.0,
+                target_node: 1,
+                target_param: param::MIX,
+            }),
+            NodeDef::Adsr(AdsrDef {
+                attack: 0.01,
+                decay: 0.1,
+                sustain: 0.5,
+                release: 0.1,
+                target_node: 0,
+                target_param: param::AMPLITUDE,
+            }),
+        ]);
+        let play = |instrument: &mut Instrument| {
+            instrument.note_on(64, 80);
+            (0..4800).map(|_| instrument.next()).collect::<Vec<_>>()
+        };
+
+        let mut used = Instrument::from_patch(&patch);
+        used.note_on(69, 127);
+        for _ in 0..4800 {
+            used.next();
+        }
+        used.note_off();
+        used.reset(&patch);
+
+        assert_eq!(play(&mut used), play(&mut Instrument::from_patch(&patch)));
+    }
+
+    /*
+     * Effects run in patch order on the summed sources.
+     */
+
+    #[test]
+    fn effects_run_in_order() {
+        let crushed = |nodes| {
+            let mut instrument = Instrument::from_patch(&patch(nodes));
+            instrument.note_on(69, 127);
+            (0..480)
+                .map(|_| instrument.next().left)
+                .collect::<Vec<f32>>()
+        };
+
+        let sine = NodeDef::Sine(SineDef {});
+        let bitcrusher = NodeDef::Bitcrusher(BitcrusherDef {
+            bits: 2.0,
+            downsample: 1.0,
+            mix: 1.0,
+        });
+        let eq = NodeDef::Eq(EqDef {
+            low_freq: 200.0,
+            low_gain: 0.0,
+            mid_freq: 1000.0,
+            mid_gain: -12.0,
+            high_freq: 5000.0,
+            high_gain: 0.0,
+        });
+
+        let mut levels = crushed(vec![sine.clone(), eq.clone(), bitcrusher.clone()]);
+        levels.sort_by(f32::total_cmp);
+        levels.dedup();
+        assert!(levels.len() <= 5);
+
+        let mut levels = crushed(vec![sine, bitcrusher, eq]);
+        levels.sort_by(f32::total_cmp);
+        levels.dedup();
+        assert!(levels.len() > 5);
+    }
+}
+
+/*
+ * The nodes a modulator can target, addressed by their
+ * index in the patch. Sources generate sound and
+ * effects process it; modulator slots hold nothing a
+ * modulator can reach, so reads there return 0.0 and
+ * writes are ignored. Each slot keeps where its node
+ * is in its own list.
+ */
+
+enum Slot {
+    Source(usize),
+    Effect(usize),
+    Modulator(usize),
+}
+
+impl Slot {
+    fn index(&self) -> usize {
+        match self {
+            Slot::Source(i) | Slot::Effect(i) | Slot::Modulator(i) => *i,
+        }
+    }
+}
+
+pub struct Nodes {
+    sources: Vec<Box<dyn Source>>,
+    effects: Vec<Box<dyn Effect>>,
+    slots: Vec<Slot>,
+}
+
+impl Nodes {
+    fn new() -> Self {
+        Self {
+            sources: vec![],
+            effects: vec![],
+            slots: vec![],
+        }
+    }
+
+    pub fn get_param(&self, node: NodeId, param: ParamId) -> f32 {
+        match self.slots.get(node) {
+            Some(Slot::Source(i)) => self.sources[*i].get_param(param),
+            Some(Slot::Effect(i)) => self.effects[*i].get_param(param),
+            _ => 0.0,
+        }
+    }
+
+    pub fn set_param(&mut self, node: NodeId, param: ParamId, value: f32) {
+        match self.slots.get(node) {
+            Some(Slot::Source(i)) => self.sources[*i].set_param(param, value),
+            Some(Slot::Effect(i)) => self.effects[*i].set_param(param, value),
+            _ => {}
+        }
+    }
+}
 
 pub struct Instrument {
     sample_rate: f32,
-    sources: Vec<Box<dyn Source>>,
+    pan: f32,
+    nodes: Nodes,
     modulators: Vec<Box<dyn Modulator>>,
 }
 
@@ -11,7 +205,8 @@ impl Instrument {
     pub fn new(sample_rate: f32) -> Self {
         Self {
             sample_rate,
-            sources: vec![],
+            pan: 0.0,
+            nodes: Nodes::new(),
             modulators: vec![],
         }
     }
@@ -19,35 +214,73 @@ impl Instrument {
     pub fn from_patch(patch: &Patch) -> Self {
         let mut instrument = Instrument::new(patch.sample_rate);
 
-        let mut node_ids = Vec::with_capacity(patch.nodes.len());
-
         for node_def in &patch.nodes {
-            let id = match node_def {
+            match node_def {
                 NodeDef::Sine(_) => instrument.add_sine(),
                 NodeDef::Lfo(_) => instrument.add_lfo(),
                 NodeDef::Adsr(_) => instrument.add_adsr(),
+                NodeDef::Distortion(_) => {
+                    instrument.add_effect(Box::new(Distortion::new(patch.sample_rate)))
+                }
+                NodeDef::Bitcrusher(_) => instrument.add_effect(Box::new(Bitcrusher::new())),
+                NodeDef::Chorus(_) => {
+                    instrument.add_effect(Box::new(Chorus::new(patch.sample_rate)))
+                }
+                NodeDef::Eq(_) => instrument.add_effect(Box::new(Eq::new(patch.sample_rate))),
+                NodeDef::Compressor(_) => {
+                    instrument.add_effect(Box::new(Compressor::new(patch.sample_rate)))
+                }
             };
-            node_ids.push(id);
         }
 
+        instrument.configure(patch);
+        instrument
+    }
+
+    // Put the instrument back as from_patch built it, for
+    // a voice's next note, without building anything. The
+    // patch must have the same nodes it was built from.
+    pub fn reset(&mut self, patch: &Patch) {
+        for source in &mut self.nodes.sources {
+            source.reset();
+        }
+        for effect in &mut self.nodes.effects {
+            effect.reset();
+        }
+        for modulator in &mut self.modulators {
+            modulator.reset();
+        }
+        self.configure(patch);
+    }
+
+    // Set every node up as the patch says. Modulator
+    // targets are patch node IDs, which are also the
+    // node slots.
+    fn configure(&mut self, patch: &Patch) {
+        self.set_pan(patch.pan);
+
         for (i, node_def) in patch.nodes.iter().enumerate() {
-            let id = node_ids[i];
+            let id = self.nodes.slots[i].index();
 
             match node_def {
                 NodeDef::Lfo(def) => {
-                    let target = node_ids[def.target_node];
-                    instrument.modulators[id]
+                    self.modulators[id]
                         .downcast_mut::<Lfo>()
                         .unwrap()
-                        .configure(target, def.target_param, def.freq, def.depth, def.offset);
+                        .configure(
+                            def.target_node,
+                            def.target_param,
+                            def.freq,
+                            def.depth,
+                            def.offset,
+                        );
                 }
                 NodeDef::Adsr(def) => {
-                    let target = node_ids[def.target_node];
-                    instrument.modulators[id]
+                    self.modulators[id]
                         .downcast_mut::<Adsr>()
                         .unwrap()
                         .configure(
-                            target,
+                            def.target_node,
                             def.target_param,
                             def.attack,
                             def.decay,
@@ -55,21 +288,68 @@ impl Instrument {
                             def.release,
                         );
                 }
+                NodeDef::Distortion(def) => {
+                    self.nodes.effects[id]
+                        .downcast_mut::<Distortion>()
+                        .unwrap()
+                        .configure(def.drive, def.mix);
+                }
+                NodeDef::Bitcrusher(def) => {
+                    self.nodes.effects[id]
+                        .downcast_mut::<Bitcrusher>()
+                        .unwrap()
+                        .configure(def.bits, def.downsample, def.mix);
+                }
+                NodeDef::Chorus(def) => {
+                    self.nodes.effects[id]
+                        .downcast_mut::<Chorus>()
+                        .unwrap()
+                        .configure(def.rate, def.depth, def.delay, def.feedback, def.mix);
+                }
+                NodeDef::Eq(def) => {
+                    self.nodes.effects[id]
+                        .downcast_mut::<Eq>()
+                        .unwrap()
+                        .configure(
+                            def.low_freq,
+                            def.low_gain,
+                            def.mid_freq,
+                            def.mid_gain,
+                            def.high_freq,
+                            def.high_gain,
+                        );
+                }
+                NodeDef::Compressor(def) => {
+                    self.nodes.effects[id]
+                        .downcast_mut::<Compressor>()
+                        .unwrap()
+                        .configure(
+                            def.key_track,
+                            def.threshold,
+                            def.ratio,
+                            def.attack,
+                            def.release,
+                            def.makeup,
+                        );
+                }
                 NodeDef::Sine(_) => {}
             }
         }
-
-        for _conn in &patch.connections {
-            // e.g. route output of node A into input of node B
-            // instrument.connect(conn.from_node, conn.to_node);
-        }
-
-        instrument
     }
 
     fn add_sine(&mut self) -> NodeId {
-        let id = self.sources.len();
-        self.sources.push(Box::new(Sine::new(self.sample_rate)));
+        let id = self.nodes.sources.len();
+        self.nodes
+            .sources
+            .push(Box::new(Sine::new(self.sample_rate)));
+        self.nodes.slots.push(Slot::Source(id));
+        id
+    }
+
+    fn add_effect(&mut self, effect: Box<dyn Effect>) -> NodeId {
+        let id = self.nodes.effects.len();
+        self.nodes.effects.push(effect);
+        self.nodes.slots.push(Slot::Effect(id));
         id
     }
 
@@ -77,12 +357,14 @@ impl Instrument {
     fn add_lfo(&mut self) -> NodeId {
         let id = self.modulators.len();
         self.modulators.push(Box::new(Lfo::new(self.sample_rate)));
+        self.nodes.slots.push(Slot::Modulator(id));
         id
     }
 
     fn add_adsr(&mut self) -> NodeId {
         let id = self.modulators.len();
         self.modulators.push(Box::new(Adsr::new(self.sample_rate)));
+        self.nodes.slots.push(Slot::Modulator(id));
         id
     }
 
@@ -115,6 +397,36 @@ impl Instrument {
         }
     }
 
+    // Pan from -1.0 (hard left) to 1.0 (hard right)
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    pub fn get_pan(&self) -> f32 {
+        self.pan
+    }
+
+    // Hand each keyed effect the latest output of its key track
+    pub fn set_keys(&mut self, keys: &[Frame; NUM_TRACKS]) {
+        for effect in &mut self.nodes.effects {
+            if let Some(key) = effect
+                .key_track()
+                .and_then(|track| keys.get(track as usize))
+            {
+                effect.set_key(*key);
+            }
+        }
+    }
+
+    // The most gain reduction any effect is applying, in dB
+    pub fn gain_reduction(&self) -> f32 {
+        self.nodes
+            .effects
+            .iter()
+            .map(|effect| effect.gain_reduction())
+            .fold(0.0, f32::max)
+    }
+
     pub fn is_released(&self) -> bool {
         self.modulators.iter().all(|m| {
             m.downcast_ref::<Adsr>()
@@ -131,8 +443,20 @@ impl Instrument {
         })
     }
 
+    // Bend every source by a number of semitones
+    pub fn set_pitch(&mut self, semitones: f32) {
+        let ratio = 2.0f32.powf(semitones / 12.0);
+        for source in &mut self.nodes.sources {
+            source.set_pitch(ratio);
+        }
+    }
+
+    pub fn set_param(&mut self, node: NodeId, param: ParamId, value: f32) {
+        self.nodes.set_param(node, param, value);
+    }
+
     pub fn note_on(&mut self, note: u8, velocity: u8) {
-        for source in &mut self.sources {
+        for source in &mut self.nodes.sources {
             source.set(note, velocity);
         }
 
@@ -147,21 +471,30 @@ impl Instrument {
         }
     }
 
-    pub fn next(&mut self) -> f32 {
+    pub fn next(&mut self) -> Frame {
         // 1. Update modulators
         for modulator in &mut self.modulators {
-            modulator.tick(&mut self.sources);
+            modulator.tick(&mut self.nodes);
         }
 
-        // 2. Sum source outputs
-        let mut sum = 0.0;
-        for source in &mut self.sources {
-            sum += source.next();
+        // 2. Sum source outputs, dropping anything non-finite
+        let mut sum = Frame::SILENCE;
+        for source in &mut self.nodes.sources {
+            sum += source.next().sanitize();
         }
 
         // 3. Normalize (optional)
-        if !self.sources.is_empty() {
-            sum /= self.sources.len() as f32;
+        if !self.nodes.sources.is_empty() {
+            sum = sum * (1.0 / self.nodes.sources.len() as f32);
+        }
+
+        // 4. Place the still mono sum in the stereo field,
+        // before any insert that makes it properly stereo
+        sum = sum.pan(self.pan);
+
+        // 5. Run the insert effects in patch order
+        for effect in &mut self.nodes.effects {
+            sum = effect.process(sum).sanitize();
         }
 
         sum
@@ -1,32 +1,559 @@
+use crate::engine::NoteTarget;
 use crate::engine::audio::*;
-use crossbeam::channel::Receiver;
+use crate::types::{AUDITION_TRACK, InstrumentId, NUM_TRACKS, Note, TableId, TrackId};
+use std::mem;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MixerChannel;
+
+    fn keyed_from(key_track: TrackId) -> Patch {
+        Patch {
+            sample_rate: 44100.0,
+            pan: 0.0,
+            table: None,
+            nodes: vec![
+                NodeDef::Sine(SineDef {}),
+                NodeDef::Adsr(AdsrDef {
+                    attack: 0.01,
+                    decay: 0.1,
+                    sustain: 1.0,
+                    release: 0.1,
+                    target_node: 0,
+                    target_param: param::AMPLITUDE,
+                }),
+                NodeDef::Compressor(CompressorDef {
+                    key_track: Some(key_track),
+                    threshold: -20.0,
+                    ratio: 4.0,
+                    attack: 1.0,
+                    release: 100.0,
+                    makeup: 0.0,
+                }),
+            ],
+            connections: vec![],
+        }
+    }
+
+    /*
+     * A track keyed from another is processed after it,
+     * so it hears the key from the same sample. Otherwise
+     * tracks run in number order.
+     */
+
+    #[test]
+    fn keyed_tracks_run_after_their_key() {
+        let mut manager = InstrumentManager::new();
+        manager.add_instrument(keyed_from(3));
+        manager.add_instrument(keyed_from(0));
+        manager.note_on(2, 0, 60, 127);
+        manager.note_on(3, 1, 60, 127);
+
+        assert_eq!(manager.order, vec![0, 1, 3, 2, 4, 5, 6, 7]);
+    }
+
+    /*
+     * Tracks keyed from each other can't both go first;
+     * once everything else has run, the lower numbered
+     * one goes and hears the other a sample late.
+     */
+
+    #[test]
+    fn cycles_are_broken_by_track_number() {
+        let mut manager = InstrumentManager::new();
+        manager.add_instrument(keyed_from(1));
+        manager.add_instrument(keyed_from(0));
+        manager.note_on(0, 0, 60, 127);
+        manager.note_on(1, 1, 60, 127);
+
+        assert_eq!(manager.order, vec![2, 3, 4, 5, 6, 7, 0, 1]);
+    }
+
+    /*
+     * A new note on a track releases the one before,
+     * and instruments that don't exist are ignored.
+     */
+
+    #[test]
+    fn one_note_per_track() {
+        let mut manager = InstrumentManager::new();
+        manager.add_synth();
+
+        manager.note_on(0, 0, 60, 127);
+        assert_eq!(manager.tracks[0].note, Some(60));
+        manager.note_on(0, 0, 64, 100);
+        assert_eq!(manager.tracks[0].note, Some(64));
+
+        manager.note_on(1, 7, 60, 127);
+        assert!(manager.tracks[1].instrument.is_none());
+
+        manager.note_off(0);
+        assert_eq!(manager.tracks[0].note, None);
+    }
+
+    /*
+     * Notes played by hand sound on the audition track,
+     * leaving the song's last track playing, and are
+     * heard with every song track muted.
+     */
+
+    #[test]
+    fn audition_has_its_own_track() {
+        let mut manager = InstrumentManager::new();
+        manager.add_synth();
+        let last = NUM_TRACKS as TrackId - 1;
+        manager.note_on(last, 0, 60, 127);
+        manager.note_on(AUDITION_TRACK, 0, 64, 127);
+
+        assert_eq!(manager.tracks[last as usize].note, Some(60));
+        assert_eq!(manager.tracks[AUDITION_TRACK as usize].note, Some(64));
+        assert_eq!(manager.order.len(), NUM_TRACKS);
+
+        for track_id in 0..NUM_TRACKS as TrackId {
+            manager
+                .mixer()
+                .set_mute(MixerChannel::Track(track_id), true);
+        }
+        let loudest = (0..4410)
+            .map(|_| manager.next().left.abs())
+            .fold(0.0, f32::max);
+        assert!(loudest > 0.1);
+    }
+
+    /*
+     * Compressor gain reduction reaches the meters.
+     */
+
+    #[test]
+    fn gain_reduction_is_metered() {
+        let mut manager = InstrumentManager::new();
+        manager.add_instrument(keyed_from(1));
+        manager.add_synth();
+        manager.note_on(0, 0, 60, 127);
+        manager.note_on(1, 1, 60, 127);
+
+        for _ in 0..4410 {
+            manager.next();
+        }
+
+        assert!(manager.meters().gain_reduction(0) > 0.0);
+        assert_eq!(manager.meters().gain_reduction(1), 0.0);
+    }
+
+    /*
+     * Changing a track's instrument uses the synth
+     * already built for it, and the last instrument's
+     * note rings on through its release.
+     */
+
+    #[test]
+    fn instrument_changes_let_the_last_note_ring() {
+        let mut manager = InstrumentManager::new();
+        manager.add_synth();
+        manager.add_synth();
+        manager.note_on(0, 0, 60, 127);
+        manager.note_on(0, 1, 64, 127);
+        for _ in 0..441 {
+            manager.next();
+        }
+
+        let track = &manager.tracks[0];
+        assert_eq!(track.synths.len(), 2);
+        assert!(track.synths[0].is_sounding());
+        assert!(track.synths[1].is_sounding());
+    }
+
+    /*
+     * Editing an instrument's node is heard from the
+     * next note. The note already playing carries on
+     * with the patch it started with.
+     */
+
+    #[test]
+    fn node_edits_take_effect_on_the_next_note() {
+        let loudest = |manager: &mut InstrumentManager| {
+            (0..4410)
+                .map(|_| manager.next().left.abs())
+                .fold(0.0, f32::max)
+        };
+
+        let mut manager = InstrumentManager::new();
+        manager.add_synth();
+        manager.note_on(0, 0, 60, 127);
+        let mut patch = manager.get_instrument(0).unwrap();
+        patch.nodes[2] = NodeDef::Adsr(AdsrDef {
+            attack: 0.01,
+            decay: 0.01,
+            sustain: 1.0,
+            release: 0.1,
+            target_node: 0,
+            target_param: param::AMPLITUDE,
+        });
+        let synths = InstrumentManager::build_synths(&patch);
+        manager.set_instrument_patch(0, patch, synths);
+
+        // Decays away with no sustain, as before
+        for _ in 0..44100 {
+            manager.next();
+        }
+        assert!(loudest(&mut manager) < 0.001);
+
+        manager.note_on(0, 0, 60, 127);
+        for _ in 0..44100 {
+            manager.next();
+        }
+        assert!(loudest(&mut manager) > 0.1);
+
+        let patch = manager.get_instrument(0).unwrap();
+        assert!(matches!(
+            patch.nodes[2],
+            NodeDef::Adsr(AdsrDef { sustain: 1.0, .. })
+        ));
+        assert!(manager.get_instrument(1).is_none());
+    }
+
+    /*
+     * A whole patch can be swapped in for an instrument
+     * while a track plays it, taking its sample rate
+     * from the manager. The synth playing is kept to
+     * finish its note and the rest are handed back. A
+     * new key track reorders the tracks straight away.
+     */
+
+    #[test]
+    fn patches_are_swapped_whole() {
+        let mut manager = InstrumentManager::new();
+        manager.add_synth();
+        manager.add_synth();
+        manager.note_on(2, 0, 60, 127);
+
+        let mut patch = keyed_from(3);
+        let synths = InstrumentManager::build_synths(&patch);
+        patch.sample_rate = 8000.0;
+        let replaced = manager.set_instrument_patch(0, patch, synths);
+        manager.note_on(3, 1, 60, 127);
+
+        assert_eq!(replaced.len(), NUM_TRACKS);
+        assert!(manager.tracks[2].retired.is_some());
+
+        assert_eq!(manager.order, vec![0, 1, 3, 2, 4, 5, 6, 7]);
+        let patch = manager.get_instrument(0).unwrap();
+        assert_eq!(patch.nodes.len(), 3);
+        assert_eq!(patch.sample_rate, manager.get_sample_rate());
+    }
+}
+
+/*
+ * Instruments are patches. Each track plays one
+ * instrument at a time, chosen by the sequencer's
+ * note_on, through its own Synth so release tails
+ * can overlap the next note. Every track has a Synth
+ * for every instrument, built when the instrument
+ * is added or its patch changes, so nothing is built
+ * in the audio callback when a track changes
+ * instrument.
+ *
+ * Tracks are processed in a fixed order worked out
+ * whenever a track changes instrument: a track whose
+ * effects are keyed from another track comes after
+ * it, otherwise lower numbers go first. Where tracks
+ * key each other round in a loop, the lowest numbered
+ * one left goes next and hears the rest one sample
+ * late.
+ *
+ * Past the song's tracks is one more for notes
+ * played by hand, which runs last and goes straight
+ * to the master bus without a mixer channel.
+ */
+
+struct Track {
+    instrument: Option<InstrumentId>,
+    // One for each instrument, by ID
+    synths: Vec<Synth>,
+    // A synth swapped out by a patch change, left to
+    // finish the notes it was playing
+    retired: Option<Synth>,
+    note: Option<Note>,
+    volume: f32,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            instrument: None,
+            synths: vec![],
+            retired: None,
+            note: None,
+            volume: 1.0,
+        }
+    }
+}
+
+impl Track {
+    // The synth for the instrument the track is playing
+    fn synth(&mut self) -> Option<&mut Synth> {
+        self.instrument
+            .and_then(|id| self.synths.get_mut(id as usize))
+    }
+
+    // Every synth that may still be sounding
+    fn all_synths(&mut self) -> impl Iterator<Item = &mut Synth> {
+        self.synths.iter_mut().chain(&mut self.retired)
+    }
+
+    // Let go of the note playing, leaving its release
+    fn release(&mut self) {
+        if let Some(note) = self.note.take() {
+            if let Some(synth) = self.synth() {
+                synth.note_off(note);
+            }
+            if let Some(retired) = &mut self.retired {
+                retired.note_off(note);
+            }
+        }
+    }
+
+    // The track's next sample, and the most gain
+    // reduction any of its synths is applying
+    fn next_sample(&mut self, keys: &[Frame; NUM_TRACKS]) -> (Frame, f32) {
+        let mut output = Frame::SILENCE;
+        let mut gain_reduction = 0.0;
+        for synth in self.synths.iter_mut().chain(&mut self.retired) {
+            synth.set_keys(keys);
+            output += synth.next_sample();
+            gain_reduction = f32::max(gain_reduction, synth.gain_reduction());
+        }
+        (output * self.volume, gain_reduction)
+    }
+}
 
 pub struct InstrumentManager {
     sample_rate: f32,
-    instruments: Vec<Instrument>,
+    instruments: Vec<Patch>,
+    tracks: [Track; Self::ALL_TRACKS],
+    mixer: Mixer,
+    master_bus: MasterBus,
+    meters: Arc<MixerMeters>,
+    order: Vec<TrackId>,
+    // Latest output of each track, for keyed effects
+    keys: [Frame; NUM_TRACKS],
 }
 
 impl InstrumentManager {
+    const POLYPHONY: usize = 4;
+    // The song's tracks and the audition track
+    const ALL_TRACKS: usize = NUM_TRACKS + 1;
+
     pub fn new() -> Self {
+        let mixer = Mixer::new(44100.0);
+        let meters = mixer.meters();
+
         Self {
             sample_rate: 44100.00,
             instruments: vec![],
+            tracks: Default::default(),
+            mixer,
+            master_bus: MasterBus::new(44100.0),
+            meters,
+            order: (0..NUM_TRACKS as TrackId).collect(),
+            keys: [Frame::SILENCE; NUM_TRACKS],
         }
     }
 
+    // Playing notes are dropped, since their synths are rebuilt
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
+        for patch in &mut self.instruments {
+            patch.sample_rate = sample_rate;
+        }
+        for track in &mut self.tracks {
+            *track = Track {
+                synths: self
+                    .instruments
+                    .iter()
+                    .map(|patch| Synth::new(patch.clone(), Self::POLYPHONY))
+                    .collect(),
+                ..Track::default()
+            };
+        }
+        self.update_order();
+        self.mixer.set_sample_rate(sample_rate);
+        self.master_bus.set_sample_rate(sample_rate);
+    }
+
+    pub fn get_sample_rate(&self) -> f32 {
+        self.sample_rate
     }
 
-    pub fn note_on(&mut self) {
-        for instrument in &mut self.instruments {
-            instrument.note_on(70, 127);
+    pub fn mixer(&mut self) -> &mut Mixer {
+        &mut self.mixer
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.mixer.set_bpm(bpm);
+    }
+
+    pub fn master_bus(&mut self) -> &mut MasterBus {
+        &mut self.master_bus
+    }
+
+    pub fn meters(&self) -> Arc<MixerMeters> {
+        self.meters.clone()
+    }
+
+    // Start a note on a track, releasing whatever it was playing
+    pub fn note_on(
+        &mut self,
+        track_id: TrackId,
+        instrument_id: InstrumentId,
+        note: Note,
+        velocity: u8,
+    ) {
+        if instrument_id as usize >= self.instruments.len() {
+            return;
+        }
+        let Some(track) = self.tracks.get_mut(track_id as usize) else {
+            return;
+        };
+
+        track.release();
+        if track.instrument != Some(instrument_id) {
+            track.instrument = Some(instrument_id);
+            self.update_order();
+        }
+
+        let track = &mut self.tracks[track_id as usize];
+        if let Some(synth) = track.synth() {
+            synth.note_on(note, velocity);
+            track.note = Some(note);
         }
     }
 
+    pub fn note_off(&mut self, track_id: TrackId) {
+        if let Some(track) = self.tracks.get_mut(track_id as usize) {
+            track.release();
+        }
+    }
+
+    // Silence a track straight away, skipping the release
+    pub fn kill(&mut self, track_id: TrackId) {
+        if let Some(track) = self.tracks.get_mut(track_id as usize) {
+            track.note = None;
+            for synth in track.all_synths() {
+                synth.kill();
+            }
+        }
+    }
+
+    // Bend a track's pitch by a number of semitones
+    pub fn set_pitch(&mut self, track_id: TrackId, semitones: f32) {
+        if let Some(track) = self.tracks.get_mut(track_id as usize) {
+            for synth in track.all_synths() {
+                synth.set_pitch(semitones);
+            }
+        }
+    }
+
+    // Volume of a track from 0.0 to 1.0, on top of velocity
+    pub fn set_volume(&mut self, track_id: TrackId, volume: f32) {
+        if let Some(track) = self.tracks.get_mut(track_id as usize) {
+            track.volume = volume.clamp(0.0, 1.0);
+        }
+    }
+
+    // Set one of the params of a track's instrument, counting
+    // through the patch's params in node order, to an amount
+    // from 0.0 to 1.0 across its range
+    pub fn set_param(&mut self, track_id: TrackId, index: usize, amount: f32) {
+        let Some(track) = self.tracks.get_mut(track_id as usize) else {
+            return;
+        };
+        let Some(patch) = track
+            .instrument
+            .and_then(|id| self.instruments.get(id as usize))
+        else {
+            return;
+        };
+
+        if let (Some((node, range)), Some(synth)) = (patch.params().nth(index), track.synth()) {
+            synth.set_param(node, range.param, range.scale(amount));
+        }
+    }
+
+    pub fn set_instrument_pan(&mut self, instrument_id: InstrumentId, pan: f32) {
+        if let Some(patch) = self.instruments.get_mut(instrument_id as usize) {
+            patch.pan = pan.clamp(-1.0, 1.0);
+        }
+
+        for track in &mut self.tracks {
+            if let Some(synth) = track.synths.get_mut(instrument_id as usize) {
+                synth.set_pan(pan);
+            }
+        }
+    }
+
+    pub fn set_instrument_table(&mut self, instrument_id: InstrumentId, table_id: Option<TableId>) {
+        if let Some(patch) = self.instruments.get_mut(instrument_id as usize) {
+            patch.table = table_id;
+        }
+    }
+
+    pub fn get_instrument(&self, instrument_id: InstrumentId) -> Option<Patch> {
+        self.instruments.get(instrument_id as usize).cloned()
+    }
+
+    // A synth for each track, built from a patch away from
+    // the audio callback, to hand to set_instrument_patch
+    pub fn build_synths(patch: &Patch) -> Vec<Synth> {
+        (0..Self::ALL_TRACKS)
+            .map(|_| Synth::new(patch.clone(), Self::POLYPHONY))
+            .collect()
+    }
+
+    // Replace an instrument's whole patch and its synths,
+    // handing back the synths replaced so they can be
+    // dropped once the lock is let go. Sounding notes
+    // finish with the old patch.
+    pub fn set_instrument_patch(
+        &mut self,
+        instrument_id: InstrumentId,
+        mut patch: Patch,
+        synths: Vec<Synth>,
+    ) -> Vec<Synth> {
+        let index = instrument_id as usize;
+        let Some(slot) = self.instruments.get_mut(index) else {
+            return synths;
+        };
+        patch.sample_rate = self.sample_rate;
+        *slot = patch;
+
+        let mut replaced = Vec::with_capacity(synths.len());
+        for (track, synth) in self.tracks.iter_mut().zip(synths) {
+            let old = mem::replace(&mut track.synths[index], synth);
+            if old.is_sounding() {
+                replaced.extend(track.retired.replace(old));
+            } else {
+                replaced.push(old);
+            }
+        }
+        // A compressor may be keyed from a different track
+        self.update_order();
+        replaced
+    }
+
+    pub fn instrument_table(&self, instrument_id: InstrumentId) -> Option<TableId> {
+        self.instruments
+            .get(instrument_id as usize)
+            .and_then(|patch| patch.table)
+    }
+
     pub fn add_synth(&mut self) {
         let patch = Patch {
             sample_rate: self.sample_rate,
+            pan: 0.0,
+            table: None,
             nodes: vec![
                 NodeDef::Sine(SineDef {}),
                 NodeDef::Lfo(LfoDef {
@@ -48,21 +575,102 @@ impl InstrumentManager {
             connections: vec![],
         };
 
-        let instrument = Instrument::from_patch(&patch);
-        self.instruments.push(instrument);
+        self.add_instrument(patch);
     }
 
-    pub fn next(&mut self) -> f32 {
-        let mut mix = 0.0;
-        for instrument in &mut self.instruments {
-            mix += instrument.next();
+    // Instruments are numbered in the order they're added
+    pub fn add_instrument(&mut self, patch: Patch) {
+        for (track, synth) in self.tracks.iter_mut().zip(Self::build_synths(&patch)) {
+            track.synths.push(synth);
+        }
+        self.instruments.push(patch);
+    }
+
+    fn update_order(&mut self) {
+        // keyed_from[t][k] is true when track t is keyed from track k
+        let mut keyed_from = [[false; NUM_TRACKS]; NUM_TRACKS];
+        for (i, track) in self.tracks[..NUM_TRACKS].iter().enumerate() {
+            let Some(patch) = track
+                .instrument
+                .and_then(|id| self.instruments.get(id as usize))
+            else {
+                continue;
+            };
+
+            for key in patch.key_tracks() {
+                if (key as usize) < NUM_TRACKS {
+                    keyed_from[i][key as usize] = true;
+                }
+            }
+        }
+
+        let mut done = [false; NUM_TRACKS];
+        self.order.clear();
+        while self.order.len() < NUM_TRACKS {
+            let ready = (0..NUM_TRACKS).find(|&track| {
+                !done[track]
+                    && (0..NUM_TRACKS)
+                        .all(|key| key == track || !keyed_from[track][key] || done[key])
+            });
+            let next = ready
+                .or_else(|| (0..NUM_TRACKS).find(|&track| !done[track]))
+                .unwrap();
+
+            done[next] = true;
+            self.order.push(next as TrackId);
         }
+    }
 
-        let count = self.instruments.len();
-        if count != 0 {
-            mix /= count as f32;
+    pub fn next(&mut self) -> Frame {
+        let mut outputs = [Frame::SILENCE; NUM_TRACKS];
+        for &track in &self.order {
+            let index = track as usize;
+            let gain_reduction;
+            (outputs[index], gain_reduction) = self.tracks[index].next_sample(&self.keys);
+
+            self.keys[index] = outputs[index];
+            self.meters.set_gain_reduction(track, gain_reduction);
         }
 
-        mix
+        let (audition, _) = self.tracks[AUDITION_TRACK as usize].next_sample(&self.keys);
+
+        let mix = self.mixer.process(&outputs) + audition;
+        self.master_bus.process(mix)
+    }
+}
+
+impl NoteTarget for InstrumentManager {
+    fn note_on(
+        &mut self,
+        track_id: TrackId,
+        instrument_id: InstrumentId,
+        note: Note,
+        velocity: u8,
+    ) {
+        InstrumentManager::note_on(self, track_id, instrument_id, note, velocity);
+    }
+
+    fn note_off(&mut self, track_id: TrackId) {
+        InstrumentManager::note_off(self, track_id);
+    }
+
+    fn kill(&mut self, track_id: TrackId) {
+        InstrumentManager::kill(self, track_id);
+    }
+
+    fn set_pitch(&mut self, track_id: TrackId, semitones: f32) {
+        InstrumentManager::set_pitch(self, track_id, semitones);
+    }
+
+    fn set_volume(&mut self, track_id: TrackId, volume: f32) {
+        InstrumentManager::set_volume(self, track_id, volume);
+    }
+
+    fn set_param(&mut self, track_id: TrackId, index: usize, amount: f32) {
+        InstrumentManager::set_param(self, track_id, index, amount);
+    }
+
+    fn instrument_table(&self, instrument_id: InstrumentId) -> Option<TableId> {
+        InstrumentManager::instrument_table(self, instrument_id)
     }
 }
@@ -0,0 +1,298 @@
+use crate::engine::audio::{Frame, db_to_gain};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /*
+     * A sine far louder than the ceiling must never
+     * come out above it, including its first cycle.
+     */
+
+    #[test]
+    fn limiter_holds_ceiling() {
+        let mut bus = MasterBus::new(SAMPLE_RATE);
+        bus.set_ceiling_db(-6.0);
+        let ceiling = db_to_gain(-6.0);
+
+        for n in 0..SAMPLE_RATE as usize {
+            let x = 4.0 * (2.0 * PI * 220.0 * n as f32 / SAMPLE_RATE).sin();
+            let out = bus.process(Frame::mono(x));
+            assert!(out.left.abs() <= ceiling + 1e-4, "sample {n}: {}", out.left);
+            assert!(out.right.abs() <= ceiling + 1e-4);
+        }
+    }
+
+    /*
+     * Quiet signals pass through untouched,
+     * just delayed by the look-ahead.
+     */
+
+    #[test]
+    fn limiter_is_transparent_below_ceiling() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        let delay = limiter.latency();
+
+        let input: Vec<f32> = (0..2000).map(|n| 0.3 * (n as f32 * 0.05).sin()).collect();
+        let output: Vec<f32> = input
+            .iter()
+            .map(|x| limiter.process(Frame::mono(*x)).left)
+            .collect();
+
+        for n in delay..input.len() {
+            assert!((output[n] - input[n - delay]).abs() < 1e-6);
+        }
+    }
+
+    /*
+     * A constant offset should be removed.
+     */
+
+    #[test]
+    fn dc_is_blocked() {
+        let mut blocker = DcBlocker::new(SAMPLE_RATE);
+        let mut out = Frame::SILENCE;
+        for _ in 0..SAMPLE_RATE as usize {
+            out = blocker.process(Frame::mono(0.5));
+        }
+        assert!(out.left.abs() < 1e-3);
+        assert!(out.right.abs() < 1e-3);
+    }
+
+    /*
+     * NaN and infinity never reach the output, and
+     * don't leave the bus stuck producing garbage.
+     */
+
+    #[test]
+    fn non_finite_samples_are_caught() {
+        let mut bus = MasterBus::new(SAMPLE_RATE);
+
+        let out = bus.process(Frame::new(f32::NAN, f32::INFINITY));
+        assert!(out.left.is_finite() && out.right.is_finite());
+
+        for _ in 0..1000 {
+            let out = bus.process(Frame::new(f32::NEG_INFINITY, 0.1));
+            assert!(out.left.is_finite() && out.right.is_finite());
+            assert!(out.left.abs() <= 1.0 && out.right.abs() <= 1.0);
+        }
+
+        let mut out = Frame::SILENCE;
+        for _ in 0..1000 {
+            out = bus.process(Frame::mono(0.1));
+        }
+        assert!(out.left.is_finite() && out.left.abs() > 0.0);
+    }
+}
+
+/*
+ * Master bus processing, applied to the mixer output
+ * just before it goes to the device:
+ *
+ *  1. Anything non-finite is replaced with silence.
+ *  2. A DC blocker removes any constant offset.
+ *  3. A look-ahead brickwall limiter keeps peaks
+ *     at or below the ceiling.
+ *  4. A hard clamp to ±1.0 as a last line of defence.
+ */
+
+pub struct MasterBus {
+    dc_blocker: DcBlocker,
+    limiter: Limiter,
+}
+
+impl MasterBus {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            dc_blocker: DcBlocker::new(sample_rate),
+            limiter: Limiter::new(sample_rate),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let ceiling_db = self.limiter.ceiling_db();
+        *self = Self::new(sample_rate);
+        self.set_ceiling_db(ceiling_db);
+    }
+
+    pub fn set_ceiling_db(&mut self, ceiling_db: f32) {
+        self.limiter.set_ceiling_db(ceiling_db);
+    }
+
+    pub fn ceiling_db(&self) -> f32 {
+        self.limiter.ceiling_db()
+    }
+
+    pub fn process(&mut self, input: Frame) -> Frame {
+        let input = input.sanitize();
+        let blocked = self.dc_blocker.process(input);
+        let limited = self.limiter.process(blocked);
+        safety_clamp(limited)
+    }
+}
+
+fn safety_clamp(frame: Frame) -> Frame {
+    let frame = frame.sanitize();
+    Frame::new(frame.left.clamp(-1.0, 1.0), frame.right.clamp(-1.0, 1.0))
+}
+
+/*
+ * One-pole high-pass filter:
+ * y[n] = x[n] - x[n-1] + r * y[n-1]
+ */
+
+pub struct DcBlocker {
+    r: f32,
+    last_input: Frame,
+    last_output: Frame,
+}
+
+impl DcBlocker {
+    const CUTOFF: f32 = 10.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            r: 1.0 - 2.0 * PI * Self::CUTOFF / sample_rate,
+            last_input: Frame::SILENCE,
+            last_output: Frame::SILENCE,
+        }
+    }
+
+    pub fn process(&mut self, input: Frame) -> Frame {
+        let output = Frame::new(
+            input.left - self.last_input.left + self.r * self.last_output.left,
+            input.right - self.last_input.right + self.r * self.last_output.right,
+        );
+        self.last_input = input;
+        self.last_output = output;
+        output
+    }
+}
+
+/*
+ * Look-ahead brickwall limiter.
+ *
+ * For every incoming frame we work out the gain that
+ * would bring it down to the ceiling. The minimum of
+ * that gain over the look-ahead window is smoothed
+ * with a moving average of the same length, so by the
+ * time a frame leaves the delay line the gain has
+ * ramped all the way down to what it needs. Release
+ * is a slower exponential recovery.
+ *
+ * All buffers are allocated up front; processing
+ * doesn't allocate.
+ */
+
+pub struct Limiter {
+    ceiling_db: f32,
+    ceiling: f32,
+    lookahead: usize,
+    delay: VecDeque<Frame>,
+    // (sample index, target gain) with increasing gains
+    window_min: VecDeque<(u64, f32)>,
+    averaging: VecDeque<f32>,
+    average_sum: f64,
+    gain: f32,
+    release: f32,
+    index: u64,
+}
+
+impl Limiter {
+    const LOOKAHEAD_TIME: f32 = 0.005;
+    const RELEASE_TIME: f32 = 0.1;
+    pub const DEFAULT_CEILING_DB: f32 = -1.0;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let lookahead = ((Self::LOOKAHEAD_TIME * sample_rate) as usize).max(1);
+
+        let mut delay = VecDeque::with_capacity(lookahead);
+        delay.extend(std::iter::repeat_n(Frame::SILENCE, lookahead - 1));
+
+        let mut averaging = VecDeque::with_capacity(lookahead);
+        averaging.extend(std::iter::repeat_n(1.0, lookahead));
+
+        Self {
+            ceiling_db: Self::DEFAULT_CEILING_DB,
+            ceiling: db_to_gain(Self::DEFAULT_CEILING_DB),
+            lookahead,
+            delay,
+            window_min: VecDeque::with_capacity(lookahead + 1),
+            averaging,
+            average_sum: lookahead as f64,
+            gain: 1.0,
+            release: 1.0 - (-1.0 / (Self::RELEASE_TIME * sample_rate)).exp(),
+            index: 0,
+        }
+    }
+
+    pub fn set_ceiling_db(&mut self, ceiling_db: f32) {
+        self.ceiling_db = ceiling_db.min(0.0);
+        self.ceiling = db_to_gain(self.ceiling_db);
+    }
+
+    pub fn ceiling_db(&self) -> f32 {
+        self.ceiling_db
+    }
+
+    // Delay, in samples, between input and output
+    pub fn latency(&self) -> usize {
+        self.lookahead - 1
+    }
+
+    pub fn process(&mut self, input: Frame) -> Frame {
+        let peak = input.left.abs().max(input.right.abs());
+        let target = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+
+        // Sliding minimum of the target gain over the window
+        while let Some(&(_, gain)) = self.window_min.back() {
+            if gain >= target {
+                self.window_min.pop_back();
+            } else {
+                break;
+            }
+        }
+        self.window_min.push_back((self.index, target));
+        while let Some(&(index, _)) = self.window_min.front() {
+            if index + (self.lookahead as u64) <= self.index {
+                self.window_min.pop_front();
+            } else {
+                break;
+            }
+        }
+        let window_min = self
+            .window_min
+            .front()
+            .map(|&(_, gain)| gain)
+            .unwrap_or(1.0);
+        self.index += 1;
+
+        // Moving average of the minimum gives a smooth attack
+        if let Some(oldest) = self.averaging.pop_front() {
+            self.average_sum -= oldest as f64;
+        }
+        self.averaging.push_back(window_min);
+        self.average_sum += window_min as f64;
+        let attack = (self.average_sum / self.lookahead as f64) as f32;
+
+        // Never above the attack curve; recover slowly
+        self.gain = if attack < self.gain {
+            attack
+        } else {
+            self.gain + (attack - self.gain) * self.release
+        };
+
+        self.delay.push_back(input);
+        let delayed = self.delay.pop_front().unwrap_or(Frame::SILENCE);
+
+        delayed * self.gain
+    }
+}
@@ -0,0 +1,507 @@
+use crate::engine::audio::{Frame, Reverb, StereoDelay};
+use crate::types::{
+    DelaySettings, MixerChannel, NUM_TRACKS, ReverbSettings, SendBus, TrackId, TrackSends,
+};
+use atomic_float::AtomicF32;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NoteDivision;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn tracks_with(track: usize, frame: Frame) -> [Frame; NUM_TRACKS] {
+        let mut tracks = [Frame::SILENCE; NUM_TRACKS];
+        tracks[track] = frame;
+        tracks
+    }
+
+    /*
+     * Adding tracks shouldn't make the others quieter:
+     * two identical tracks should be twice as loud as one.
+     */
+
+    #[test]
+    fn tracks_are_summed() {
+        let mut mixer = Mixer::new(44100.0);
+
+        let one = mixer.process(&tracks_with(0, Frame::mono(0.25)));
+
+        let mut tracks = tracks_with(0, Frame::mono(0.25));
+        tracks[1] = Frame::mono(0.25);
+        let two = mixer.process(&tracks);
+
+        assert!((two.left - 2.0 * one.left).abs() < 1e-5);
+        assert!((two.right - 2.0 * one.right).abs() < 1e-5);
+    }
+
+    /*
+     * Muted tracks are silent; soloing a track silences
+     * every track that isn't soloed.
+     */
+
+    #[test]
+    fn mute_and_solo() {
+        let mut mixer = Mixer::new(44100.0);
+        let mut tracks = tracks_with(0, Frame::mono(0.5));
+        tracks[1] = Frame::mono(0.25);
+
+        mixer.set_mute(MixerChannel::Track(0), true);
+        mixer.settle();
+        let muted = mixer.process(&tracks);
+        let expected = mixer.process(&tracks_with(1, Frame::mono(0.25)));
+        assert!((muted.left - expected.left).abs() < 1e-5);
+
+        mixer.set_mute(MixerChannel::Track(0), false);
+        mixer.set_solo(0, true);
+        mixer.settle();
+        let soloed = mixer.process(&tracks);
+        let expected = mixer.process(&tracks_with(0, Frame::mono(0.5)));
+        assert!((soloed.left - expected.left).abs() < 1e-5);
+    }
+
+    /*
+     * -6 dB on a track should roughly halve its level.
+     */
+
+    #[test]
+    fn gain_in_db() {
+        let mut mixer = Mixer::new(44100.0);
+        let unity = mixer.process(&tracks_with(2, Frame::mono(1.0)));
+
+        mixer.set_gain_db(MixerChannel::Track(2), -6.0);
+        mixer.settle();
+        let quieter = mixer.process(&tracks_with(2, Frame::mono(1.0)));
+
+        assert!((quieter.left / unity.left - 0.501).abs() < 1e-3);
+    }
+
+    /*
+     * Meters are readable from another thread through
+     * the shared handle.
+     */
+
+    #[test]
+    fn meters_follow_output() {
+        let mut mixer = Mixer::new(44100.0);
+        let meters = mixer.meters();
+
+        mixer.process(&tracks_with(3, Frame::new(0.5, -0.25)));
+
+        // Metered after the fader; a centred balance is unity
+        let (left, right) = meters.track(3);
+        assert!((left - 0.5).abs() < 1e-5);
+        assert!((right - 0.25).abs() < 1e-5);
+        assert_eq!(meters.track(4), (0.0, 0.0));
+        assert!(meters.master().0 > 0.0);
+    }
+
+    /*
+     * RMS meters settle on a steady sine's RMS level,
+     * 3 dB under its peak.
+     */
+
+    #[test]
+    fn rms_follows_output() {
+        let mut mixer = Mixer::new(48000.0);
+        let meters = mixer.meters();
+        for i in 0..48000 {
+            let phase = i as f32 * 440.0 / 48000.0;
+            let sample = (phase * std::f32::consts::TAU).sin();
+            mixer.process(&tracks_with(0, Frame::mono(sample)));
+        }
+
+        let (left, right) = meters.track_rms(0);
+        assert!((left - FRAC_1_SQRT_2).abs() < 0.02);
+        assert!((right - FRAC_1_SQRT_2).abs() < 0.02);
+        assert!(meters.track(0).0 > left);
+        assert_eq!(meters.track_rms(1), (0.0, 0.0));
+        assert!(meters.master_rms().0 > 0.0);
+    }
+
+    /*
+     * A track sending to the delay is heard again on the
+     * master once the echo comes round; without the send
+     * it isn't.
+     */
+
+    #[test]
+    fn sends_reach_returns() {
+        let echo_of = |send: f32| {
+            let mut mixer = Mixer::new(48000.0);
+            mixer.set_bpm(120.0);
+            mixer.set_delay(DelaySettings {
+                division: NoteDivision::Sixteenth,
+                ping_pong: false,
+                ..Default::default()
+            });
+            mixer.set_reverb(ReverbSettings {
+                return_level: 0.0,
+                ..Default::default()
+            });
+            mixer.set_send(0, SendBus::Delay, send);
+
+            mixer.process(&tracks_with(0, Frame::mono(1.0)));
+            let mut loudest: f32 = 0.0;
+            for _ in 1..7000 {
+                let out = mixer.process(&[Frame::SILENCE; NUM_TRACKS]);
+                loudest = loudest.max(out.left.abs());
+            }
+            loudest
+        };
+
+        assert!(echo_of(1.0) > 0.1);
+        assert!(echo_of(0.0) < 1e-6);
+    }
+}
+
+/*
+ * Peak and RMS levels for every strip, and the gain
+ * reduction of any compressors on each track, written
+ * by the audio thread and read by the UI. Each value
+ * is a separate atomic so neither side ever waits on
+ * the other.
+ */
+
+pub struct MixerMeters {
+    tracks: [StripMeter; NUM_TRACKS],
+    master: StripMeter,
+    gain_reduction: [AtomicF32; NUM_TRACKS],
+}
+
+#[derive(Default)]
+struct StripMeter {
+    left: AtomicF32,
+    right: AtomicF32,
+    rms_left: AtomicF32,
+    rms_right: AtomicF32,
+}
+
+impl StripMeter {
+    fn store(&self, peak: Frame, rms: Frame) {
+        self.left.store(peak.left, Ordering::Relaxed);
+        self.right.store(peak.right, Ordering::Relaxed);
+        self.rms_left.store(rms.left, Ordering::Relaxed);
+        self.rms_right.store(rms.right, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> (f32, f32) {
+        (
+            self.left.load(Ordering::Relaxed),
+            self.right.load(Ordering::Relaxed),
+        )
+    }
+
+    fn load_rms(&self) -> (f32, f32) {
+        (
+            self.rms_left.load(Ordering::Relaxed),
+            self.rms_right.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl MixerMeters {
+    fn new() -> Self {
+        Self {
+            tracks: Default::default(),
+            master: StripMeter::default(),
+            gain_reduction: Default::default(),
+        }
+    }
+
+    // Peak (left, right) level of a track, linear
+    pub fn track(&self, track_id: TrackId) -> (f32, f32) {
+        self.tracks
+            .get(track_id as usize)
+            .map(|meter| meter.load())
+            .unwrap_or((0.0, 0.0))
+    }
+
+    // Peak (left, right) level of the master, linear
+    pub fn master(&self) -> (f32, f32) {
+        self.master.load()
+    }
+
+    // RMS (left, right) level of a track, linear
+    pub fn track_rms(&self, track_id: TrackId) -> (f32, f32) {
+        self.tracks
+            .get(track_id as usize)
+            .map(|meter| meter.load_rms())
+            .unwrap_or((0.0, 0.0))
+    }
+
+    // RMS (left, right) level of the master, linear
+    pub fn master_rms(&self) -> (f32, f32) {
+        self.master.load_rms()
+    }
+
+    // Gain reduction on a track in dB, 0.0 when nothing is compressing
+    pub fn gain_reduction(&self, track_id: TrackId) -> f32 {
+        self.gain_reduction
+            .get(track_id as usize)
+            .map(|meter| meter.load(Ordering::Relaxed))
+            .unwrap_or(0.0)
+    }
+
+    pub fn set_gain_reduction(&self, track_id: TrackId, reduction: f32) {
+        if let Some(meter) = self.gain_reduction.get(track_id as usize) {
+            meter.store(reduction, Ordering::Relaxed);
+        }
+    }
+}
+
+/*
+ * A single channel strip: gain, pan, mute and solo.
+ * Gain changes are smoothed to avoid zipper noise.
+ * What reaches a strip is already stereo, so its pan
+ * is a balance control.
+ */
+
+pub struct ChannelStrip {
+    gain_db: f32,
+    target_gain: f32,
+    gain: f32,
+    pan: f32,
+    mute: bool,
+    solo: bool,
+    sends: TrackSends,
+    peak: Frame,
+    // Running mean of the squared output, for RMS
+    mean_square: Frame,
+}
+
+impl ChannelStrip {
+    pub const MIN_GAIN_DB: f32 = -96.0;
+    pub const MAX_GAIN_DB: f32 = 12.0;
+
+    fn new() -> Self {
+        Self {
+            gain_db: 0.0,
+            target_gain: 1.0,
+            gain: 1.0,
+            pan: 0.0,
+            mute: false,
+            solo: false,
+            sends: TrackSends::default(),
+            peak: Frame::SILENCE,
+            mean_square: Frame::SILENCE,
+        }
+    }
+
+    fn set_gain_db(&mut self, gain_db: f32) {
+        self.gain_db = gain_db.clamp(Self::MIN_GAIN_DB, Self::MAX_GAIN_DB);
+        self.target_gain = db_to_gain(self.gain_db);
+    }
+
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.mute
+    }
+
+    pub fn is_soloed(&self) -> bool {
+        self.solo
+    }
+
+    pub fn sends(&self) -> TrackSends {
+        self.sends
+    }
+
+    fn process(&mut self, input: Frame, audible: bool, smoothing: f32) -> Frame {
+        let target = if audible { self.target_gain } else { 0.0 };
+        self.gain += (target - self.gain) * smoothing;
+
+        (input * self.gain).balance(self.pan)
+    }
+
+    fn meter(&mut self, output: Frame, decay: f32, averaging: f32) {
+        self.peak.left = output.left.abs().max(self.peak.left * decay);
+        self.peak.right = output.right.abs().max(self.peak.right * decay);
+        self.mean_square.left += (output.left * output.left - self.mean_square.left) * averaging;
+        self.mean_square.right +=
+            (output.right * output.right - self.mean_square.right) * averaging;
+    }
+
+    fn rms(&self) -> Frame {
+        Frame::new(self.mean_square.left.sqrt(), self.mean_square.right.sqrt())
+    }
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/*
+ * The mixer sums one channel strip per track
+ * into the master strip. Each track also feeds the
+ * delay and reverb buses, post-fader, and their
+ * returns are added in before the master strip.
+ */
+
+pub struct Mixer {
+    tracks: [ChannelStrip; NUM_TRACKS],
+    master: ChannelStrip,
+    delay: StereoDelay,
+    reverb: Reverb,
+    meters: Arc<MixerMeters>,
+    smoothing: f32,
+    peak_decay: f32,
+    rms_averaging: f32,
+}
+
+impl Mixer {
+    // Time for a gain change to mostly settle
+    const SMOOTHING_TIME: f32 = 0.01;
+    // Time for a peak meter to fall by 60 dB
+    const PEAK_FALL_TIME: f32 = 1.5;
+    // Time the RMS meters average over
+    const RMS_TIME: f32 = 0.3;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let mut mixer = Self {
+            tracks: std::array::from_fn(|_| ChannelStrip::new()),
+            master: ChannelStrip::new(),
+            delay: StereoDelay::new(sample_rate),
+            reverb: Reverb::new(sample_rate),
+            meters: Arc::new(MixerMeters::new()),
+            smoothing: 0.0,
+            peak_decay: 0.0,
+            rms_averaging: 0.0,
+        };
+        mixer.set_sample_rate(sample_rate);
+        mixer
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let delay = self.delay.settings();
+        self.delay = StereoDelay::new(sample_rate);
+        self.delay.configure(delay);
+
+        let reverb = self.reverb.settings();
+        self.reverb = Reverb::new(sample_rate);
+        self.reverb.configure(reverb);
+
+        self.smoothing = 1.0 - (-1.0 / (Self::SMOOTHING_TIME * sample_rate)).exp();
+        self.peak_decay = db_to_gain(-60.0 / (Self::PEAK_FALL_TIME * sample_rate));
+        self.rms_averaging = 1.0 - (-1.0 / (Self::RMS_TIME * sample_rate)).exp();
+    }
+
+    pub fn meters(&self) -> Arc<MixerMeters> {
+        self.meters.clone()
+    }
+
+    pub fn strip(&self, channel: MixerChannel) -> Option<&ChannelStrip> {
+        match channel {
+            MixerChannel::Track(track_id) => self.tracks.get(track_id as usize),
+            MixerChannel::Master => Some(&self.master),
+        }
+    }
+
+    fn strip_mut(&mut self, channel: MixerChannel) -> Option<&mut ChannelStrip> {
+        match channel {
+            MixerChannel::Track(track_id) => self.tracks.get_mut(track_id as usize),
+            MixerChannel::Master => Some(&mut self.master),
+        }
+    }
+
+    pub fn set_gain_db(&mut self, channel: MixerChannel, gain_db: f32) {
+        if let Some(strip) = self.strip_mut(channel) {
+            strip.set_gain_db(gain_db);
+        }
+    }
+
+    // Pan from -1.0 (hard left) to 1.0 (hard right)
+    pub fn set_pan(&mut self, channel: MixerChannel, pan: f32) {
+        if let Some(strip) = self.strip_mut(channel) {
+            strip.pan = pan.clamp(-1.0, 1.0);
+        }
+    }
+
+    pub fn set_mute(&mut self, channel: MixerChannel, mute: bool) {
+        if let Some(strip) = self.strip_mut(channel) {
+            strip.mute = mute;
+        }
+    }
+
+    // Only tracks can be soloed
+    pub fn set_solo(&mut self, track_id: TrackId, solo: bool) {
+        if let Some(strip) = self.tracks.get_mut(track_id as usize) {
+            strip.solo = solo;
+        }
+    }
+
+    // Amount, from 0.0 to 1.0, of a track going to a send bus
+    pub fn set_send(&mut self, track_id: TrackId, bus: SendBus, amount: f32) {
+        if let Some(strip) = self.tracks.get_mut(track_id as usize) {
+            strip.sends.set(bus, amount);
+        }
+    }
+
+    pub fn set_delay(&mut self, settings: DelaySettings) {
+        self.delay.configure(settings);
+    }
+
+    pub fn set_reverb(&mut self, settings: ReverbSettings) {
+        self.reverb.configure(settings);
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.delay.set_bpm(bpm);
+    }
+
+    // Jump straight to the target gains, skipping the smoothing.
+    #[cfg(test)]
+    fn settle(&mut self) {
+        let any_solo = self.tracks.iter().any(|strip| strip.solo);
+        for strip in &mut self.tracks {
+            let audible = !strip.mute && (!any_solo || strip.solo);
+            strip.gain = if audible { strip.target_gain } else { 0.0 };
+        }
+        self.master.gain = if self.master.mute {
+            0.0
+        } else {
+            self.master.target_gain
+        };
+    }
+
+    pub fn process(&mut self, tracks: &[Frame; NUM_TRACKS]) -> Frame {
+        let any_solo = self.tracks.iter().any(|strip| strip.solo);
+
+        let mut mix = Frame::SILENCE;
+        let mut delay_bus = Frame::SILENCE;
+        let mut reverb_bus = Frame::SILENCE;
+        for (strip, input) in self.tracks.iter_mut().zip(tracks) {
+            let audible = !strip.mute && (!any_solo || strip.solo);
+            let output = strip.process(*input, audible, self.smoothing);
+            strip.meter(output, self.peak_decay, self.rms_averaging);
+            mix += output;
+            delay_bus += output * strip.sends.delay;
+            reverb_bus += output * strip.sends.reverb;
+        }
+
+        mix += self.delay.process(delay_bus) * self.delay.settings().return_level;
+        mix += self.reverb.process(reverb_bus) * self.reverb.settings().return_level;
+
+        let master_audible = !self.master.mute;
+        let output = self.master.process(mix, master_audible, self.smoothing);
+        self.master
+            .meter(output, self.peak_decay, self.rms_averaging);
+
+        for (meter, strip) in self.meters.tracks.iter().zip(&self.tracks) {
+            meter.store(strip.peak, strip.rms());
+        }
+        self.meters
+            .master
+            .store(self.master.peak, self.master.rms());
+
+        output
+    }
+}
@@ -1,14 +1,22 @@
 pub mod audio;
+pub mod effects;
+pub mod frame;
 pub mod instrument;
 pub mod instrument_manager;
+pub mod master_bus;
+pub mod mixer;
 pub mod modulators;
 pub mod node;
 pub mod sources;
 pub mod synth;
 
 pub use audio::Audio;
-pub use instrument::Instrument;
+pub use effects::{Bitcrusher, Chorus, Compressor, Distortion, Effect, Eq, Reverb, StereoDelay};
+pub use frame::{Frame, balance_gains, pan_gains};
+pub use instrument::{Instrument, Nodes};
 pub use instrument_manager::InstrumentManager;
+pub use master_bus::{DcBlocker, Limiter, MasterBus};
+pub use mixer::{ChannelStrip, Mixer, MixerMeters, db_to_gain};
 pub use modulators::{Adsr, Lfo, Modulator};
 pub use node::*;
 pub use sources::{NodeId, ParamId, Sine, Source, param};
@@ -1,4 +1,4 @@
-use crate::engine::audio::{Modulator, NodeId, ParamId, Source};
+use crate::engine::audio::{Modulator, NodeId, Nodes, ParamId};
 
 #[derive(Debug, Clone, PartialEq)]
 enum EnvelopeStage {
@@ -65,12 +65,11 @@ impl Adsr {
 }
 
 impl Modulator for Adsr {
-    fn tick(&mut self, sources: &mut Vec<Box<dyn Source>>) {
+    fn tick(&mut self, nodes: &mut Nodes) {
         let dt = 1.0 / self.sample_rate;
         self.time += dt;
 
-        let source = &mut sources[self.target_id];
-        let param_value = source.get_param(self.param_id);
+        let param_value = nodes.get_param(self.target_id, self.param_id);
 
         let base_value = *self.base_value.get_or_insert(param_value);
 
@@ -101,7 +100,7 @@ impl Modulator for Adsr {
             }
         }
 
-        source.set_param(self.param_id, base_value * self.param_value);
+        nodes.set_param(self.target_id, self.param_id, base_value * self.param_value);
     }
 
     fn note_on(&mut self) {
@@ -115,6 +114,10 @@ impl Modulator for Adsr {
         self.release_start_value = self.param_value;
     }
 
+    fn reset(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
+
     fn is_active(&self) -> bool {
         self.stage != EnvelopeStage::Idle
     }
@@ -1,4 +1,4 @@
-use crate::engine::audio::{Modulator, NodeId, ParamId, Source};
+use crate::engine::audio::{Modulator, NodeId, Nodes, ParamId};
 use std::f32::consts::PI;
 
 pub struct Lfo {
@@ -43,9 +43,8 @@ impl Lfo {
 }
 
 impl Modulator for Lfo {
-    fn tick(&mut self, sources: &mut Vec<Box<dyn Source>>) {
-        let source = &mut sources[self.target_id];
-        let param_value = source.get_param(self.param_id);
+    fn tick(&mut self, nodes: &mut Nodes) {
+        let param_value = nodes.get_param(self.target_id, self.param_id);
         let base_freq = *self.base_freq.get_or_insert(param_value);
 
         self.phase += 2.0 * PI * self.freq / self.sample_rate;
@@ -56,7 +55,7 @@ impl Modulator for Lfo {
 
         let lfo_value = self.phase.sin() * self.range + self.offset;
 
-        source.set_param(self.param_id, base_freq + lfo_value);
+        nodes.set_param(self.target_id, self.param_id, base_freq + lfo_value);
     }
 
     fn note_on(&mut self) {
@@ -65,6 +64,9 @@ impl Modulator for Lfo {
     }
 
     fn note_off(&mut self) {}
+    fn reset(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
     fn is_active(&self) -> bool {
         true
     }
@@ -1,10 +1,12 @@
-use crate::engine::audio::Source;
+use crate::engine::audio::Nodes;
 use downcast_rs::{Downcast, impl_downcast};
 
 pub trait Modulator: Send + Sync + Downcast {
-    fn tick(&mut self, sources: &mut Vec<Box<dyn Source>>);
+    fn tick(&mut self, nodes: &mut Nodes);
     fn note_on(&mut self) {}
     fn note_off(&mut self) {}
+    // Back to how it was built, for a voice's next note
+    fn reset(&mut self) {}
     fn is_active(&self) -> bool {
         true
     }
@@ -1,17 +1,123 @@
 use crate::engine::audio::*;
+use crate::types::{TableId, TrackId};
 
 #[derive(Clone, Debug)]
 pub struct Patch {
     pub sample_rate: f32,
+    pub pan: f32,
+    // Table started with every note
+    pub table: Option<TableId>,
     pub nodes: Vec<NodeDef>,
+    // Not routed yet: sources are summed and effects
+    // run in patch order
     pub connections: Vec<Connection>,
 }
 
+impl Patch {
+    // Every settable param, in node order
+    pub fn params(&self) -> impl Iterator<Item = (NodeId, ParamRange)> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(node, def)| def.params().iter().map(move |range| (node, *range)))
+    }
+
+    // Tracks the effects in this patch are keyed from
+    pub fn key_tracks(&self) -> impl Iterator<Item = TrackId> + '_ {
+        self.nodes.iter().filter_map(|node| match node {
+            NodeDef::Compressor(def) => def.key_track,
+            _ => None,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum NodeDef {
     Sine(SineDef),
     Lfo(LfoDef),
     Adsr(AdsrDef),
+
+    /*
+     * Insert effects. These process the summed
+     * sources in the order they appear in the patch.
+     */
+    Distortion(DistortionDef),
+    Bitcrusher(BitcrusherDef),
+    Chorus(ChorusDef),
+    Eq(EqDef),
+    Compressor(CompressorDef),
+}
+
+/*
+ * A node parameter that can be set from outside the
+ * patch, and the range it can be set over.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamRange {
+    pub param: ParamId,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl ParamRange {
+    const fn new(param: ParamId, min: f32, max: f32) -> Self {
+        Self { param, min, max }
+    }
+
+    // A value from 0.0 to 1.0 of the way across the range
+    pub fn scale(&self, amount: f32) -> f32 {
+        self.min + amount.clamp(0.0, 1.0) * (self.max - self.min)
+    }
+}
+
+impl NodeDef {
+    // Modulators don't have params of their own, and a
+    // sine's frequency comes from the note it plays
+    pub fn params(&self) -> &'static [ParamRange] {
+        const SINE: &[ParamRange] = &[ParamRange::new(param::AMPLITUDE, 0.0, 1.0)];
+        const DISTORTION: &[ParamRange] = &[
+            ParamRange::new(param::DRIVE, 1.0, 100.0),
+            ParamRange::new(param::MIX, 0.0, 1.0),
+        ];
+        const BITCRUSHER: &[ParamRange] = &[
+            ParamRange::new(param::BITS, 1.0, 16.0),
+            ParamRange::new(param::DOWNSAMPLE, 1.0, 64.0),
+            ParamRange::new(param::MIX, 0.0, 1.0),
+        ];
+        const CHORUS: &[ParamRange] = &[
+            ParamRange::new(param::RATE, 0.0, 10.0),
+            ParamRange::new(param::DEPTH, 0.0, 20.0),
+            ParamRange::new(param::DELAY, 0.1, 30.0),
+            ParamRange::new(param::FEEDBACK, -0.95, 0.95),
+            ParamRange::new(param::MIX, 0.0, 1.0),
+        ];
+        const EQ: &[ParamRange] = &[
+            ParamRange::new(param::LOW_FREQ, 20.0, 20000.0),
+            ParamRange::new(param::LOW_GAIN, -24.0, 24.0),
+            ParamRange::new(param::MID_FREQ, 20.0, 20000.0),
+            ParamRange::new(param::MID_GAIN, -24.0, 24.0),
+            ParamRange::new(param::HIGH_FREQ, 20.0, 20000.0),
+            ParamRange::new(param::HIGH_GAIN, -24.0, 24.0),
+        ];
+        const COMPRESSOR: &[ParamRange] = &[
+            ParamRange::new(param::THRESHOLD, -60.0, 0.0),
+            ParamRange::new(param::RATIO, 1.0, 20.0),
+            ParamRange::new(param::ATTACK, 0.1, 200.0),
+            ParamRange::new(param::RELEASE, 10.0, 2000.0),
+            ParamRange::new(param::MAKEUP, 0.0, 24.0),
+        ];
+
+        match self {
+            NodeDef::Sine(_) => SINE,
+            NodeDef::Lfo(_) | NodeDef::Adsr(_) => &[],
+            NodeDef::Distortion(_) => DISTORTION,
+            NodeDef::Bitcrusher(_) => BITCRUSHER,
+            NodeDef::Chorus(_) => CHORUS,
+            NodeDef::Eq(_) => EQ,
+            NodeDef::Compressor(_) => COMPRESSOR,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -36,6 +142,57 @@ pub struct AdsrDef {
     pub target_node: NodeId,
 }
 
+// Drive is a gain from 1.0 to 100.0 into the shaper
+#[derive(Clone, Debug)]
+pub struct DistortionDef {
+    pub drive: f32,
+    pub mix: f32,
+}
+
+// Downsample is the factor to reduce the sample rate by
+#[derive(Clone, Debug)]
+pub struct BitcrusherDef {
+    pub bits: f32,
+    pub downsample: f32,
+    pub mix: f32,
+}
+
+// Rate in Hz; depth and delay in milliseconds
+#[derive(Clone, Debug)]
+pub struct ChorusDef {
+    pub rate: f32,
+    pub depth: f32,
+    pub delay: f32,
+    pub feedback: f32,
+    pub mix: f32,
+}
+
+// Frequencies in Hz; gains in dB
+#[derive(Clone, Debug)]
+pub struct EqDef {
+    pub low_freq: f32,
+    pub low_gain: f32,
+    pub mid_freq: f32,
+    pub mid_gain: f32,
+    pub high_freq: f32,
+    pub high_gain: f32,
+}
+
+/*
+ * Threshold and makeup in dB; attack and release in
+ * milliseconds. With a key track the detector listens
+ * to that track instead of the instrument itself.
+ */
+#[derive(Clone, Debug)]
+pub struct CompressorDef {
+    pub key_track: Option<TrackId>,
+    pub threshold: f32,
+    pub ratio: f32,
+    pub attack: f32,
+    pub release: f32,
+    pub makeup: f32,
+}
+
 #[derive(Clone, Debug)]
 pub struct Connection {
     pub from_node: NodeId,
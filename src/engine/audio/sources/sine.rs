@@ -1,11 +1,12 @@
 use std::f32::consts::PI;
 
-use crate::engine::audio::{ParamId, Source, param};
+use crate::engine::audio::{Frame, ParamId, Source, param};
 
 #[derive(Debug)]
 pub struct Sine {
     amplitude: f32,
     freq: f32,
+    pitch: f32,
     phase: f32,
     sample_rate: f32,
 }
@@ -15,6 +16,7 @@ impl Sine {
         Self {
             amplitude: 1.0,
             freq: 440.0,
+            pitch: 1.0,
             phase: 0.0,
             sample_rate,
         }
@@ -22,14 +24,14 @@ impl Sine {
 }
 
 impl Source for Sine {
-    fn next(&mut self) -> f32 {
-        self.phase += 2.0 * PI * self.freq / self.sample_rate;
+    fn next(&mut self) -> Frame {
+        self.phase += 2.0 * PI * self.freq * self.pitch / self.sample_rate;
 
         if self.phase > 2.0 * PI {
             self.phase -= 2.0 * PI;
         }
 
-        self.amplitude * self.phase.sin()
+        Frame::mono(self.amplitude * self.phase.sin())
     }
 
     fn set(&mut self, note: u8, velocity: u8) {
@@ -40,6 +42,14 @@ impl Source for Sine {
         self.amplitude = vel_norm;
     }
 
+    fn reset(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
+
+    fn set_pitch(&mut self, ratio: f32) {
+        self.pitch = ratio;
+    }
+
     fn get_param(&self, param: ParamId) -> f32 {
         match param {
             param::FREQUENCY => self.freq,
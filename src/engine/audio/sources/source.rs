@@ -1,8 +1,16 @@
+use crate::engine::audio::Frame;
+
 pub trait Source: Send + Sync {
-    fn next(&mut self) -> f32;
+    fn next(&mut self) -> Frame;
     fn get_param(&self, param: ParamId) -> f32;
     fn set_param(&mut self, param: ParamId, value: f32);
     fn set(&mut self, note: u8, velocity: u8);
+    // Back to how it was built, for a voice's next note
+    fn reset(&mut self) {}
+
+    // Bend the pitch by a frequency ratio, on top of
+    // whatever the note and modulators have set
+    fn set_pitch(&mut self, _ratio: f32) {}
 }
 
 pub type NodeId = usize;
@@ -11,4 +19,25 @@ pub type ParamId = u32;
 pub mod param {
     pub const AMPLITUDE: u32 = 1000;
     pub const FREQUENCY: u32 = 1001;
+
+    // Insert effects
+    pub const MIX: u32 = 1100;
+    pub const DRIVE: u32 = 1101;
+    pub const BITS: u32 = 1102;
+    pub const DOWNSAMPLE: u32 = 1103;
+    pub const RATE: u32 = 1104;
+    pub const DEPTH: u32 = 1105;
+    pub const DELAY: u32 = 1106;
+    pub const FEEDBACK: u32 = 1107;
+    pub const LOW_FREQ: u32 = 1108;
+    pub const LOW_GAIN: u32 = 1109;
+    pub const MID_FREQ: u32 = 1110;
+    pub const MID_GAIN: u32 = 1111;
+    pub const HIGH_FREQ: u32 = 1112;
+    pub const HIGH_GAIN: u32 = 1113;
+    pub const THRESHOLD: u32 = 1114;
+    pub const RATIO: u32 = 1115;
+    pub const ATTACK: u32 = 1116;
+    pub const RELEASE: u32 = 1117;
+    pub const MAKEUP: u32 = 1118;
 }
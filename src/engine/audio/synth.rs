@@ -1,7 +1,12 @@
 use crate::engine::audio::*;
+use crate::types::NUM_TRACKS;
 
 pub struct Synth {
     patch: Patch,
+    // Pitch bend in semitones, and params set since
+    // the patch was loaded, applied to every new voice
+    pitch: f32,
+    params: Vec<(NodeId, ParamId, f32)>,
     voices: Vec<Voice>,   // Active + free voices
     active_voices: usize, // Number of currently playing voices
     next_voice: usize,    // Round-robin / steal from here
@@ -28,6 +33,8 @@ impl Synth {
 
         Self {
             patch,
+            pitch: 0.0,
+            params: vec![],
             voices,
             active_voices: 0,
             next_voice: 0,
@@ -54,10 +61,14 @@ impl Synth {
 
         // Now safe to use self.voices again
         let voice = &mut self.voices[chosen];
-        voice.instrument = Instrument::from_patch(&self.patch);
+        voice.instrument.reset(&self.patch);
+        for &(node, param, value) in &self.params {
+            voice.instrument.set_param(node, param, value);
+        }
+        voice.instrument.set_pitch(self.pitch);
         voice.instrument.note_on(note, velocity);
-        voice.note = 0;
-        voice.velocity = 0;
+        voice.note = note;
+        voice.velocity = velocity;
         voice.is_active = true;
 
         self.next_voice = (chosen + 1) % len;
@@ -74,8 +85,67 @@ impl Synth {
         }
     }
 
-    pub fn next_sample(&mut self) -> f32 {
-        let mut sum = 0.0;
+    // Silence every voice at once, skipping the release
+    pub fn kill(&mut self) {
+        for voice in &mut self.voices {
+            voice.is_active = false;
+        }
+        self.active_voices = 0;
+    }
+
+    pub fn set_pitch(&mut self, semitones: f32) {
+        self.pitch = semitones;
+        for voice in &mut self.voices {
+            voice.instrument.set_pitch(semitones);
+        }
+    }
+
+    pub fn set_param(&mut self, node: NodeId, param: ParamId, value: f32) {
+        match self
+            .params
+            .iter_mut()
+            .find(|(n, p, _)| *n == node && *p == param)
+        {
+            Some(entry) => entry.2 = value,
+            None => self.params.push((node, param, value)),
+        }
+
+        for voice in &mut self.voices {
+            voice.instrument.set_param(node, param, value);
+        }
+    }
+
+    // Whether any voice is still making a sound
+    pub fn is_sounding(&self) -> bool {
+        self.voices.iter().any(|voice| voice.is_active)
+    }
+
+    // Pan from -1.0 (hard left) to 1.0 (hard right)
+    pub fn set_pan(&mut self, pan: f32) {
+        self.patch.pan = pan;
+        for voice in &mut self.voices {
+            voice.instrument.set_pan(pan);
+        }
+    }
+
+    // Hand keyed effects in the playing voices their key track outputs
+    pub fn set_keys(&mut self, keys: &[Frame; NUM_TRACKS]) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.is_active) {
+            voice.instrument.set_keys(keys);
+        }
+    }
+
+    // The most gain reduction any playing voice is applying, in dB
+    pub fn gain_reduction(&self) -> f32 {
+        self.voices
+            .iter()
+            .filter(|voice| voice.is_active)
+            .map(|voice| voice.instrument.gain_reduction())
+            .fold(0.0, f32::max)
+    }
+
+    pub fn next_sample(&mut self) -> Frame {
+        let mut sum = Frame::SILENCE;
         let mut active = 0;
 
         for voice in &mut self.voices {
@@ -92,6 +162,10 @@ impl Synth {
 
         self.active_voices = self.voices.iter().filter(|v| v.is_active).count();
 
-        if active > 0 { sum / active as f32 } else { 0.0 }
+        if active > 0 {
+            sum * (1.0 / active as f32)
+        } else {
+            Frame::SILENCE
+        }
     }
 }
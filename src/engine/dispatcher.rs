@@ -35,10 +35,53 @@ impl Dispatcher {
         thread::spawn(move || {
             while let Ok(action) = rx.recv() {
                 match &action {
-                    Action::PlayPhrase(_) => {
+                    Action::PlayPhrase(_) | Action::PlayChain(_) | Action::PlaySong(_) => {
                         sequencer_tx.send(action.clone()).unwrap();
                         audio_tx.send(action.clone()).unwrap();
                     }
+                    Action::GetPlayhead { .. } => {
+                        sequencer_tx.send(action.clone()).unwrap();
+                    }
+                    Action::SetPatternValue { .. }
+                    | Action::SetPhraseStep { .. }
+                    | Action::SetPhraseLength { .. }
+                    | Action::CutBlock { .. }
+                    | Action::PasteBlock { .. }
+                    | Action::ClearBlock { .. }
+                    | Action::Undo
+                    | Action::Cleanup { .. }
+                    | Action::SetChainPhrase { .. }
+                    | Action::SetChainTranspose { .. }
+                    | Action::SetSongDimensions { .. }
+                    | Action::SetGrooveStep { .. }
+                    | Action::SetTicksPerStep { .. }
+                    | Action::SetTrackGroove { .. }
+                    | Action::SetPhraseGroove { .. }
+                    | Action::SetTableRow { .. } => {
+                        update_tx.send(action.clone()).unwrap();
+                        sequencer_tx.send(action.clone()).unwrap();
+                    }
+                    Action::SetDelaySettings { .. }
+                    | Action::SetReverbSettings { .. }
+                    | Action::SetMixerSend { .. }
+                    | Action::SetMixerGain { .. }
+                    | Action::SetMixerPan { .. }
+                    | Action::SetMixerMute { .. }
+                    | Action::SetMixerSolo { .. } => {
+                        update_tx.send(action.clone()).unwrap();
+                        audio_tx.send(action.clone()).unwrap();
+                    }
+                    Action::SetInstrumentPan { .. }
+                    | Action::SetInstrumentTable { .. }
+                    | Action::SetInstrumentNode { .. }
+                    | Action::SetInstrumentPatch { .. }
+                    | Action::GetInstrument { .. }
+                    | Action::SetLimiterCeiling { .. }
+                    | Action::NoteOn { .. }
+                    | Action::NoteOff { .. }
+                    | Action::GetMixerMeters { .. } => {
+                        audio_tx.send(action.clone()).unwrap();
+                    }
                     _ => {
                         update_tx.send(action.clone()).unwrap();
                     }
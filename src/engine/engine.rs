@@ -4,6 +4,7 @@ use crate::engine::Sequencer;
 use crate::engine::audio::*;
 use crate::messaging::Action;
 use crate::model::Song;
+use crate::types::InstrumentId;
 use crossbeam::channel::{Receiver, Sender, unbounded};
 use parking_lot::Mutex;
 use std::sync::Arc;
@@ -58,20 +59,123 @@ impl Engine {
             {
                 let mut manager_lock = instrument_manager.lock();
                 manager_lock.set_sample_rate(audio_engine.get_sample_rate() as f32);
+                sequencer
+                    .lock()
+                    .set_sample_rate(audio_engine.get_sample_rate());
                 manager_lock.add_synth();
             }
 
+            // Whether the sequencer is playing. The audio can be
+            // running without it, for notes played by hand.
+            let mut sequencing = false;
+
             while let Ok(action) = audio_rx.recv() {
                 match action {
-                    Action::PlayPhrase(phrase_id) => {
-                        if audio_engine.is_playing() {
+                    Action::PlayPhrase(_) | Action::PlayChain(_) | Action::PlaySong(_) => {
+                        sequencing = !sequencing;
+                        if !sequencing {
                             println!("stop");
                             audio_engine.stop();
-                        } else {
+                        } else if !audio_engine.is_playing() {
                             println!("start");
                             audio_engine.start();
                         }
                     }
+                    Action::NoteOn {
+                        track_id,
+                        instrument_id,
+                        note,
+                        velocity,
+                    } => {
+                        if !audio_engine.is_playing() {
+                            audio_engine.start();
+                        }
+                        instrument_manager
+                            .lock()
+                            .note_on(track_id, instrument_id, note, velocity);
+                    }
+                    Action::NoteOff { track_id } => {
+                        instrument_manager.lock().note_off(track_id);
+                    }
+                    Action::SetInstrumentPan { instrument_id, pan } => {
+                        instrument_manager
+                            .lock()
+                            .set_instrument_pan(instrument_id, pan);
+                    }
+                    Action::SetInstrumentTable {
+                        instrument_id,
+                        table_id,
+                    } => {
+                        instrument_manager
+                            .lock()
+                            .set_instrument_table(instrument_id, table_id);
+                    }
+                    Action::SetInstrumentNode {
+                        instrument_id,
+                        node,
+                        def,
+                    } => {
+                        let patch = instrument_manager.lock().get_instrument(instrument_id);
+                        if let Some(mut patch) = patch
+                            && let Some(slot) = patch.nodes.get_mut(node)
+                        {
+                            *slot = def;
+                            swap_patch(&instrument_manager, instrument_id, patch);
+                        }
+                    }
+                    Action::SetInstrumentPatch {
+                        instrument_id,
+                        patch,
+                    } => {
+                        swap_patch(&instrument_manager, instrument_id, patch);
+                    }
+                    Action::GetInstrument {
+                        instrument_id,
+                        reply_to,
+                    } => {
+                        let _ =
+                            reply_to.send(instrument_manager.lock().get_instrument(instrument_id));
+                    }
+                    Action::SetMixerGain { channel, gain_db } => {
+                        instrument_manager
+                            .lock()
+                            .mixer()
+                            .set_gain_db(channel, gain_db);
+                    }
+                    Action::SetMixerPan { channel, pan } => {
+                        instrument_manager.lock().mixer().set_pan(channel, pan);
+                    }
+                    Action::SetMixerMute { channel, mute } => {
+                        instrument_manager.lock().mixer().set_mute(channel, mute);
+                    }
+                    Action::SetMixerSolo { track_id, solo } => {
+                        instrument_manager.lock().mixer().set_solo(track_id, solo);
+                    }
+                    Action::SetDelaySettings { settings } => {
+                        instrument_manager.lock().mixer().set_delay(settings);
+                    }
+                    Action::SetReverbSettings { settings } => {
+                        instrument_manager.lock().mixer().set_reverb(settings);
+                    }
+                    Action::SetMixerSend {
+                        track_id,
+                        bus,
+                        amount,
+                    } => {
+                        instrument_manager
+                            .lock()
+                            .mixer()
+                            .set_send(track_id, bus, amount);
+                    }
+                    Action::SetLimiterCeiling { ceiling_db } => {
+                        instrument_manager
+                            .lock()
+                            .master_bus()
+                            .set_ceiling_db(ceiling_db);
+                    }
+                    Action::GetMixerMeters { reply_to } => {
+                        let _ = reply_to.send(instrument_manager.lock().meters());
+                    }
 
                     _ => {}
                 }
@@ -79,3 +183,19 @@ impl Engine {
         });
     }
 }
+
+// Build the synths for an instrument's new patch here
+// rather than in the audio callback, swap them in under
+// one lock, and drop the old ones after letting it go
+fn swap_patch(
+    instrument_manager: &Mutex<InstrumentManager>,
+    instrument_id: InstrumentId,
+    mut patch: Patch,
+) {
+    patch.sample_rate = instrument_manager.lock().get_sample_rate();
+    let synths = InstrumentManager::build_synths(&patch);
+    let replaced = instrument_manager
+        .lock()
+        .set_instrument_patch(instrument_id, patch, synths);
+    drop(replaced);
+}
@@ -5,6 +5,6 @@ pub mod sequencer;
 
 pub use dispatcher::Dispatcher;
 pub use engine::Engine;
-pub use sequencer::Sequencer;
+pub use sequencer::{NoteTarget, Playhead, Sequencer, TrackPosition};
 
 pub use audio::*;
@@ -1,26 +1,1198 @@
-use crate::engine::audio::*;
 use crate::messaging::Action;
-use crate::types::PatternId;
-use crossbeam::channel::{Receiver, Sender, unbounded, bounded};
+use crate::types::{
+    ChainId, DEFAULT_TICKS_PER_STEP, FxKind, InstrumentId, MAX_NOTE, MAX_VELOCITY, NUM_TABLE_ROWS,
+    NUM_TRACKS, Note, PatternId, PhraseId, Step, StepKind, Table, TableId, TableRow, Timing,
+    TrackId,
+};
+use crossbeam::channel::{Receiver, Sender, bounded, unbounded};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Fx;
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        On(TrackId, InstrumentId, Note, u8),
+        Off(TrackId),
+        Kill(TrackId),
+        Pitch(f32),
+        Volume(f32),
+        Param(usize, f32),
+    }
+
+    // Notes played, with the sample they were played on
+    #[derive(Default)]
+    struct Recorder {
+        sample: usize,
+        events: Vec<(usize, Event)>,
+        instrument_tables: HashMap<InstrumentId, TableId>,
+    }
+
+    impl NoteTarget for Recorder {
+        fn note_on(
+            &mut self,
+            track_id: TrackId,
+            instrument_id: InstrumentId,
+            note: Note,
+            velocity: u8,
+        ) {
+            self.events.push((
+                self.sample,
+                Event::On(track_id, instrument_id, note, velocity),
+            ));
+        }
+
+        fn note_off(&mut self, track_id: TrackId) {
+            self.events.push((self.sample, Event::Off(track_id)));
+        }
+
+        fn kill(&mut self, track_id: TrackId) {
+            self.events.push((self.sample, Event::Kill(track_id)));
+        }
+
+        fn set_pitch(&mut self, _track_id: TrackId, semitones: f32) {
+            self.events.push((self.sample, Event::Pitch(semitones)));
+        }
+
+        fn set_volume(&mut self, _track_id: TrackId, volume: f32) {
+            self.events.push((self.sample, Event::Volume(volume)));
+        }
+
+        fn set_param(&mut self, _track_id: TrackId, index: usize, amount: f32) {
+            self.events.push((self.sample, Event::Param(index, amount)));
+        }
+
+        fn instrument_table(&self, instrument_id: InstrumentId) -> Option<TableId> {
+            self.instrument_tables.get(&instrument_id).copied()
+        }
+    }
+
+    // 120 bpm at 48kHz is 6000 samples per step
+    // and 1000 per tick
+    const SAMPLES_PER_TICK: usize = 1000;
+
+    fn sequencer() -> Sequencer {
+        let (tx, rx) = unbounded();
+        Sequencer::new(tx, rx, 48000, 120.0)
+    }
+
+    fn run(sequencer: &mut Sequencer, recorder: &mut Recorder, samples: usize) {
+        for _ in 0..samples {
+            sequencer.tick(recorder);
+            recorder.sample += 1;
+        }
+    }
+
+    fn play(sequencer: &Sequencer, steps: Vec<Option<Step>>) {
+        play_timed(sequencer, steps, Timing::default());
+    }
+
+    fn play_timed(sequencer: &Sequencer, steps: Vec<Option<Step>>, timing: Timing) {
+        let ticks_per_step = timing.ticks_per_step;
+        sequencer
+            .phrase_tx
+            .send(Command::Play(arrangement(
+                ticks_per_step,
+                vec![vec![vec![queued(0, steps, 0, timing)]]],
+            )))
+            .unwrap();
+    }
+
+    fn arrangement(ticks_per_step: u8, rows: Vec<Vec<Vec<QueuedPhrase>>>) -> Arrangement {
+        Arrangement {
+            ticks_per_step,
+            rows,
+            first_pattern: None,
+        }
+    }
+
+    fn queued(
+        phrase_id: PhraseId,
+        steps: Vec<Option<Step>>,
+        transpose: i8,
+        timing: Timing,
+    ) -> QueuedPhrase {
+        QueuedPhrase {
+            phrase_id,
+            chain: None,
+            steps,
+            transpose,
+            timing,
+        }
+    }
+
+    /*
+     * Steps play their instrument and velocity. An empty
+     * instrument column keeps the last instrument and an
+     * empty velocity column plays at full velocity.
+     */
+
+    #[test]
+    fn steps_play_instrument_and_velocity() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        play(
+            &sequencer,
+            vec![
+                Some(Step {
+                    instrument: Some(2),
+                    velocity: Some(0x40),
+                    ..Step::new(60, 0)
+                }),
+                None,
+                Some(Step::new(62, 0)),
+            ],
+        );
+        run(&mut sequencer, &mut recorder, 13000);
+
+        assert_eq!(
+            recorder.events,
+            vec![
+                (0, Event::On(0, 2, 60, 0x40)),
+                (12000, Event::On(0, 2, 62, MAX_VELOCITY)),
+            ]
+        );
+    }
+
+    /*
+     * A note with a length is released that many steps
+     * later; without one it rings until stopped.
+     */
+
+    #[test]
+    fn length_and_stop_release_notes() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        play(&sequencer, vec![Some(Step::new(60, 1)), None, None, None]);
+        run(&mut sequencer, &mut recorder, 7000);
+        assert_eq!(recorder.events[1], (6000, Event::Off(0)));
+
+        recorder.events.clear();
+        play(&sequencer, vec![Some(Step::new(60, 0)), None]);
+        run(&mut sequencer, &mut recorder, 100);
+        sequencer.phrase_tx.send(Command::Stop).unwrap();
+        run(&mut sequencer, &mut recorder, 20000);
+
+        assert_eq!(recorder.events.len(), 2);
+        assert_eq!(recorder.events[1].1, Event::Off(0));
+    }
+
+    /*
+     * Edits to the playing phrase are heard next
+     * time round.
+     */
+
+    #[test]
+    fn edits_reach_playing_phrase() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        play(&sequencer, vec![Some(Step::new(60, 0)), None]);
+        run(&mut sequencer, &mut recorder, 100);
+        sequencer
+            .phrase_tx
+            .send(Command::SetStep {
+                phrase_id: 0,
+                index: 1,
+                step: Some(Step::new(64, 0)),
+            })
+            .unwrap();
+        run(&mut sequencer, &mut recorder, 6000);
+
+        assert_eq!(
+            recorder.events[1],
+            (6000, Event::On(0, 0, 64, MAX_VELOCITY))
+        );
+    }
+
+    /*
+     * Steps last as many ticks as the groove says,
+     * cycling round it.
+     */
+
+    #[test]
+    fn groove_sets_step_lengths() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        let timing = Timing {
+            ticks_per_step: 6,
+            groove: vec![8, 4],
+        };
+        play_timed(&sequencer, vec![Some(Step::new(60, 0)); 4], timing);
+        run(&mut sequencer, &mut recorder, 24 * SAMPLES_PER_TICK);
+
+        assert_eq!(
+            notes(&recorder.events),
+            vec![(0, 60), (8000, 60), (12000, 60), (20000, 60)]
+        );
+    }
+
+    /*
+     * Ticks per step sets how finely a step is divided:
+     * straight steps stay the same length, but ticks
+     * get longer with fewer of them.
+     */
+
+    #[test]
+    fn ticks_per_step_sets_tick_length() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        let timing = Timing {
+            ticks_per_step: 4,
+            groove: vec![],
+        };
+        play_timed(
+            &sequencer,
+            vec![with_fx(60, FxKind::Retrigger, 1), Some(Step::new(62, 0))],
+            timing,
+        );
+        run(&mut sequencer, &mut recorder, 7000);
+
+        assert_eq!(
+            notes(&recorder.events),
+            vec![(0, 60), (1500, 60), (3000, 60), (4500, 60), (6000, 62)]
+        );
+    }
+
+    /*
+     * A groove changed while playing takes over from
+     * the next step.
+     */
+
+    #[test]
+    fn timing_changes_reach_playing_phrase() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        play(&sequencer, vec![Some(Step::new(60, 0)); 4]);
+        run(&mut sequencer, &mut recorder, 100);
+        sequencer
+            .phrase_tx
+            .send(Command::Update(arrangement(
+                6,
+                vec![vec![vec![queued(
+                    0,
+                    vec![Some(Step::new(60, 0)); 4],
+                    0,
+                    Timing {
+                        ticks_per_step: 6,
+                        groove: vec![3],
+                    },
+                )]]],
+            )))
+            .unwrap();
+        run(&mut sequencer, &mut recorder, 12000);
+
+        assert_eq!(
+            notes(&recorder.events),
+            vec![(0, 60), (6000, 60), (9000, 60), (12000, 60)]
+        );
+    }
+
+    /*
+     * A chain plays its phrases in turn, each moved by
+     * its row's transpose, then starts again.
+     */
+
+    #[test]
+    fn chain_rows_transpose_phrases() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        let steps = vec![Some(Step::new(48, 0)), Some(Step::new(55, 0))];
+        sequencer
+            .phrase_tx
+            .send(Command::Play(arrangement(
+                DEFAULT_TICKS_PER_STEP,
+                vec![vec![vec![
+                    queued(0, steps.clone(), 0, Timing::default()),
+                    queued(0, steps.clone(), 5, Timing::default()),
+                    queued(0, steps, -12, Timing::default()),
+                ]]],
+            )))
+            .unwrap();
+        run(&mut sequencer, &mut recorder, 7 * 6000);
+
+        assert_eq!(
+            notes(&recorder.events)
+                .into_iter()
+                .map(|(_, note)| note)
+                .collect::<Vec<_>>(),
+            vec![48, 55, 53, 60, 36, 43, 48]
+        );
+    }
+
+    /*
+     * Tracks come back into step at each pattern row:
+     * the 3-step phrase plays again while the 4-step
+     * one finishes, then both move on. Track 1 has no
+     * chain on the second row so its note is released,
+     * and after the last row it's back to the first.
+     */
+
+    #[test]
+    fn tracks_sync_at_pattern_rows() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        let phrase = |note, len| {
+            let mut steps = vec![None; len];
+            steps[0] = Some(Step::new(note, 0));
+            vec![queued(0, steps, 0, Timing::default())]
+        };
+        sequencer
+            .phrase_tx
+            .send(Command::Play(arrangement(
+                DEFAULT_TICKS_PER_STEP,
+                vec![vec![phrase(60, 3), phrase(72, 4)], vec![phrase(48, 1)]],
+            )))
+            .unwrap();
+        run(&mut sequencer, &mut recorder, 5 * 6000 + 1);
+
+        let steps: Vec<(usize, Event)> = recorder
+            .events
+            .into_iter()
+            .map(|(sample, event)| (sample / 6000, event))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (0, Event::On(0, 0, 60, MAX_VELOCITY)),
+                (0, Event::On(1, 0, 72, MAX_VELOCITY)),
+                (3, Event::On(0, 0, 60, MAX_VELOCITY)),
+                (4, Event::Off(1)),
+                (4, Event::On(0, 0, 48, MAX_VELOCITY)),
+                (5, Event::On(0, 0, 60, MAX_VELOCITY)),
+                (5, Event::On(1, 0, 72, MAX_VELOCITY)),
+            ]
+        );
+    }
+
+    /*
+     * The playhead has the pattern row, and the chain
+     * row, phrase and step each track has got to. It's
+     * cleared when playback stops.
+     */
+
+    #[test]
+    fn playhead_follows_playback() {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        let chain_row = |index| QueuedPhrase {
+            chain: Some((3, index)),
+            ..queued(7, vec![None; 2], 0, Timing::default())
+        };
+        sequencer
+            .phrase_tx
+            .send(Command::Play(Arrangement {
+                first_pattern: Some(2),
+                ..arrangement(
+                    DEFAULT_TICKS_PER_STEP,
+                    vec![vec![vec![chain_row(0), chain_row(2)]]],
+                )
+            }))
+            .unwrap();
+        run(&mut sequencer, &mut recorder, 3 * 6000 + 1);
+
+        let playhead = sequencer.playhead.clone();
+        assert_eq!(playhead.pattern_row(), Some(2));
+        assert_eq!(
+            playhead.track(0),
+            Some(TrackPosition {
+                chain: Some((3, 2)),
+                phrase: 7,
+                step: 1,
+            })
+        );
+        assert_eq!(playhead.track(1), None);
+
+        sequencer.phrase_tx.send(Command::Stop).unwrap();
+        run(&mut sequencer, &mut recorder, 1);
+        assert_eq!(playhead.pattern_row(), None);
+        assert_eq!(playhead.track(0), None);
+    }
+
+    fn with_fx(note: Note, kind: FxKind, value: u8) -> Option<Step> {
+        let mut step = Step::new(note, 0);
+        step.fx[0] = Some(Fx::new(kind, value));
+        Some(step)
+    }
+
+    // Play some steps for a number of ticks, returning
+    // what happened on each tick
+    fn play_ticks(steps: Vec<Option<Step>>, ticks: usize) -> (Sequencer, Vec<(usize, Event)>) {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder::default();
+
+        play(&sequencer, steps);
+        run(&mut sequencer, &mut recorder, ticks * SAMPLES_PER_TICK);
+
+        let events = recorder
+            .events
+            .into_iter()
+            .map(|(sample, event)| (sample / SAMPLES_PER_TICK, event))
+            .collect();
+        (sequencer, events)
+    }
+
+    fn pitches(events: &[(usize, Event)]) -> Vec<(usize, f32)> {
+        events
+            .iter()
+            .filter_map(|(tick, event)| match event {
+                Event::Pitch(semitones) => Some((*tick, *semitones)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn notes(events: &[(usize, Event)]) -> Vec<(usize, Note)> {
+        events
+            .iter()
+            .filter_map(|(tick, event)| match event {
+                Event::On(_, _, note, _) => Some((*tick, *note)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /*
+     * Arpeggio steps through the note, +x and +y on
+     * successive ticks, and stops with the step.
+     */
+
+    #[test]
+    fn fx_arpeggio() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Arpeggio, 0x37), None], 7);
+
+        assert_eq!(
+            pitches(&events),
+            vec![(1, 3.0), (2, 7.0), (3, 0.0), (4, 3.0), (5, 7.0), (6, 0.0)]
+        );
+    }
+
+    /*
+     * Slides move the pitch every tick but the first,
+     * and the pitch stays where it got to.
+     */
+
+    #[test]
+    fn fx_slide_up() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::SlideUp, 0x10), None], 12);
+
+        assert_eq!(
+            pitches(&events),
+            vec![(1, 1.0), (2, 2.0), (3, 3.0), (4, 4.0), (5, 5.0)]
+        );
+    }
+
+    #[test]
+    fn fx_slide_down() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::SlideDown, 0x08), None], 12);
+
+        assert_eq!(
+            pitches(&events),
+            vec![(1, -0.5), (2, -1.0), (3, -1.5), (4, -2.0), (5, -2.5)]
+        );
+    }
+
+    /*
+     * Vibrato swings either side of the note by the
+     * depth, then settles back when the step ends.
+     */
+
+    #[test]
+    fn fx_vibrato() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Vibrato, 0x84), None], 7);
+        let pitches = pitches(&events);
+
+        assert!((pitches[0].1 - 0.5).abs() < 1e-5);
+        assert!(pitches.iter().any(|(_, p)| (p + 0.5).abs() < 1e-5));
+        assert_eq!(pitches.last(), Some(&(6, 0.0)));
+    }
+
+    /*
+     * Volume slides change the volume every tick but
+     * the first, and a new note puts it back to full.
+     */
+
+    #[test]
+    fn fx_volume_slide() {
+        let (_, events) = play_ticks(
+            vec![
+                with_fx(60, FxKind::VolumeSlide, 0x08),
+                Some(Step::new(60, 0)),
+            ],
+            7,
+        );
+
+        let volumes: Vec<(usize, f32)> = events
+            .iter()
+            .filter_map(|(tick, event)| match event {
+                Event::Volume(volume) => Some((*tick, *volume)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            volumes,
+            vec![
+                (1, 0.875),
+                (2, 0.75),
+                (3, 0.625),
+                (4, 0.5),
+                (5, 0.375),
+                (6, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn fx_retrigger() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Retrigger, 2), None], 6);
+
+        assert_eq!(notes(&events), vec![(0, 60), (2, 60), (4, 60)]);
+    }
+
+    #[test]
+    fn fx_note_delay() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Delay, 3), None], 6);
+
+        assert_eq!(notes(&events), vec![(3, 60)]);
+    }
+
+    #[test]
+    fn fx_note_cut() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Cut, 2), None], 6);
+
+        assert_eq!(
+            events,
+            vec![(0, Event::On(0, 0, 60, MAX_VELOCITY)), (2, Event::Off(0))]
+        );
+    }
+
+    #[test]
+    fn fx_kill() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Kill, 1), None], 6);
+
+        assert_eq!(
+            events,
+            vec![(0, Event::On(0, 0, 60, MAX_VELOCITY)), (1, Event::Kill(0))]
+        );
+    }
+
+    /*
+     * An off step releases the note and a kill step
+     * cuts it dead, each on the step's first tick.
+     */
+
+    #[test]
+    fn note_off_and_kill_steps() {
+        let (_, events) = play_ticks(
+            vec![
+                Some(Step::new(60, 0)),
+                Some(Step::of_kind(StepKind::Off)),
+                Some(Step::new(62, 0)),
+                Some(Step::of_kind(StepKind::Kill)),
+            ],
+            24,
+        );
+
+        assert_eq!(
+            events,
+            vec![
+                (0, Event::On(0, 0, 60, MAX_VELOCITY)),
+                (6, Event::Off(0)),
+                (12, Event::On(0, 0, 62, MAX_VELOCITY)),
+                (18, Event::Kill(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn fx_tempo() {
+        let (sequencer, _) = play_ticks(vec![with_fx(60, FxKind::Tempo, 0x8C), None], 1);
+
+        assert_eq!(sequencer.get_bpm(), 140.0);
+    }
+
+    /*
+     * Hop plays the given step next instead of the
+     * one after.
+     */
+
+    #[test]
+    fn fx_hop() {
+        let (_, events) = play_ticks(
+            vec![
+                Some(Step::new(60, 0)),
+                with_fx(62, FxKind::Hop, 0),
+                Some(Step::new(64, 0)),
+            ],
+            24,
+        );
+
+        assert_eq!(notes(&events), vec![(0, 60), (6, 62), (12, 60), (18, 62)]);
+    }
+
+    #[test]
+    fn fx_param() {
+        let (_, events) = play_ticks(vec![with_fx(60, FxKind::Param, 0x2A), None], 1);
+
+        assert_eq!(events[0], (0, Event::Param(2, 10.0 / 15.0)));
+    }
+
+    fn table_row(transpose: u8, volume: Option<u8>) -> TableRow {
+        TableRow {
+            transpose,
+            volume,
+            ..Default::default()
+        }
+    }
+
+    // Play some steps with table 1 loaded, returning
+    // what happened on each tick
+    fn play_table(
+        steps: Vec<Option<Step>>,
+        rows: Vec<TableRow>,
+        instrument_tables: HashMap<InstrumentId, TableId>,
+        ticks: usize,
+    ) -> Vec<(usize, Event)> {
+        let mut sequencer = sequencer();
+        let mut recorder = Recorder {
+            instrument_tables,
+            ..Default::default()
+        };
+
+        let mut table = Table::default();
+        table.rows[..rows.len()].copy_from_slice(&rows);
+        sequencer
+            .phrase_tx
+            .send(Command::SetTables(HashMap::from([(1, table)])))
+            .unwrap();
+        play(&sequencer, steps);
+        run(&mut sequencer, &mut recorder, ticks * SAMPLES_PER_TICK);
+
+        recorder
+            .events
+            .into_iter()
+            .map(|(sample, event)| (sample / SAMPLES_PER_TICK, event))
+            .collect()
+    }
+
+    /*
+     * An instrument's table starts with each note and
+     * plays a row a tick, transposing and setting the
+     * volume. Past the last row, the transpose is back
+     * to 0 as the empty rows set it.
+     */
+
+    #[test]
+    fn instrument_starts_table() {
+        let events = play_table(
+            vec![Some(Step::new(60, 0)), None],
+            vec![
+                table_row(0, None),
+                table_row(0x0C, None),
+                table_row(0xF4, Some(0x40)),
+            ],
+            HashMap::from([(0, 1)]),
+            4,
+        );
+
+        assert_eq!(pitches(&events), vec![(1, 12.0), (2, -12.0), (3, 0.0)]);
+        assert!(events.contains(&(2, Event::Volume(0x40 as f32 / MAX_VELOCITY as f32))));
+    }
+
+    /*
+     * H xy hops to row y x times, then carries on.
+     */
+
+    #[test]
+    fn table_hop_counts() {
+        let mut hop = table_row(3, None);
+        hop.fx[0] = Some(Fx::new(FxKind::Hop, 0x20));
+
+        let events = play_table(
+            vec![with_fx(60, FxKind::Table, 1), None],
+            vec![table_row(0, None), hop, table_row(7, None)],
+            HashMap::new(),
+            8,
+        );
+
+        assert_eq!(
+            pitches(&events),
+            vec![
+                (1, 3.0),
+                (2, 0.0),
+                (3, 3.0),
+                (4, 0.0),
+                (5, 3.0),
+                (6, 7.0),
+                (7, 0.0)
+            ]
+        );
+    }
+
+    /*
+     * With x of 0, H loops for good, and the table
+     * keeps running across empty steps.
+     */
+
+    #[test]
+    fn table_loops() {
+        let mut hop = table_row(5, None);
+        hop.fx[0] = Some(Fx::new(FxKind::Hop, 0x00));
+
+        let events = play_table(
+            vec![with_fx(60, FxKind::Table, 1), None],
+            vec![table_row(0, None), hop],
+            HashMap::new(),
+            10,
+        );
+
+        assert_eq!(
+            pitches(&events),
+            (1..10)
+                .map(|tick| (tick, if tick % 2 == 1 { 5.0 } else { 0.0 }))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /*
+     * A table's FX act on the tick their row plays.
+     */
+
+    #[test]
+    fn table_fx() {
+        let mut retrigger = table_row(0, None);
+        retrigger.fx[0] = Some(Fx::new(FxKind::Retrigger, 0));
+        let mut kill = table_row(0, None);
+        kill.fx[1] = Some(Fx::new(FxKind::Kill, 0));
+
+        let events = play_table(
+            vec![with_fx(60, FxKind::Table, 1), None],
+            vec![table_row(0, None), retrigger, kill, table_row(0x0C, None)],
+            HashMap::new(),
+            6,
+        );
+
+        assert_eq!(
+            events,
+            vec![
+                (0, Event::On(0, 0, 60, MAX_VELOCITY)),
+                (1, Event::On(0, 0, 60, MAX_VELOCITY)),
+                (2, Event::Kill(0)),
+            ]
+        );
+    }
+}
+
+/*
+ * Whatever the sequencer plays notes on: the
+ * InstrumentManager in the audio engine, or
+ * something that records them in tests.
+ */
+
+pub trait NoteTarget {
+    fn note_on(&mut self, track_id: TrackId, instrument_id: InstrumentId, note: Note, velocity: u8);
+    fn note_off(&mut self, track_id: TrackId);
+
+    // Silence a note without its release
+    fn kill(&mut self, track_id: TrackId);
+
+    // Bend the playing note by a number of semitones
+    fn set_pitch(&mut self, track_id: TrackId, semitones: f32);
+
+    // Track volume from 0.0 to 1.0, on top of velocity
+    fn set_volume(&mut self, track_id: TrackId, volume: f32);
+
+    // Set the index'th param of the track's instrument to
+    // an amount from 0.0 to 1.0 across its range
+    fn set_param(&mut self, track_id: TrackId, index: usize, amount: f32);
+
+    // The table an instrument starts with each note
+    fn instrument_table(&self, instrument_id: InstrumentId) -> Option<TableId>;
+}
+
+/*
+ * Sent from the sequencer's thread to tick(), which
+ * runs on the audio thread and mustn't block.
+ */
+
+enum Command {
+    // Start from the top of the first row
+    Play(Arrangement),
+    // Swap in a new arrangement without starting again,
+    // after the song, chains or grooves have been edited
+    Update(Arrangement),
+    Stop,
+    SetTables(HashMap<TableId, Table>),
+    SetTableRow {
+        table_id: TableId,
+        index: usize,
+        row: TableRow,
+    },
+    SetStep {
+        phrase_id: PhraseId,
+        index: usize,
+        step: Option<Step>,
+    },
+}
+
+/*
+ * Everything lined up to play: a list of pattern
+ * rows, each with the phrases of every track's chain.
+ * A phrase or chain played on its own is a single
+ * row on the first track.
+ *
+ * Tracks can have chains of different lengths, and
+ * phrases of different lengths and grooves, so they
+ * are brought back into step at every pattern row:
+ *
+ * - The row lasts as long as its longest chain, in
+ *   ticks, going by phrase lengths and grooves when
+ *   the row starts. The first track with a chain
+ *   that long leads the row.
+ * - Other tracks play their chains from the top
+ *   again whenever they run out.
+ * - When the leading track gets to the end of its
+ *   chain, every track moves on to the next row
+ *   together, cutting off whatever it was part way
+ *   through. After the last row it's back to the
+ *   first.
+ *
+ * So a track of 12-step phrases against one of
+ * 16-step phrases plays 4 steps of its chain again
+ * before the next row starts them both together.
+ * Hops don't count towards a row's length, but a
+ * hop on the leading track can keep the row going.
+ */
+
+struct Arrangement {
+    ticks_per_step: u8,
+    rows: Vec<Vec<Vec<QueuedPhrase>>>,
+    // The pattern row the first row came from, for a song
+    first_pattern: Option<usize>,
+}
+
+impl Arrangement {
+    // What a track plays on a row; nothing if it has no chain there
+    fn phrases(&self, row: usize, track_id: usize) -> &[QueuedPhrase] {
+        self.rows
+            .get(row)
+            .and_then(|tracks| tracks.get(track_id))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    // The track that leads a row, as above
+    fn lead(&self, row: usize) -> usize {
+        let ticks = |phrases: &Vec<QueuedPhrase>| -> u32 {
+            phrases
+                .iter()
+                .map(|phrase| {
+                    (0..phrase.steps.len())
+                        .map(|position| phrase.timing.step_ticks(position))
+                        .sum::<u32>()
+                })
+                .sum()
+        };
+
+        self.rows.get(row).map_or(0, |tracks| {
+            tracks
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|(_, phrases)| ticks(phrases))
+                .map_or(0, |(track_id, _)| track_id)
+        })
+    }
+}
+
+/*
+ * A phrase lined up to play: on its own, or as
+ * one row of a chain, with that row's transpose.
+ * It only has the steps within its length.
+ */
+
+struct QueuedPhrase {
+    phrase_id: PhraseId,
+    // The chain and chain row it's from, if any
+    chain: Option<(ChainId, usize)>,
+    steps: Vec<Option<Step>>,
+    transpose: i8,
+    timing: Timing,
+}
+
+/*
+ * What the sequencer's thread has been asked to play.
+ */
+
+#[derive(Clone, Copy)]
+enum Source {
+    Phrase(PhraseId),
+    Chain(ChainId),
+    Song(PatternId),
+}
+
+/*
+ * Where a running table has got to, and how many
+ * times each row has hopped.
+ */
+
+struct TableState {
+    table_id: TableId,
+    row: usize,
+    hops: [u8; NUM_TABLE_ROWS],
+}
+
+impl TableState {
+    fn new(table_id: TableId) -> Self {
+        Self {
+            table_id,
+            row: 0,
+            hops: [0; NUM_TABLE_ROWS],
+        }
+    }
+}
+
+/*
+ * What a track is playing, and the state of the
+ * effect commands and table running on it.
+ */
+
+struct Channel {
+    instrument: InstrumentId,
+    note: Option<Note>,
+    velocity: u8,
+    // Steps left until the playing note is released
+    hold: Option<u8>,
+    // The step whose commands are running
+    step: Option<Step>,
+    // Pitch offset in semitones left by slides
+    slide: f32,
+    volume: f32,
+    vibrato_phase: f32,
+    table: Option<TableState>,
+    // Transpose in semitones and volume set by the table
+    transpose: f32,
+    table_volume: f32,
+    // Last values sent, so they're only sent on change
+    pitch_sent: f32,
+    volume_sent: f32,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            instrument: 0,
+            note: None,
+            velocity: MAX_VELOCITY,
+            hold: None,
+            step: None,
+            slide: 0.0,
+            volume: 1.0,
+            vibrato_phase: 0.0,
+            table: None,
+            transpose: 0.0,
+            table_volume: 1.0,
+            pitch_sent: 0.0,
+            volume_sent: 1.0,
+        }
+    }
+}
+
+/*
+ * Where a track has got to in its chain: the
+ * phrase playing, and the step within it.
+ */
+
+struct Track {
+    channel: Channel,
+    index: usize,
+    position: usize,
+    // Tick within the current step, and its length in ticks
+    tick: u32,
+    step_ticks: u32,
+    // Step to play after this one, if a hop was hit
+    hop: Option<usize>,
+}
+
+impl Track {
+    fn new() -> Self {
+        Self {
+            channel: Channel::new(),
+            index: 0,
+            position: 0,
+            tick: 0,
+            step_ticks: 0,
+            hop: None,
+        }
+    }
+
+    // Back to the top of the chain
+    fn restart(&mut self) {
+        self.index = 0;
+        self.position = 0;
+        self.tick = 0;
+        self.hop = None;
+    }
+
+    // Release the note and stop its commands and table
+    fn silence(&mut self, track_id: usize, target: &mut impl NoteTarget) {
+        target.note_off(track_id as TrackId);
+        self.channel.hold = None;
+        self.channel.step = None;
+        self.channel.table = None;
+    }
+}
+
+/*
+ * Where playback has got to, written by the audio
+ * thread and read by the views to show the rows
+ * playing. Like the mixer meters, each value is a
+ * separate atomic so neither side waits on the other;
+ * a view might see a track half way through moving
+ * on, which the next frame puts right.
+ */
+
+pub struct Playhead {
+    // The pattern row, when a song is playing
+    pattern_row: AtomicUsize,
+    tracks: [TrackHead; NUM_TRACKS],
+}
+
+#[derive(Default)]
+struct TrackHead {
+    chain: AtomicUsize,
+    chain_row: AtomicUsize,
+    phrase: AtomicUsize,
+    step: AtomicUsize,
+}
+
+/*
+ * What a track is playing: the chain and chain row
+ * if it's playing a chain, and the phrase and step.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackPosition {
+    pub chain: Option<(ChainId, usize)>,
+    pub phrase: PhraseId,
+    pub step: usize,
+}
+
+impl Playhead {
+    // Stored for values that aren't there
+    const NONE: usize = usize::MAX;
+
+    fn new() -> Self {
+        let playhead = Self {
+            pattern_row: AtomicUsize::new(Self::NONE),
+            tracks: Default::default(),
+        };
+        playhead.clear();
+        playhead
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.pattern_row().is_some()
+            || (0..NUM_TRACKS).any(|track_id| self.track(track_id).is_some())
+    }
+
+    pub fn pattern_row(&self) -> Option<usize> {
+        Some(self.pattern_row.load(Ordering::Relaxed)).filter(|&row| row != Self::NONE)
+    }
+
+    // Where a track is, or nothing if it isn't playing
+    pub fn track(&self, track_id: usize) -> Option<TrackPosition> {
+        let head = self.tracks.get(track_id)?;
+        let phrase = head.phrase.load(Ordering::Relaxed);
+        if phrase == Self::NONE {
+            return None;
+        }
+
+        let chain = head.chain.load(Ordering::Relaxed);
+        Some(TrackPosition {
+            chain: (chain != Self::NONE)
+                .then(|| (chain as ChainId, head.chain_row.load(Ordering::Relaxed))),
+            phrase: phrase as PhraseId,
+            step: head.step.load(Ordering::Relaxed),
+        })
+    }
+
+    fn set_pattern_row(&self, row: Option<usize>) {
+        self.pattern_row
+            .store(row.unwrap_or(Self::NONE), Ordering::Relaxed);
+    }
+
+    fn set_track(&self, track_id: usize, position: Option<TrackPosition>) {
+        let head = &self.tracks[track_id];
+        let chain = position.and_then(|p| p.chain);
+        head.chain.store(
+            chain.map_or(Self::NONE, |(chain_id, _)| chain_id as usize),
+            Ordering::Relaxed,
+        );
+        head.chain_row
+            .store(chain.map_or(0, |(_, row)| row), Ordering::Relaxed);
+        head.step
+            .store(position.map_or(0, |p| p.step), Ordering::Relaxed);
+        head.phrase.store(
+            position.map_or(Self::NONE, |p| p.phrase as usize),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn clear(&self) {
+        self.set_pattern_row(None);
+        for track_id in 0..NUM_TRACKS {
+            self.set_track(track_id, None);
+        }
+    }
+}
+
 pub struct Sequencer {
     tx: Sender<Action>,
     rx: Receiver<Action>,
     bpm: f32,
     sample_rate: u64,
     playing: bool,
-    phrase_tx: Sender<Action>,
-    phrase_rx: Receiver<Action>,
+    phrase_tx: Sender<Command>,
+    phrase_rx: Receiver<Command>,
+
+    // Playback state: the pattern row playing, the
+    // track leading it, and where each track has got to
+    arrangement: Arrangement,
+    row: usize,
+    lead: usize,
+    tracks: Vec<Track>,
+    // The leading track's chain has ended, so the next
+    // tick starts the next row
+    row_ended: bool,
+    // Samples left until the next tick
+    countdown: f64,
+    tables: HashMap<TableId, Table>,
+    playhead: Arc<Playhead>,
 }
 
 impl Sequencer {
+    const STEPS_PER_BEAT: f64 = 4.0;
+
+    // Phrases and chains played on their own go to the first track
+    const PHRASE_TRACK: TrackId = 0;
 
-    
     pub fn new(tx: Sender<Action>, rx: Receiver<Action>, sample_rate: u64, bpm: f32) -> Self {
-        
-        let (phrase_tx, phrase_rx):(Sender<Action>, Receiver<Action>) = unbounded();
-        
+        let (phrase_tx, phrase_rx): (Sender<Command>, Receiver<Command>) = unbounded();
+
         Self {
             sample_rate,
             bpm,
@@ -29,25 +1201,107 @@ impl Sequencer {
             rx,
             phrase_tx,
             phrase_rx,
+            arrangement: Arrangement {
+                ticks_per_step: DEFAULT_TICKS_PER_STEP,
+                rows: vec![],
+                first_pattern: None,
+            },
+            row: 0,
+            lead: 0,
+            tracks: (0..NUM_TRACKS).map(|_| Track::new()).collect(),
+            row_ended: false,
+            countdown: 0.0,
+            tables: HashMap::new(),
+            playhead: Arc::new(Playhead::new()),
         }
     }
 
     pub fn run(&self) {
         let rx = self.rx.clone();
         let tx = self.tx.clone();
+        let phrase_tx = self.phrase_tx.clone();
+        let playhead = self.playhead.clone();
 
         thread::spawn(move || {
+            // PlayPhrase, PlayChain and PlaySong toggle playback on and off
+            let mut playing: Option<Source> = None;
+
             while let Ok(action) = rx.recv() {
+                let source = match action {
+                    Action::PlayPhrase(phrase_id) => Some(Source::Phrase(phrase_id)),
+                    Action::PlayChain(chain_id) => Some(Source::Chain(chain_id)),
+                    Action::PlaySong(pattern_id) => Some(Source::Song(pattern_id)),
+                    _ => None,
+                };
+
                 match action {
-                    Action::PlayPhrase(phrase_id) => {
-                        let (reply_tx, reply_rx) = bounded(1); // one-shot channel
-                        tx.send(Action::GetPhraseData {
-                            phrase_id,
-                            reply_to: reply_tx,
-                        })
-                        .unwrap();
+                    Action::PlayPhrase(_) | Action::PlayChain(_) | Action::PlaySong(_) => {
+                        if playing.is_some() {
+                            phrase_tx.send(Command::Stop).unwrap();
+                            playing = None;
+                            // The audio may stop before the command is seen
+                            playhead.clear();
+                        } else if let Some(source) = source {
+                            let (reply_tx, reply_rx) = bounded(1); // one-shot channel
+                            tx.send(Action::GetTables { reply_to: reply_tx }).unwrap();
+                            let tables = reply_rx.recv().unwrap();
+                            phrase_tx.send(Command::SetTables(tables)).unwrap();
 
-                        let phrase_data = reply_rx.recv().unwrap();
+                            let arrangement = Self::fetch_arrangement(&tx, source);
+                            phrase_tx.send(Command::Play(arrangement)).unwrap();
+                            playing = Some(source);
+                        }
+                    }
+                    Action::SetTableRow {
+                        table_id,
+                        index,
+                        row,
+                    } => {
+                        phrase_tx
+                            .send(Command::SetTableRow {
+                                table_id,
+                                index,
+                                row,
+                            })
+                            .unwrap();
+                    }
+                    // Pattern, chain, length, groove and dimension changes
+                    // and block edits might affect what's playing, so
+                    // look it all up again
+                    Action::SetPatternValue { .. }
+                    | Action::CutBlock { .. }
+                    | Action::PasteBlock { .. }
+                    | Action::ClearBlock { .. }
+                    | Action::Undo
+                    | Action::Cleanup { .. }
+                    | Action::SetChainPhrase { .. }
+                    | Action::SetChainTranspose { .. }
+                    | Action::SetPhraseLength { .. }
+                    | Action::SetSongDimensions { .. }
+                    | Action::SetGrooveStep { .. }
+                    | Action::SetTicksPerStep { .. }
+                    | Action::SetTrackGroove { .. }
+                    | Action::SetPhraseGroove { .. } => {
+                        if let Some(source) = playing {
+                            let arrangement = Self::fetch_arrangement(&tx, source);
+                            phrase_tx.send(Command::Update(arrangement)).unwrap();
+                        }
+                    }
+                    Action::SetPhraseStep {
+                        phrase_id,
+                        index,
+                        step,
+                    } => {
+                        phrase_tx
+                            .send(Command::SetStep {
+                                phrase_id,
+                                index,
+                                step,
+                            })
+                            .unwrap();
+                    }
+                    Action::GetPlayhead { reply_to } => {
+                        let _ = reply_to.send(playhead.clone());
                     }
                     _ => {}
                 }
@@ -55,5 +1309,547 @@ impl Sequencer {
         });
     }
 
-    pub fn tick(&self) {}
+    // The song has already had any change that prompted
+    // this, as the dispatcher sent it there first
+    fn fetch_arrangement(tx: &Sender<Action>, source: Source) -> Arrangement {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTicksPerStep { reply_to: reply_tx })
+            .unwrap();
+        let ticks_per_step = reply_rx.recv().unwrap();
+
+        let track = Self::PHRASE_TRACK;
+        let rows = match source {
+            Source::Phrase(phrase_id) => {
+                vec![vec![vec![Self::fetch_phrase(tx, track, phrase_id, 0)]]]
+            }
+            Source::Chain(chain_id) => vec![vec![Self::fetch_chain(tx, track, chain_id)]],
+            // From the starting row up to the first row with no chains
+            Source::Song(pattern_id) => {
+                let (reply_tx, reply_rx) = bounded(1);
+                tx.send(Action::GetPatternData { reply_to: reply_tx })
+                    .unwrap();
+
+                reply_rx
+                    .recv()
+                    .unwrap()
+                    .into_iter()
+                    .skip(pattern_id as usize)
+                    .take_while(|chains| chains.iter().any(Option::is_some))
+                    .map(|chains| {
+                        chains
+                            .into_iter()
+                            .enumerate()
+                            .map(|(track_id, chain_id)| {
+                                chain_id.map_or(vec![], |chain_id| {
+                                    Self::fetch_chain(tx, track_id as TrackId, chain_id)
+                                })
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+        };
+
+        let first_pattern = match source {
+            Source::Song(pattern_id) => Some(pattern_id as usize),
+            _ => None,
+        };
+
+        Arrangement {
+            ticks_per_step,
+            rows,
+            first_pattern,
+        }
+    }
+
+    fn fetch_chain(tx: &Sender<Action>, track_id: TrackId, chain_id: ChainId) -> Vec<QueuedPhrase> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetChainData {
+            chain_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        reply_rx
+            .recv()
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, row)| {
+                row.phrase.map(|phrase_id| QueuedPhrase {
+                    chain: Some((chain_id, index)),
+                    ..Self::fetch_phrase(tx, track_id, phrase_id, row.semitones())
+                })
+            })
+            .collect()
+    }
+
+    fn fetch_phrase(
+        tx: &Sender<Action>,
+        track_id: TrackId,
+        phrase_id: PhraseId,
+        transpose: i8,
+    ) -> QueuedPhrase {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseData {
+            phrase_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        let mut steps = reply_rx.recv().unwrap();
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseLength {
+            phrase_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        steps.truncate(reply_rx.recv().unwrap());
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTiming {
+            track_id,
+            phrase_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        let timing = reply_rx.recv().unwrap();
+
+        QueuedPhrase {
+            phrase_id,
+            chain: None,
+            steps,
+            transpose,
+            timing,
+        }
+    }
+
+    pub fn get_bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u64) {
+        self.sample_rate = sample_rate;
+    }
+
+    // Ticks per step is the same for the whole song
+    fn samples_per_tick(&self) -> f64 {
+        self.sample_rate as f64 * 60.0
+            / (self.bpm as f64
+                * Self::STEPS_PER_BEAT
+                * self.arrangement.ticks_per_step.max(1) as f64)
+    }
+
+    // Called once per sample from the audio thread
+    pub fn tick(&mut self, target: &mut impl NoteTarget) {
+        while let Ok(command) = self.phrase_rx.try_recv() {
+            self.handle_command(command, target);
+        }
+
+        if !self.playing || self.arrangement.rows.is_empty() {
+            return;
+        }
+
+        if self.countdown <= 0.0 {
+            self.play_tick(target);
+            self.countdown += self.samples_per_tick();
+        }
+        self.countdown -= 1.0;
+    }
+
+    fn handle_command(&mut self, command: Command, target: &mut impl NoteTarget) {
+        match command {
+            Command::Play(arrangement) => {
+                self.arrangement = arrangement;
+                self.row = 0;
+                self.lead = self.arrangement.lead(0);
+                for track in &mut self.tracks {
+                    track.restart();
+                }
+                self.row_ended = false;
+                self.countdown = 0.0;
+                self.playing = true;
+            }
+            Command::Stop => {
+                for (track_id, track) in self.tracks.iter_mut().enumerate() {
+                    if self.playing && !self.arrangement.phrases(self.row, track_id).is_empty() {
+                        track.silence(track_id, target);
+                    }
+                }
+                self.playing = false;
+                self.playhead.clear();
+            }
+            Command::Update(arrangement) => {
+                self.arrangement = arrangement;
+                if self.row >= self.arrangement.rows.len() {
+                    self.row = 0;
+                    for track in &mut self.tracks {
+                        track.restart();
+                    }
+                }
+                for (track_id, track) in self.tracks.iter_mut().enumerate() {
+                    if track.index >= self.arrangement.phrases(self.row, track_id).len() {
+                        track.restart();
+                    }
+                }
+                self.lead = self.arrangement.lead(self.row);
+            }
+            Command::SetTables(tables) => {
+                self.tables = tables;
+            }
+            Command::SetTableRow {
+                table_id,
+                index,
+                row,
+            } => {
+                if let Some(slot) = self.tables.entry(table_id).or_default().rows.get_mut(index) {
+                    *slot = row;
+                }
+            }
+            Command::SetStep {
+                phrase_id,
+                index,
+                step,
+            } => {
+                let phrases = self.arrangement.rows.iter_mut().flatten().flatten();
+                for phrase in phrases {
+                    if phrase.phrase_id == phrase_id
+                        && let Some(slot) = phrase.steps.get_mut(index)
+                    {
+                        *slot = step;
+                    }
+                }
+            }
+        }
+    }
+
+    // Play a tick on every track, moving on to the
+    // next row first if the leading track's chain has ended
+    fn play_tick(&mut self, target: &mut impl NoteTarget) {
+        if self.row_ended {
+            self.row_ended = false;
+            self.next_row(target);
+        }
+        self.playhead
+            .set_pattern_row(self.arrangement.first_pattern.map(|first| first + self.row));
+
+        for track_id in 0..self.tracks.len() {
+            if self.arrangement.phrases(self.row, track_id).is_empty() {
+                self.playhead.set_track(track_id, None);
+                continue;
+            }
+
+            let track = &mut self.tracks[track_id];
+            if track.tick == 0 {
+                let phrase = &self.arrangement.phrases(self.row, track_id)[track.index];
+                track.step_ticks = phrase.timing.step_ticks(track.position);
+                self.playhead.set_track(
+                    track_id,
+                    Some(TrackPosition {
+                        chain: phrase.chain,
+                        phrase: phrase.phrase_id,
+                        step: track.position,
+                    }),
+                );
+                self.start_step(track_id, target);
+            }
+            self.run_commands(track_id, target);
+
+            let track = &mut self.tracks[track_id];
+            track.tick += 1;
+            if track.tick >= track.step_ticks {
+                track.tick = 0;
+                let chain_ended = self.next_step(track_id);
+                self.row_ended |= chain_ended && track_id == self.lead;
+            }
+        }
+    }
+
+    // Hops stay within the phrase; otherwise the end of
+    // a phrase moves on to the next in the chain. Returns
+    // whether the chain has come to its end.
+    fn next_step(&mut self, track_id: usize) -> bool {
+        let phrases = self.arrangement.phrases(self.row, track_id);
+        let track = &mut self.tracks[track_id];
+        let len = phrases[track.index].steps.len();
+        match track.hop.take() {
+            Some(step) => {
+                track.position = step % len;
+                false
+            }
+            None => {
+                track.position += 1;
+                if track.position < len {
+                    return false;
+                }
+                track.position = 0;
+                track.index = (track.index + 1) % phrases.len();
+                track.index == 0
+            }
+        }
+    }
+
+    // Start every track from the top of the next row,
+    // silencing those that don't play on it
+    fn next_row(&mut self, target: &mut impl NoteTarget) {
+        let next = (self.row + 1) % self.arrangement.rows.len();
+        for (track_id, track) in self.tracks.iter_mut().enumerate() {
+            if !self.arrangement.phrases(self.row, track_id).is_empty()
+                && self.arrangement.phrases(next, track_id).is_empty()
+            {
+                track.silence(track_id, target);
+            }
+            track.restart();
+        }
+
+        self.row = next;
+        self.lead = self.arrangement.lead(next);
+    }
+
+    fn start_step(&mut self, track_id: usize, target: &mut impl NoteTarget) {
+        let track = &mut self.tracks[track_id];
+        let channel = &mut track.channel;
+
+        if let Some(remaining) = channel.hold {
+            if remaining <= 1 {
+                target.note_off(track_id as TrackId);
+                channel.hold = None;
+            } else {
+                channel.hold = Some(remaining - 1);
+            }
+        }
+
+        let phrase = &self.arrangement.phrases(self.row, track_id)[track.index];
+        let transpose = phrase.transpose;
+        let step = phrase.steps.get(track.position).copied().flatten();
+        channel.step = step;
+        let Some(step) = step else {
+            return;
+        };
+
+        let mut delayed = false;
+        for fx in step.fx.iter().flatten() {
+            match fx.kind {
+                FxKind::Delay => delayed = fx.value > 0,
+                FxKind::Tempo if fx.value > 0 => self.bpm = fx.value as f32,
+                FxKind::Hop => track.hop = Some(fx.value as usize),
+                FxKind::Param => target.set_param(
+                    track_id as TrackId,
+                    fx.high() as usize,
+                    fx.low() as f32 / 15.0,
+                ),
+                _ => {}
+            }
+        }
+
+        if !delayed {
+            Self::start_note(&mut track.channel, target, track_id, step, transpose);
+        }
+    }
+
+    // The chain row's transpose moves the note itself,
+    // as far as the top of the MIDI range
+    fn start_note(
+        channel: &mut Channel,
+        target: &mut impl NoteTarget,
+        track_id: usize,
+        step: Step,
+        transpose: i8,
+    ) {
+        let note = match step.kind {
+            StepKind::Note(note) => note.saturating_add_signed(transpose).min(MAX_NOTE),
+            StepKind::Off => {
+                target.note_off(track_id as TrackId);
+                channel.note = None;
+                channel.hold = None;
+                return;
+            }
+            StepKind::Kill => {
+                target.kill(track_id as TrackId);
+                channel.note = None;
+                channel.hold = None;
+                channel.table = None;
+                return;
+            }
+        };
+        if let Some(instrument) = step.instrument {
+            channel.instrument = instrument;
+        }
+        channel.velocity = step.velocity.unwrap_or(MAX_VELOCITY);
+        channel.note = Some(note);
+        channel.hold = (step.len > 0).then_some(step.len);
+        channel.slide = 0.0;
+        channel.volume = 1.0;
+        channel.vibrato_phase = 0.0;
+
+        // An A command overrides the instrument's table
+        let table = step
+            .fx
+            .iter()
+            .flatten()
+            .find(|fx| fx.kind == FxKind::Table)
+            .map(|fx| fx.value)
+            .or_else(|| target.instrument_table(channel.instrument));
+        channel.table = table.map(TableState::new);
+        channel.transpose = 0.0;
+        channel.table_volume = 1.0;
+
+        target.note_on(
+            track_id as TrackId,
+            channel.instrument,
+            note,
+            channel.velocity,
+        );
+    }
+
+    // Per-tick work for the commands on the current step
+    fn run_commands(&mut self, track_id: usize, target: &mut impl NoteTarget) {
+        let track = &mut self.tracks[track_id];
+        let tick = track.tick;
+        let transpose = self.arrangement.phrases(self.row, track_id)[track.index].transpose;
+        let channel = &mut track.channel;
+
+        let mut bend = 0.0;
+        let step = channel.step;
+        for fx in step.iter().flat_map(|step| step.fx).flatten() {
+            let value = fx.value as u32;
+            match fx.kind {
+                FxKind::Arpeggio => {
+                    bend += [0, fx.high(), fx.low()][tick as usize % 3] as f32;
+                }
+                FxKind::SlideUp if tick > 0 => channel.slide += value as f32 / 16.0,
+                FxKind::SlideDown if tick > 0 => channel.slide -= value as f32 / 16.0,
+                FxKind::Vibrato => {
+                    let depth = fx.low() as f32 / 8.0;
+                    bend += depth * (2.0 * PI * channel.vibrato_phase).sin();
+                    channel.vibrato_phase += fx.high() as f32 / 32.0;
+                }
+                FxKind::VolumeSlide if tick > 0 => {
+                    let change = (fx.high() as f32 - fx.low() as f32) / 64.0;
+                    channel.volume = (channel.volume + change).clamp(0.0, 1.0);
+                }
+                FxKind::Retrigger if value > 0 && tick > 0 && tick.is_multiple_of(value) => {
+                    if let Some(note) = channel.note {
+                        target.note_on(
+                            track_id as TrackId,
+                            channel.instrument,
+                            note,
+                            channel.velocity,
+                        );
+                    }
+                }
+                FxKind::Delay if value > 0 && tick == value => {
+                    if let Some(step) = step {
+                        Self::start_note(channel, target, track_id, step, transpose);
+                    }
+                }
+                FxKind::Cut if tick == value => {
+                    target.note_off(track_id as TrackId);
+                    channel.note = None;
+                }
+                FxKind::Kill if tick == value => {
+                    target.kill(track_id as TrackId);
+                    channel.note = None;
+                    channel.table = None;
+                }
+                _ => {}
+            }
+        }
+
+        self.run_table(track_id, target);
+        Self::send_levels(&mut self.tracks[track_id].channel, target, track_id, bend);
+    }
+
+    // Play the running table's current row and move on
+    fn run_table(&mut self, track_id: usize, target: &mut impl NoteTarget) {
+        let track = track_id as TrackId;
+        let channel = &mut self.tracks[track_id].channel;
+        let Some(state) = &mut channel.table else {
+            return;
+        };
+
+        let row = self
+            .tables
+            .get(&state.table_id)
+            .map(|table| table.rows[state.row])
+            .unwrap_or_default();
+
+        channel.transpose = row.semitones() as f32;
+        if let Some(volume) = row.volume {
+            channel.table_volume = volume.min(MAX_VELOCITY) as f32 / MAX_VELOCITY as f32;
+        }
+
+        let mut next = state.row + 1;
+        let mut next_table = None;
+        let mut stop = false;
+        for fx in row.fx.iter().flatten() {
+            let value = fx.value as f32;
+            match fx.kind {
+                FxKind::SlideUp => channel.slide += value / 16.0,
+                FxKind::SlideDown => channel.slide -= value / 16.0,
+                FxKind::VolumeSlide => {
+                    let change = (fx.high() as f32 - fx.low() as f32) / 64.0;
+                    channel.volume = (channel.volume + change).clamp(0.0, 1.0);
+                }
+                FxKind::Retrigger => {
+                    if let Some(note) = channel.note {
+                        target.note_on(track, channel.instrument, note, channel.velocity);
+                    }
+                }
+                FxKind::Cut => {
+                    target.note_off(track);
+                    channel.note = None;
+                }
+                FxKind::Kill => {
+                    target.kill(track);
+                    channel.note = None;
+                    stop = true;
+                }
+                FxKind::Tempo if fx.value > 0 => self.bpm = value,
+                FxKind::Param => {
+                    target.set_param(track, fx.high() as usize, fx.low() as f32 / 15.0)
+                }
+                FxKind::Table => next_table = Some(fx.value),
+                FxKind::Hop => {
+                    let hops = &mut state.hops[state.row];
+                    if fx.high() == 0 || *hops < fx.high() {
+                        *hops += 1;
+                        next = fx.low() as usize;
+                    } else {
+                        *hops = 0;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if stop {
+            channel.table = None;
+        } else if let Some(table_id) = next_table {
+            channel.table = Some(TableState::new(table_id));
+        } else if next < NUM_TABLE_ROWS {
+            state.row = next;
+        } else {
+            channel.table = None;
+        }
+    }
+
+    // Send the pitch and volume if they've changed
+    fn send_levels(
+        channel: &mut Channel,
+        target: &mut impl NoteTarget,
+        track_id: usize,
+        bend: f32,
+    ) {
+        let pitch = channel.slide + bend + channel.transpose;
+        if pitch != channel.pitch_sent {
+            target.set_pitch(track_id as TrackId, pitch);
+            channel.pitch_sent = pitch;
+        }
+        let volume = channel.volume * channel.table_volume;
+        if volume != channel.volume_sent {
+            target.set_volume(track_id as TrackId, volume);
+            channel.volume_sent = volume;
+        }
+    }
 }
@@ -1,10 +1,18 @@
-use crate::model::Song;
-use crossbeam::channel::{Receiver, Sender};
+use crate::engine::Playhead;
+use crate::engine::audio::{MixerMeters, NodeDef, NodeId, Patch};
+use crate::model::{Song, SongError};
+use crossbeam::channel::{Receiver, Sender, bounded};
 use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::thread;
 
-use crate::types::{ChainId, PatternId, PhraseId, Step, TrackId};
+use crate::types::{
+    Block, Cell, ChainId, ChainRow, CleanupReport, DelaySettings, Diagnostic, Grid, Groove,
+    GrooveId, InstrumentId, MixerChannel, MixerSettings, NUM_TRACKS, Notation, Note, PatternId,
+    PhraseId, ReverbSettings, SendBus, SendEffects, SongDimensions, Step, Table, TableId, TableRow,
+    Timing, TrackId, Usage,
+};
 
 #[cfg(test)]
 mod tests {
@@ -12,6 +20,7 @@ mod tests {
     use crossbeam::channel::{bounded, unbounded};
 
     use super::*;
+    use crate::types::{Accidentals, NUM_PATTERNS, Problem};
     use serial_test::serial;
 
     struct TestEnv {
@@ -70,8 +79,8 @@ mod tests {
     }
 
     /*
-     * Test setting phrase IDs in a chain, then retrieving all
-     * chains and check the phrase IDs have been set.
+     * Test setting phrase IDs and transposes in a chain, then
+     * retrieving all chains and check they have been set.
      */
 
     #[test]
@@ -83,11 +92,13 @@ mod tests {
 
         let chain_ids = vec![0, 15, 1];
         let phrase_ids = vec![Some(0), Some(15), None];
+        let transposes = vec![0x0C, 0xF4, 0x00];
         let indices = [0, 1, 3];
 
         for i in 0..indices.len() {
             let chain_id = chain_ids[i];
             let phrase_id = phrase_ids[i];
+            let transpose = transposes[i];
             let index = indices[i];
 
             let _ = tx.send(Action::SetChainPhrase {
@@ -95,6 +106,11 @@ mod tests {
                 index: index,
                 phrase_id: phrase_id,
             });
+            let _ = tx.send(Action::SetChainTranspose {
+                chain_id,
+                index,
+                transpose,
+            });
 
             let (reply_tx, reply_rx) = bounded(1);
 
@@ -106,8 +122,640 @@ mod tests {
 
             let chain = reply_rx.recv().unwrap();
 
-            assert!(chain[index] == phrase_id);
+            assert!(
+                chain[index]
+                    == ChainRow {
+                        phrase: phrase_id,
+                        transpose
+                    }
+            );
+        }
+    }
+
+    /*
+     * Test clones go to the lowest ID that isn't written
+     * to or in use, and that a deep clone gets its own
+     * copies of the chain's phrases.
+     */
+
+    #[test]
+    #[serial]
+    fn clone_chains_and_phrases() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        for (index, phrase_id) in [0, 2, 0].into_iter().enumerate() {
+            let _ = tx.send(Action::SetChainPhrase {
+                chain_id: 0,
+                index,
+                phrase_id: Some(phrase_id),
+            });
+        }
+        let _ = tx.send(Action::SetPhraseStep {
+            phrase_id: 0,
+            index: 0,
+            step: Some(Step::new(60, 0)),
+        });
+        let _ = tx.send(Action::SetPatternValue {
+            pattern_id: 0,
+            track_id: 0,
+            chain_id: Some(1),
+        });
+
+        let clone_chain = |deep| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::CloneChain {
+                chain_id: 0,
+                deep,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            reply_rx.recv().unwrap().unwrap()
+        };
+        let phrases = |chain_id| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetChainData {
+                chain_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            let rows: Vec<ChainRow> = reply_rx.recv().unwrap();
+            rows.iter().map(|row| row.phrase).collect::<Vec<_>>()
+        };
+
+        assert_eq!(clone_chain(false), 2);
+        assert_eq!(phrases(2), vec![Some(0), Some(2), Some(0), None]);
+
+        assert_eq!(clone_chain(true), 3);
+        assert_eq!(phrases(3), vec![Some(1), Some(3), Some(1), None]);
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseData {
+            phrase_id: 1,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        assert_eq!(reply_rx.recv().unwrap()[0], Some(Step::new(60, 0)));
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::ClonePhrase {
+            phrase_id: 2,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        assert_eq!(reply_rx.recv().unwrap(), Ok(4));
+    }
+
+    /*
+     * Test copying a block of phrase cells and pasting it
+     * over another phrase, then inserting it, undoing that,
+     * and that it can't be pasted into the song's chain
+     * columns.
+     */
+
+    #[test]
+    #[serial]
+    fn block_edits() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        for (index, note) in [60, 61, 62, 63].into_iter().enumerate() {
+            let _ = tx.send(Action::SetPhraseStep {
+                phrase_id: 0,
+                index,
+                step: Some(Step {
+                    instrument: (index == 1).then_some(1),
+                    ..Step::new(note, 0)
+                }),
+            });
+        }
+
+        let notes = |phrase_id| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetPhraseData {
+                phrase_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            let steps: Vec<Option<Step>> = reply_rx.recv().unwrap();
+            steps[..6]
+                .iter()
+                .map(|step| step.and_then(|s| s.note().map(|note| (note, s.instrument))))
+                .collect::<Vec<_>>()
+        };
+
+        let _ = tx.send(Action::CopyBlock {
+            block: Block {
+                grid: Grid::Phrase(0),
+                row: 1,
+                col: 0,
+                rows: 2,
+                cols: 2,
+            },
+        });
+        let _ = tx.send(Action::PasteBlock {
+            grid: Grid::Phrase(5),
+            row: 0,
+            col: 0,
+            insert: false,
+        });
+        assert_eq!(
+            notes(5),
+            vec![
+                Some((61, Some(1))),
+                Some((62, None)),
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+
+        let _ = tx.send(Action::PasteBlock {
+            grid: Grid::Phrase(0),
+            row: 0,
+            col: 0,
+            insert: true,
+        });
+        assert_eq!(
+            notes(0),
+            vec![
+                Some((61, Some(1))),
+                Some((62, None)),
+                Some((60, None)),
+                Some((61, Some(1))),
+                Some((62, None)),
+                Some((63, None)),
+            ]
+        );
+
+        let _ = tx.send(Action::Undo);
+        assert_eq!(
+            notes(0),
+            vec![
+                Some((60, None)),
+                Some((61, Some(1))),
+                Some((62, None)),
+                Some((63, None)),
+                None,
+                None,
+            ]
+        );
+
+        let _ = tx.send(Action::PasteBlock {
+            grid: Grid::Song,
+            row: 0,
+            col: 0,
+            insert: false,
+        });
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPatternData { reply_to: reply_tx })
+            .unwrap();
+        let patterns = reply_rx.recv().unwrap();
+        assert!(patterns.iter().flatten().all(Option::is_none));
+    }
+
+    /*
+     * Test that clearing and insert-pasting on the song
+     * grid only keeps patterns up to the last one with
+     * a chain in, rather than filling out every row.
+     */
+
+    #[test]
+    #[serial]
+    fn song_block_edits_keep_patterns_short() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let patterns = |tx: &Sender<Action>| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetPatternData { reply_to: reply_tx })
+                .unwrap();
+            reply_rx.recv().unwrap()
+        };
+        let whole_song = Block {
+            grid: Grid::Song,
+            row: 0,
+            col: 0,
+            rows: NUM_PATTERNS,
+            cols: 1,
+        };
+
+        for pattern_id in [0, 2] {
+            let _ = tx.send(Action::SetPatternValue {
+                pattern_id,
+                track_id: 0,
+                chain_id: Some(pattern_id),
+            });
+        }
+        let _ = tx.send(Action::CopyBlock {
+            block: Block {
+                rows: 1,
+                ..whole_song
+            },
+        });
+        let _ = tx.send(Action::PasteBlock {
+            grid: Grid::Song,
+            row: 0,
+            col: 0,
+            insert: true,
+        });
+        assert_eq!(patterns(&tx).len(), 4);
+
+        let _ = tx.send(Action::ClearBlock {
+            block: Block {
+                row: 2,
+                ..whole_song
+            },
+        });
+        assert_eq!(patterns(&tx).len(), 2);
+
+        let _ = tx.send(Action::CutBlock { block: whole_song });
+        assert!(patterns(&tx).is_empty());
+    }
+
+    /*
+     * Test that the usage shows which chains and phrases
+     * nothing refers to, and that cleaning up deletes
+     * them and renumbers the rest.
+     */
+
+    #[test]
+    #[serial]
+    fn usage_and_cleanup() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        // Pattern 1 uses chain 4 twice, which uses phrase 9;
+        // chain 2 isn't in a pattern, so phrase 3 is unused
+        for track_id in [0, 2] {
+            let _ = tx.send(Action::SetPatternValue {
+                pattern_id: 1,
+                track_id,
+                chain_id: Some(4),
+            });
+        }
+        for (chain_id, phrase_id) in [(4, 9), (2, 3)] {
+            let _ = tx.send(Action::SetChainPhrase {
+                chain_id,
+                index: 0,
+                phrase_id: Some(phrase_id),
+            });
+            let _ = tx.send(Action::SetPhraseStep {
+                phrase_id,
+                index: 0,
+                step: Some(Step::new(60, 0)),
+            });
+        }
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetUsage { reply_to: reply_tx }).unwrap();
+        let usage = reply_rx.recv().unwrap();
+
+        assert_eq!(usage.pattern_chains, vec![vec![], vec![4, 4]]);
+        assert_eq!(usage.chain_references[&4], 2);
+        assert_eq!(usage.phrase_references[&3], 1);
+        assert_eq!(usage.orphaned_chains, vec![2]);
+        assert_eq!(usage.orphaned_phrases, vec![3]);
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::Cleanup {
+            renumber: true,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        assert_eq!(
+            reply_rx.recv().unwrap(),
+            CleanupReport {
+                removed_chains: vec![2],
+                removed_phrases: vec![3],
+                renumbered_chains: vec![(4, 0)],
+                renumbered_phrases: vec![(9, 0)],
+            }
+        );
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPatternData { reply_to: reply_tx })
+            .unwrap();
+        assert_eq!(reply_rx.recv().unwrap()[1][..3], [Some(0), None, Some(0)]);
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetChainData {
+            chain_id: 0,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        assert_eq!(reply_rx.recv().unwrap()[0].phrase, Some(0));
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseData {
+            phrase_id: 0,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        assert_eq!(reply_rx.recv().unwrap()[0], Some(Step::new(60, 0)));
+    }
+
+    /*
+     * Test that cleaning up keeps empty chains and phrases
+     * that are still referenced, so nothing is left
+     * pointing at an ID that's gone.
+     */
+
+    #[test]
+    #[serial]
+    fn cleanup_keeps_referenced_empty_chains_and_phrases() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let cleanup = |tx: &Sender<Action>, renumber: bool| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::Cleanup {
+                renumber,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            reply_rx.recv().unwrap()
+        };
+
+        // Chain 5 plays phrase 7, whose only step has been
+        // cleared; chain 6 had its only phrase cleared
+        for (track_id, chain_id) in [(0, 5), (1, 6)] {
+            let _ = tx.send(Action::SetPatternValue {
+                pattern_id: 0,
+                track_id,
+                chain_id: Some(chain_id),
+            });
+        }
+        for (chain_id, phrase_id) in [(5, Some(7)), (6, Some(8)), (6, None)] {
+            let _ = tx.send(Action::SetChainPhrase {
+                chain_id,
+                index: 0,
+                phrase_id,
+            });
+        }
+        for step in [Some(Step::new(60, 0)), None] {
+            let _ = tx.send(Action::SetPhraseStep {
+                phrase_id: 7,
+                index: 0,
+                step,
+            });
+        }
+
+        assert_eq!(cleanup(&tx, false), CleanupReport::default());
+        assert_eq!(
+            cleanup(&tx, true),
+            CleanupReport {
+                renumbered_chains: vec![(5, 0), (6, 1)],
+                renumbered_phrases: vec![(7, 0)],
+                ..Default::default()
+            }
+        );
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPatternData { reply_to: reply_tx })
+            .unwrap();
+        assert_eq!(reply_rx.recv().unwrap()[0][..2], [Some(0), Some(1)]);
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetChainData {
+            chain_id: 0,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        assert_eq!(reply_rx.recv().unwrap()[0].phrase, Some(0));
+    }
+
+    /*
+     * Test the checker finds a missing chain, a missing
+     * phrase, an empty pattern in the middle of the song
+     * and a velocity out of range.
+     */
+
+    #[test]
+    #[serial]
+    fn check_song() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        for pattern_id in [0, 2] {
+            let _ = tx.send(Action::SetPatternValue {
+                pattern_id,
+                track_id: 1,
+                chain_id: Some(pattern_id),
+            });
+        }
+        let _ = tx.send(Action::SetChainPhrase {
+            chain_id: 0,
+            index: 3,
+            phrase_id: Some(7),
+        });
+        let _ = tx.send(Action::SetPhraseStep {
+            phrase_id: 1,
+            index: 5,
+            step: Some(Step {
+                velocity: Some(0x90),
+                ..Step::new(60, 0)
+            }),
+        });
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::CheckSong { reply_to: reply_tx }).unwrap();
+        let problems: Vec<(Grid, usize, Option<usize>, Problem)> = reply_rx
+            .recv()
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.grid, d.row, d.col, d.problem))
+            .collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                (Grid::Song, 1, None, Problem::EmptyPattern),
+                (Grid::Song, 2, Some(1), Problem::MissingChain(2)),
+                (Grid::Chain(0), 3, Some(0), Problem::MissingPhrase(7)),
+                (
+                    Grid::Phrase(1),
+                    5,
+                    Some(Grid::VELOCITY_COLUMN),
+                    Problem::OutOfRange {
+                        what: "velocity",
+                        value: 0x90,
+                        max: 0x7F
+                    }
+                ),
+            ]
+        );
+    }
+
+    /*
+     * Test that setting a song checks it, flagging
+     * chain rows whose transpose takes a note in their
+     * phrase out of range either way, and not one that
+     * keeps every note in range.
+     */
+
+    #[test]
+    #[serial]
+    fn set_song_is_checked() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let mut song = Song::new();
+        song.update_pattern(0, 0, Some(0)).unwrap();
+        song.set_phrase_step(1, 0, Some(Step::new(0x70, 0)))
+            .unwrap();
+        song.set_phrase_step(2, 0, Some(Step::new(0x05, 0)))
+            .unwrap();
+        for (row, phrase_id, transpose) in [(0, 1, 0x0C), (1, 2, 0xF4), (2, 1, 0x10)] {
+            song.set_chain_phrase(0, row, Some(phrase_id)).unwrap();
+            song.set_chain_transpose(0, row, transpose).unwrap();
+        }
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::SetSong {
+            song: Box::new(song),
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        let problems: Vec<(Grid, usize, Option<usize>, Problem)> = reply_rx
+            .recv()
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.grid, d.row, d.col, d.problem))
+            .collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                (
+                    Grid::Chain(0),
+                    1,
+                    Some(1),
+                    Problem::TransposeOutOfRange(-12)
+                ),
+                (Grid::Chain(0), 2, Some(1), Problem::TransposeOutOfRange(16)),
+            ]
+        );
+    }
+
+    /*
+     * Test that edits the song turns down come back on
+     * the edit errors receiver, and edits that fit don't.
+     */
+
+    #[test]
+    #[serial]
+    fn rejected_edits_are_reported() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetEditErrors { reply_to: reply_tx })
+            .unwrap();
+        let errors = reply_rx.recv().unwrap();
+
+        let _ = tx.send(Action::SetPhraseStep {
+            phrase_id: 0,
+            index: 0,
+            step: Some(Step::new(60, 0)),
+        });
+        let _ = tx.send(Action::SetPhraseStep {
+            phrase_id: 0,
+            index: 0x1000,
+            step: Some(Step::new(60, 0)),
+        });
+
+        assert!(matches!(
+            errors.recv().unwrap(),
+            SongError::OutOfRange { index: 0x1000, .. }
+        ));
+        assert!(errors.is_empty());
+    }
+
+    /*
+     * Test that chains and phrases come back as long as
+     * the song's dimensions say, that edits past the end
+     * are turned down, and that bad dimensions are too.
+     */
+
+    #[test]
+    #[serial]
+    fn song_dimensions() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let dimensions = |tx: &Sender<Action>| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetSongDimensions { reply_to: reply_tx })
+                .unwrap();
+            reply_rx.recv().unwrap()
+        };
+        let phrase = |tx: &Sender<Action>| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetPhraseData {
+                phrase_id: 1,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            reply_rx.recv().unwrap()
+        };
+
+        assert_eq!(dimensions(&tx), SongDimensions::default());
+        assert_eq!(phrase(&tx).len(), 16);
+
+        let bigger = SongDimensions {
+            tracks: 4,
+            phrases_per_chain: 16,
+            steps_per_phrase: 32,
+        };
+        let _ = tx.send(Action::SetSongDimensions { dimensions: bigger });
+        let _ = tx.send(Action::SetSongDimensions {
+            dimensions: SongDimensions {
+                tracks: NUM_TRACKS + 1,
+                ..bigger
+            },
+        });
+        assert_eq!(dimensions(&tx), bigger);
+
+        for index in [20, 40] {
+            let _ = tx.send(Action::SetPhraseStep {
+                phrase_id: 1,
+                index,
+                step: Some(Step::new(60, 0)),
+            });
         }
+        let steps = phrase(&tx);
+        assert_eq!(steps.len(), 32);
+        assert_eq!(steps[20], Some(Step::new(60, 0)));
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetChainData {
+            chain_id: 1,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        assert_eq!(reply_rx.recv().unwrap().len(), 16);
+
+        let _ = tx.send(Action::SetPatternValue {
+            pattern_id: 0,
+            track_id: 5,
+            chain_id: Some(1),
+        });
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPatternData { reply_to: reply_tx })
+            .unwrap();
+        assert!(reply_rx.recv().unwrap().is_empty());
     }
 
     /*
@@ -143,22 +791,342 @@ mod tests {
             });
         }
 
-        for i in 0..steps.len() {
-            let phrase_id = phrase_ids[i];
-            let index = indices[i];
-            let step = steps[i];
+        for i in 0..steps.len() {
+            let phrase_id = phrase_ids[i];
+            let index = indices[i];
+            let step = steps[i];
+
+            let (reply_tx, reply_rx) = bounded(1);
+
+            let _ = tx.send(Action::GetPhraseData {
+                phrase_id,
+                reply_to: reply_tx,
+            });
+
+            let phrase = reply_rx.recv().unwrap();
+
+            assert!(phrase[index] == step);
+        }
+    }
+
+    /*
+     * Test a step's instrument and velocity columns are
+     * kept with it, and are empty on a step without them.
+     */
+
+    #[test]
+    #[serial]
+    fn phrase_step_columns() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let steps = [
+            Step {
+                instrument: Some(3),
+                velocity: Some(0x20),
+                ..Step::new(0, 4)
+            },
+            Step {
+                instrument: Some(0x7F),
+                ..Step::new(60, 1)
+            },
+            Step::new(12, 2),
+        ];
+
+        for (index, step) in steps.iter().enumerate() {
+            let _ = tx.send(Action::SetPhraseStep {
+                phrase_id: 6,
+                index,
+                step: Some(*step),
+            });
+        }
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseData {
+            phrase_id: 6,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        let phrase = reply_rx.recv().unwrap();
+
+        for (index, step) in steps.iter().enumerate() {
+            assert_eq!(phrase[index], Some(*step));
+        }
+        assert_eq!(phrase[1].unwrap().velocity, None);
+        assert_eq!(phrase[2].unwrap().instrument, None);
+    }
+
+    /*
+     * Test a phrase's length defaults to the song's
+     * steps per phrase, can be shortened, and turns
+     * down lengths it can't have.
+     */
+
+    #[test]
+    #[serial]
+    fn phrase_length() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let length = |phrase_id| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetPhraseLength {
+                phrase_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            reply_rx.recv().unwrap()
+        };
+
+        assert_eq!(length(4), 16);
+
+        let _ = tx.send(Action::SetPhraseLength {
+            phrase_id: 4,
+            length: 12,
+        });
+        assert_eq!(length(4), 12);
+
+        for bad in [0, 17] {
+            let _ = tx.send(Action::SetPhraseLength {
+                phrase_id: 4,
+                length: bad,
+            });
+        }
+        assert_eq!(length(4), 12);
+    }
+
+    /*
+     * Test the notation is kept, and names notes the
+     * way it says: middle C is C-4 by default and
+     * B3 in octave 3 spelt with flats. Notes below
+     * octave 0 are named in it to stay three wide,
+     * and setting another song keeps the notation.
+     */
+
+    #[test]
+    #[serial]
+    fn notation() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let notation = || {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetNotation { reply_to: reply_tx }).unwrap();
+            reply_rx.recv().unwrap()
+        };
+
+        assert_eq!(notation().name(60), "C-4");
+        assert_eq!(notation().name(66), "F#4");
+
+        let _ = tx.send(Action::SetNotation {
+            notation: Notation {
+                accidentals: Accidentals::Flats,
+                middle_c_octave: 3,
+            },
+        });
+        assert_eq!(notation().name(60), "C-3");
+        assert_eq!(notation().name(58), "Bb2");
+        assert_eq!(notation().name(0), "C-0");
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::SetSong {
+            song: Box::new(Song::new()),
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        reply_rx.recv().unwrap();
+        assert_eq!(notation().name(60), "C-3");
+    }
+
+    /*
+     * Test setting up grooves, then checking a phrase
+     * plays with its track's groove until it's given
+     * one of its own.
+     */
+
+    #[test]
+    #[serial]
+    fn groove_data() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        for (index, ticks) in [Some(8), Some(4), None, Some(2)].into_iter().enumerate() {
+            let _ = tx.send(Action::SetGrooveStep {
+                groove_id: 1,
+                index,
+                ticks,
+            });
+        }
+        let _ = tx.send(Action::SetGrooveStep {
+            groove_id: 2,
+            index: 0,
+            ticks: Some(5),
+        });
+        let _ = tx.send(Action::SetTicksPerStep { ticks_per_step: 4 });
+        let _ = tx.send(Action::SetTrackGroove {
+            track_id: 3,
+            groove_id: 1,
+        });
+
+        let timing = |phrase_id| {
+            let (reply_tx, reply_rx) = bounded(1);
+            tx.send(Action::GetTiming {
+                track_id: 3,
+                phrase_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+            reply_rx.recv().unwrap()
+        };
+
+        assert_eq!(
+            timing(7),
+            Timing {
+                ticks_per_step: 4,
+                groove: vec![8, 4],
+            }
+        );
+
+        let _ = tx.send(Action::SetPhraseGroove {
+            phrase_id: 7,
+            groove_id: Some(2),
+        });
+
+        assert_eq!(timing(7).groove, vec![5]);
+    }
+
+    /*
+     * Test setting rows in tables, then retrieving
+     * them one at a time and all together.
+     */
+
+    #[test]
+    #[serial]
+    fn table_data() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let row = TableRow {
+            transpose: 0xF4,
+            volume: Some(0x40),
+            ..Default::default()
+        };
+
+        let _ = tx.send(Action::SetTableRow {
+            table_id: 2,
+            index: 5,
+            row,
+        });
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTableData {
+            table_id: 2,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        let table = reply_rx.recv().unwrap();
+        assert_eq!(table.rows[5], row);
+        assert_eq!(table.rows[5].semitones(), -12);
+        assert_eq!(table.rows[0], TableRow::default());
+
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTables { reply_to: reply_tx }).unwrap();
+
+        let tables = reply_rx.recv().unwrap();
+        assert_eq!(tables.get(&2), Some(&table));
+    }
+
+    /*
+     * Test setting the send effects, then retrieving
+     * them and checking they've been stored.
+     */
+
+    #[test]
+    #[serial]
+    fn send_effects() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
+
+        let delay = DelaySettings {
+            feedback: 0.7,
+            ping_pong: false,
+            ..Default::default()
+        };
+        let reverb = ReverbSettings {
+            size: 0.9,
+            pre_delay: 0.1,
+            ..Default::default()
+        };
+
+        let _ = tx.send(Action::SetDelaySettings { settings: delay });
+        let _ = tx.send(Action::SetReverbSettings { settings: reverb });
+        let _ = tx.send(Action::SetMixerSend {
+            track_id: 3,
+            bus: SendBus::Reverb,
+            amount: 0.5,
+        });
+
+        let (reply_tx, reply_rx) = bounded(1);
+
+        tx.send(Action::GetSendEffects { reply_to: reply_tx })
+            .unwrap();
+
+        let effects = reply_rx.recv().unwrap();
+
+        assert_eq!(effects.delay, delay);
+        assert_eq!(effects.reverb, reverb);
+        assert_eq!(effects.sends[3].reverb, 0.5);
+        assert_eq!(effects.sends[3].delay, 0.0);
+    }
+
+    /*
+     * Test that channel strip settings, mute and solo
+     * included, are kept in the song.
+     */
+
+    #[test]
+    #[serial]
+    fn mixer_settings() {
+        let env = TestEnv::new();
+
+        let tx = env.tx.clone();
 
-            let (reply_tx, reply_rx) = bounded(1);
+        let _ = tx.send(Action::SetMixerGain {
+            channel: MixerChannel::Track(2),
+            gain_db: -6.0,
+        });
+        let _ = tx.send(Action::SetMixerPan {
+            channel: MixerChannel::Master,
+            pan: 2.0,
+        });
+        let _ = tx.send(Action::SetMixerMute {
+            channel: MixerChannel::Track(5),
+            mute: true,
+        });
+        let _ = tx.send(Action::SetMixerSolo {
+            track_id: 1,
+            solo: true,
+        });
 
-            let _ = tx.send(Action::GetPhraseData {
-                phrase_id,
-                reply_to: reply_tx,
-            });
+        let (reply_tx, reply_rx) = bounded(1);
 
-            let phrase = reply_rx.recv().unwrap();
+        tx.send(Action::GetMixerSettings { reply_to: reply_tx })
+            .unwrap();
 
-            assert!(phrase[index] == step);
-        }
+        let mixer = reply_rx.recv().unwrap();
+
+        assert_eq!(mixer.tracks[2].gain_db, -6.0);
+        assert_eq!(mixer.master.pan, 1.0);
+        assert!(mixer.tracks[5].mute);
+        assert!(mixer.tracks[1].solo);
+        assert!(!mixer.tracks[0].mute && !mixer.tracks[0].solo);
     }
 }
 
@@ -173,6 +1141,60 @@ pub enum Action {
     PlayAudio,
     StopAudio,
     PlayPhrase(PhraseId),
+    PlayChain(ChainId),
+    // Play the song from a pattern row on
+    PlaySong(PatternId),
+
+    /*
+     * Notes played by hand, to audition them.
+     * They start the audio if it isn't running.
+     */
+    NoteOn {
+        track_id: TrackId,
+        instrument_id: InstrumentId,
+        note: Note,
+        velocity: u8,
+    },
+
+    NoteOff {
+        track_id: TrackId,
+    },
+
+    /*
+     * How many tracks the song has, and how long
+     * its chains and phrases are. Views lay
+     * themselves out from these.
+     */
+    GetSongDimensions {
+        reply_to: Sender<SongDimensions>,
+    },
+
+    SetSongDimensions {
+        dimensions: SongDimensions,
+    },
+
+    /*
+     * Get a receiver for the edits the song turns
+     * down, e.g. past the end of a shortened phrase.
+     * Most edits have nobody waiting on a reply, so
+     * this is where the views hear about them.
+     */
+    GetEditErrors {
+        reply_to: Sender<Receiver<SongError>>,
+    },
+
+    /*
+     * How notes are written: sharps or flats,
+     * and the octave middle C is in. A user setting
+     * kept beside the song, not in it.
+     */
+    GetNotation {
+        reply_to: Sender<Notation>,
+    },
+
+    SetNotation {
+        notation: Notation,
+    },
 
     /*
      * Get all pattern data in convenient form.
@@ -192,12 +1214,12 @@ pub enum Action {
     },
 
     /*
-     * Get the list of phrases that
-     * compose a particular chain.
+     * Get the list of phrases that compose
+     * a particular chain, with their transposes.
      */
     GetChainData {
         chain_id: ChainId,
-        reply_to: Sender<Vec<Option<PhraseId>>>,
+        reply_to: Sender<Vec<ChainRow>>,
     },
 
     SetChainPhrase {
@@ -206,6 +1228,91 @@ pub enum Action {
         phrase_id: Option<PhraseId>,
     },
 
+    SetChainTranspose {
+        chain_id: ChainId,
+        index: usize,
+        transpose: u8,
+    },
+
+    /*
+     * Block edits in the song, chain and phrase
+     * grids. The clipboard is kept here in the
+     * engine, so a block copied in one view can
+     * be pasted in another. Cuts, pastes and
+     * clears are each a single step for Undo.
+     */
+    CopyBlock {
+        block: Block,
+    },
+
+    CutBlock {
+        block: Block,
+    },
+
+    // Paste the clipboard with its top left at a cell
+    PasteBlock {
+        grid: Grid,
+        row: usize,
+        col: usize,
+        insert: bool,
+    },
+
+    ClearBlock {
+        block: Block,
+    },
+
+    Undo,
+
+    /*
+     * Which chains and phrases the song uses, and
+     * deleting the ones it doesn't. Cleanup can also
+     * renumber what's left from 0, rewriting the
+     * patterns and chains to match.
+     */
+    GetUsage {
+        reply_to: Sender<Usage>,
+    },
+
+    /*
+     * Check the song for references to things that
+     * don't exist, values out of range and empty
+     * patterns, with the cell each problem is in.
+     */
+    CheckSong {
+        reply_to: Sender<Vec<Diagnostic>>,
+    },
+
+    /*
+     * Replace the whole song, as when one is loaded,
+     * replying with what checking it found so a bad
+     * song is flagged as soon as it arrives.
+     */
+    SetSong {
+        song: Box<Song>,
+        reply_to: Sender<Vec<Diagnostic>>,
+    },
+
+    Cleanup {
+        renumber: bool,
+        reply_to: Sender<CleanupReport>,
+    },
+
+    /*
+     * Copy a chain or phrase to the lowest free ID,
+     * replying with the new ID. A deep chain clone
+     * copies the chain's phrases as well.
+     */
+    CloneChain {
+        chain_id: ChainId,
+        deep: bool,
+        reply_to: Sender<Result<ChainId, SongError>>,
+    },
+
+    ClonePhrase {
+        phrase_id: PhraseId,
+        reply_to: Sender<Result<PhraseId, SongError>>,
+    },
+
     GetPhraseData {
         phrase_id: PhraseId,
         reply_to: Sender<Vec<Option<Step>>>,
@@ -216,26 +1323,281 @@ pub enum Action {
         index: usize,
         step: Option<Step>,
     },
+
+    /*
+     * How many of a phrase's steps play, from 1
+     * up to the song's steps per phrase.
+     */
+    GetPhraseLength {
+        phrase_id: PhraseId,
+        reply_to: Sender<usize>,
+    },
+
+    SetPhraseLength {
+        phrase_id: PhraseId,
+        length: usize,
+    },
+
+    /*
+     * Grooves, and which track or phrase
+     * plays with which. A phrase with no
+     * groove of its own uses its track's.
+     */
+    GetGroove {
+        groove_id: GrooveId,
+        reply_to: Sender<Groove>,
+    },
+
+    SetGrooveStep {
+        groove_id: GrooveId,
+        index: usize,
+        ticks: Option<u8>,
+    },
+
+    GetTicksPerStep {
+        reply_to: Sender<u8>,
+    },
+
+    SetTicksPerStep {
+        ticks_per_step: u8,
+    },
+
+    GetTrackGrooves {
+        reply_to: Sender<[GrooveId; NUM_TRACKS]>,
+    },
+
+    SetTrackGroove {
+        track_id: TrackId,
+        groove_id: GrooveId,
+    },
+
+    GetPhraseGroove {
+        phrase_id: PhraseId,
+        reply_to: Sender<Option<GrooveId>>,
+    },
+
+    SetPhraseGroove {
+        phrase_id: PhraseId,
+        groove_id: Option<GrooveId>,
+    },
+
+    /*
+     * Ticks per step and the groove to use
+     * for a phrase played on a track.
+     */
+    GetTiming {
+        track_id: TrackId,
+        phrase_id: PhraseId,
+        reply_to: Sender<Timing>,
+    },
+
+    /*
+     * Tables, which run alongside notes
+     * one row per tick.
+     */
+    GetTableData {
+        table_id: TableId,
+        reply_to: Sender<Table>,
+    },
+
+    GetTables {
+        reply_to: Sender<HashMap<TableId, Table>>,
+    },
+
+    SetTableRow {
+        table_id: TableId,
+        index: usize,
+        row: TableRow,
+    },
+
+    /*
+     * The table an instrument starts with
+     * every note, if any.
+     */
+    SetInstrumentTable {
+        instrument_id: InstrumentId,
+        table_id: Option<TableId>,
+    },
+
+    /*
+     * An instrument's patch, or None if there's
+     * no instrument with that ID.
+     */
+    GetInstrument {
+        instrument_id: InstrumentId,
+        reply_to: Sender<Option<Patch>>,
+    },
+
+    /*
+     * Replace one of an instrument's nodes. Notes
+     * already playing finish with the old one.
+     */
+    SetInstrumentNode {
+        instrument_id: InstrumentId,
+        node: NodeId,
+        def: NodeDef,
+    },
+
+    /*
+     * Replace an instrument's whole patch, as the
+     * patch editor does when nodes are wired up.
+     */
+    SetInstrumentPatch {
+        instrument_id: InstrumentId,
+        patch: Patch,
+    },
+
+    /*
+     * Stereo position of an instrument,
+     * from -1.0 (hard left) to 1.0 (hard right).
+     */
+    SetInstrumentPan {
+        instrument_id: InstrumentId,
+        pan: f32,
+    },
+
+    /*
+     * Mixer channel strip settings. These are stored
+     * in the song as well as being applied to the mixer.
+     */
+    SetMixerGain {
+        channel: MixerChannel,
+        gain_db: f32,
+    },
+
+    SetMixerPan {
+        channel: MixerChannel,
+        pan: f32,
+    },
+
+    SetMixerMute {
+        channel: MixerChannel,
+        mute: bool,
+    },
+
+    SetMixerSolo {
+        track_id: TrackId,
+        solo: bool,
+    },
+
+    GetMixerSettings {
+        reply_to: Sender<MixerSettings>,
+    },
+
+    /*
+     * Send effects. These are stored in the song
+     * as well as being applied to the mixer.
+     */
+    SetDelaySettings {
+        settings: DelaySettings,
+    },
+
+    SetReverbSettings {
+        settings: ReverbSettings,
+    },
+
+    SetMixerSend {
+        track_id: TrackId,
+        bus: SendBus,
+        amount: f32,
+    },
+
+    GetSendEffects {
+        reply_to: Sender<SendEffects>,
+    },
+
+    /*
+     * Level, in dBFS, that the master bus
+     * limiter won't let peaks go above.
+     */
+    SetLimiterCeiling {
+        ceiling_db: f32,
+    },
+
+    /*
+     * Get a handle to the mixer's peak and gain reduction
+     * meters. The audio thread keeps them up to date;
+     * read them as often as needed without sending more
+     * actions.
+     */
+    GetMixerMeters {
+        reply_to: Sender<Arc<MixerMeters>>,
+    },
+
+    /*
+     * Get a handle to where playback has got to,
+     * which the sequencer keeps up to date in the
+     * same way.
+     */
+    GetPlayhead {
+        reply_to: Sender<Arc<Playhead>>,
+    },
 }
 
 pub struct UpdateEngine {
     rx: Receiver<Action>,
     song: Arc<Mutex<Song>>,
+    errors_tx: Sender<SongError>,
+    errors_rx: Receiver<SongError>,
 }
 
 impl UpdateEngine {
+    // Rejected edits waiting for the UI, past which
+    // more are dropped
+    const MAX_ERRORS: usize = 16;
+
     pub fn new(rx: Receiver<Action>, song: Arc<Mutex<Song>>) -> Self {
-        Self { rx, song }
+        let (errors_tx, errors_rx) = bounded(Self::MAX_ERRORS);
+        Self {
+            rx,
+            song,
+            errors_tx,
+            errors_rx,
+        }
     }
 
     pub fn run(&self) {
         let rx = self.rx.clone();
         let song = self.song.clone();
+        let errors_tx = self.errors_tx.clone();
+        let errors_rx = self.errors_rx.clone();
+        // Edits that don't fit the song are dropped, and
+        // the UI told why. If it's fallen behind, it
+        // has enough to be going on with.
+        let report = move |result: Result<(), SongError>| {
+            if let Err(err) = result {
+                let _ = errors_tx.try_send(err);
+            }
+        };
 
         thread::spawn(move || {
+            // Cells copied or cut, row by row
+            let mut clipboard: Vec<Vec<Cell>> = vec![];
+            // How the views write notes. It's the user's
+            // choice rather than the song's, so it stays
+            // put when another song is set.
+            let mut notation = Notation::default();
+
             while let Ok(action) = rx.recv() {
                 let mut song_guard = song.lock();
                 match action {
+                    Action::GetSongDimensions { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_dimensions());
+                    }
+                    Action::SetSongDimensions { dimensions } => {
+                        report(song_guard.set_dimensions(dimensions));
+                    }
+                    Action::GetEditErrors { reply_to } => {
+                        let _ = reply_to.send(errors_rx.clone());
+                    }
+                    Action::GetNotation { reply_to } => {
+                        let _ = reply_to.send(notation);
+                    }
+                    Action::SetNotation {
+                        notation: new_notation,
+                    } => {
+                        notation = new_notation;
+                    }
                     Action::GetPatternData { reply_to } => {
                         let _ = reply_to.send(song_guard.get_pattern_data());
                     }
@@ -244,7 +1606,7 @@ impl UpdateEngine {
                         track_id,
                         chain_id,
                     } => {
-                        song_guard.update_pattern(pattern_id, track_id, chain_id);
+                        report(song_guard.update_pattern(pattern_id, track_id, chain_id));
                     }
                     Action::GetChainData { chain_id, reply_to } => {
                         let _ = reply_to.send(song_guard.get_chain_data(chain_id));
@@ -254,7 +1616,62 @@ impl UpdateEngine {
                         index,
                         phrase_id,
                     } => {
-                        song_guard.set_chain_phrase(chain_id, index, phrase_id);
+                        report(song_guard.set_chain_phrase(chain_id, index, phrase_id));
+                    }
+                    Action::SetChainTranspose {
+                        chain_id,
+                        index,
+                        transpose,
+                    } => {
+                        report(song_guard.set_chain_transpose(chain_id, index, transpose));
+                    }
+                    Action::CopyBlock { block } => match song_guard.copy_block(block) {
+                        Ok(cells) => clipboard = cells,
+                        Err(err) => report(Err(err)),
+                    },
+                    Action::CutBlock { block } => match song_guard.cut_block(block) {
+                        Ok(cells) => clipboard = cells,
+                        Err(err) => report(Err(err)),
+                    },
+                    Action::PasteBlock {
+                        grid,
+                        row,
+                        col,
+                        insert,
+                    } if !clipboard.is_empty() => {
+                        report(song_guard.paste_block(grid, row, col, &clipboard, insert));
+                    }
+                    Action::ClearBlock { block } => {
+                        report(song_guard.clear_block(block));
+                    }
+                    Action::Undo => {
+                        song_guard.undo();
+                    }
+                    Action::GetUsage { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_usage());
+                    }
+                    Action::CheckSong { reply_to } => {
+                        let _ = reply_to.send(song_guard.check());
+                    }
+                    Action::SetSong { song, reply_to } => {
+                        *song_guard = *song;
+                        let _ = reply_to.send(song_guard.check());
+                    }
+                    Action::Cleanup { renumber, reply_to } => {
+                        let _ = reply_to.send(song_guard.cleanup(renumber));
+                    }
+                    Action::CloneChain {
+                        chain_id,
+                        deep,
+                        reply_to,
+                    } => {
+                        let _ = reply_to.send(song_guard.clone_chain(chain_id, deep));
+                    }
+                    Action::ClonePhrase {
+                        phrase_id,
+                        reply_to,
+                    } => {
+                        let _ = reply_to.send(song_guard.clone_phrase(phrase_id));
                     }
                     Action::GetPhraseData {
                         phrase_id,
@@ -267,7 +1684,107 @@ impl UpdateEngine {
                         index,
                         step,
                     } => {
-                        song_guard.set_phrase_step(phrase_id, index, step);
+                        report(song_guard.set_phrase_step(phrase_id, index, step));
+                    }
+                    Action::GetPhraseLength {
+                        phrase_id,
+                        reply_to,
+                    } => {
+                        let _ = reply_to.send(song_guard.get_phrase_length(phrase_id));
+                    }
+                    Action::SetPhraseLength { phrase_id, length } => {
+                        report(song_guard.set_phrase_length(phrase_id, length));
+                    }
+                    Action::GetGroove {
+                        groove_id,
+                        reply_to,
+                    } => {
+                        let _ = reply_to.send(song_guard.get_groove(groove_id));
+                    }
+                    Action::SetGrooveStep {
+                        groove_id,
+                        index,
+                        ticks,
+                    } => {
+                        report(song_guard.set_groove_step(groove_id, index, ticks));
+                    }
+                    Action::GetTicksPerStep { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_ticks_per_step());
+                    }
+                    Action::SetTicksPerStep { ticks_per_step } => {
+                        song_guard.set_ticks_per_step(ticks_per_step);
+                    }
+                    Action::GetTrackGrooves { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_track_grooves());
+                    }
+                    Action::SetTrackGroove {
+                        track_id,
+                        groove_id,
+                    } => {
+                        report(song_guard.set_track_groove(track_id, groove_id));
+                    }
+                    Action::GetPhraseGroove {
+                        phrase_id,
+                        reply_to,
+                    } => {
+                        let _ = reply_to.send(song_guard.get_phrase_groove(phrase_id));
+                    }
+                    Action::SetPhraseGroove {
+                        phrase_id,
+                        groove_id,
+                    } => {
+                        song_guard.set_phrase_groove(phrase_id, groove_id);
+                    }
+                    Action::GetTiming {
+                        track_id,
+                        phrase_id,
+                        reply_to,
+                    } => {
+                        let _ = reply_to.send(song_guard.get_timing(track_id, phrase_id));
+                    }
+                    Action::GetTableData { table_id, reply_to } => {
+                        let _ = reply_to.send(song_guard.get_table(table_id));
+                    }
+                    Action::GetTables { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_tables());
+                    }
+                    Action::SetTableRow {
+                        table_id,
+                        index,
+                        row,
+                    } => {
+                        report(song_guard.set_table_row(table_id, index, row));
+                    }
+                    Action::SetDelaySettings { settings } => {
+                        song_guard.set_delay(settings);
+                    }
+                    Action::SetReverbSettings { settings } => {
+                        song_guard.set_reverb(settings);
+                    }
+                    Action::SetMixerSend {
+                        track_id,
+                        bus,
+                        amount,
+                    } => {
+                        song_guard.set_send(track_id, bus, amount);
+                    }
+                    Action::GetSendEffects { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_send_effects());
+                    }
+                    Action::SetMixerGain { channel, gain_db } => {
+                        song_guard.set_mixer_gain(channel, gain_db);
+                    }
+                    Action::SetMixerPan { channel, pan } => {
+                        song_guard.set_mixer_pan(channel, pan);
+                    }
+                    Action::SetMixerMute { channel, mute } => {
+                        song_guard.set_mixer_mute(channel, mute);
+                    }
+                    Action::SetMixerSolo { track_id, solo } => {
+                        song_guard.set_mixer_solo(track_id, solo);
+                    }
+                    Action::GetMixerSettings { reply_to } => {
+                        let _ = reply_to.send(song_guard.get_mixer_settings());
                     }
                     _ => {}
                 }
@@ -0,0 +1,119 @@
+use super::{Song, SongError};
+use crate::types::{Block, Cell, Grid};
+
+/*
+ * Copying, cutting, pasting and clearing blocks of
+ * cells. Blocks hanging off the edge of their grid
+ * are cut down to fit, as are pastes.
+ *
+ * Each edit is saved as a single step for undo, so
+ * however many cells it touches, one undo puts them
+ * all back.
+ */
+
+impl Song {
+    // The cells in a block, row by row
+    pub fn copy_block(&self, block: Block) -> Result<Vec<Vec<Cell>>, SongError> {
+        let (rows, cols) = self.block_range(block)?;
+
+        Ok(rows
+            .map(|row| {
+                cols.clone()
+                    .map(|col| self.get_cell(block.grid, row, col))
+                    .collect()
+            })
+            .collect())
+    }
+
+    pub fn cut_block(&mut self, block: Block) -> Result<Vec<Vec<Cell>>, SongError> {
+        let cells = self.copy_block(block)?;
+        self.save_snapshot(block.grid);
+        self.clear_cells(block)?;
+        Ok(cells)
+    }
+
+    pub fn clear_block(&mut self, block: Block) -> Result<(), SongError> {
+        self.block_range(block)?;
+        self.save_snapshot(block.grid);
+        self.clear_cells(block)
+    }
+
+    // Paste cells with their top left at a row and column.
+    // Inserting moves the rows below down out of the way
+    // first, in the columns being pasted into.
+    pub fn paste_block(
+        &mut self,
+        grid: Grid,
+        row: usize,
+        col: usize,
+        cells: &[Vec<Cell>],
+        insert: bool,
+    ) -> Result<(), SongError> {
+        let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+        let block = Block {
+            grid,
+            row,
+            col,
+            rows: cells.len(),
+            cols: width,
+        };
+        let (rows, cols) = self.block_range(block)?;
+        self.save_snapshot(grid);
+
+        if insert {
+            let (num_rows, _) = self.grid_size(grid);
+            for to in (rows.end..num_rows).rev() {
+                let from = to - cells.len();
+                for col in cols.clone() {
+                    let cell = self.get_cell(grid, from, col);
+                    self.set_cell(grid, to, col, cell)?;
+                }
+            }
+        }
+
+        for (row, line) in rows.zip(cells) {
+            for (col, cell) in cols.clone().zip(line) {
+                self.set_cell(grid, row, col, *cell)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_cells(&mut self, block: Block) -> Result<(), SongError> {
+        let (rows, cols) = self.block_range(block)?;
+        for row in rows {
+            for col in cols.clone() {
+                let cell = self.get_cell(block.grid, row, col).cleared();
+                self.set_cell(block.grid, row, col, cell)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The rows and columns of a block that are in its grid
+    fn block_range(
+        &self,
+        block: Block,
+    ) -> Result<(std::ops::Range<usize>, std::ops::Range<usize>), SongError> {
+        let (num_rows, num_cols) = self.grid_size(block.grid);
+        if block.row >= num_rows {
+            return Err(SongError::OutOfRange {
+                what: "block row",
+                index: block.row,
+                len: num_rows,
+            });
+        }
+        if block.col >= num_cols {
+            return Err(SongError::OutOfRange {
+                what: "block column",
+                index: block.col,
+                len: num_cols,
+            });
+        }
+
+        Ok((
+            block.row..(block.row + block.rows).min(num_rows),
+            block.col..(block.col + block.cols).min(num_cols),
+        ))
+    }
+}
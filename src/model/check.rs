@@ -0,0 +1,177 @@
+use super::Song;
+use crate::types::{
+    ChainId, ChainRow, Diagnostic, FxKind, Grid, MAX_NOTE, MAX_VELOCITY, PhraseId, Problem,
+};
+
+/*
+ * Checking a song for references to chains, phrases
+ * and tables that don't exist, values out of range,
+ * transposes that push notes out of range, and gaps
+ * in the song. Nothing here stops the song
+ * playing: a missing chain or phrase plays as empty.
+ * Only what the views show is checked, so anything
+ * kept past the song's dimensions is left alone.
+ */
+
+impl Song {
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        self.check_patterns(&mut diagnostics);
+
+        let mut chain_ids: Vec<_> = self.chains.keys().copied().collect();
+        chain_ids.sort_unstable();
+        for chain_id in chain_ids {
+            for (row, chain_row) in self.get_chain_data(chain_id).iter().enumerate() {
+                match chain_row.phrase {
+                    Some(phrase_id) if !self.phrases.contains_key(&phrase_id) => {
+                        diagnostics.push(Diagnostic {
+                            grid: Grid::Chain(chain_id),
+                            row,
+                            col: Some(0),
+                            problem: Problem::MissingPhrase(phrase_id),
+                        })
+                    }
+                    Some(phrase_id) => {
+                        self.check_transpose(chain_id, row, phrase_id, *chain_row, &mut diagnostics)
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        let mut phrase_ids: Vec<_> = self.phrases.keys().copied().collect();
+        phrase_ids.sort_unstable();
+        for phrase_id in phrase_ids {
+            self.check_phrase(phrase_id, &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    // Missing chains, and empty rows before the last one used
+    fn check_patterns(&self, diagnostics: &mut Vec<Diagnostic>) {
+        let patterns = self.get_pattern_data();
+        let last_used = patterns
+            .iter()
+            .rposition(|chains| chains.iter().any(Option::is_some));
+
+        for (row, chains) in patterns.iter().enumerate() {
+            if last_used.is_some_and(|last| row < last) && chains.iter().all(Option::is_none) {
+                diagnostics.push(Diagnostic {
+                    grid: Grid::Song,
+                    row,
+                    col: None,
+                    problem: Problem::EmptyPattern,
+                });
+            }
+
+            for (col, chain_id) in chains.iter().enumerate() {
+                if let Some(chain_id) = chain_id
+                    && !self.chains.contains_key(chain_id)
+                {
+                    diagnostics.push(Diagnostic {
+                        grid: Grid::Song,
+                        row,
+                        col: Some(col),
+                        problem: Problem::MissingChain(*chain_id),
+                    });
+                }
+            }
+        }
+    }
+
+    // A transpose that takes any of the phrase's notes
+    // below 0 or past the highest note, where the
+    // sequencer would clamp them
+    fn check_transpose(
+        &self,
+        chain_id: ChainId,
+        row: usize,
+        phrase_id: PhraseId,
+        chain_row: ChainRow,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let semitones = chain_row.semitones() as i16;
+        if semitones == 0 {
+            return;
+        }
+
+        let length = self.get_phrase_length(phrase_id);
+        let out_of_range = self.get_phrase_data(phrase_id)[..length]
+            .iter()
+            .filter_map(|step| step.and_then(|step| step.note()))
+            .any(|note| !(0..=MAX_NOTE as i16).contains(&(note as i16 + semitones)));
+
+        if out_of_range {
+            diagnostics.push(Diagnostic {
+                grid: Grid::Chain(chain_id),
+                row,
+                col: Some(1),
+                problem: Problem::TransposeOutOfRange(chain_row.semitones()),
+            });
+        }
+    }
+
+    fn check_phrase(&self, phrase_id: PhraseId, diagnostics: &mut Vec<Diagnostic>) {
+        let grid = Grid::Phrase(phrase_id);
+        let length = self.get_phrase_length(phrase_id);
+        let tables = self.get_tables();
+
+        for (row, step) in self.get_phrase_data(phrase_id).iter().enumerate() {
+            let Some(step) = step else {
+                continue;
+            };
+
+            let mut report = |col, problem| {
+                diagnostics.push(Diagnostic {
+                    grid,
+                    row,
+                    col: Some(col),
+                    problem,
+                })
+            };
+
+            if let Some(note) = step.note()
+                && note > MAX_NOTE
+            {
+                report(
+                    Grid::NOTE_COLUMN,
+                    Problem::OutOfRange {
+                        what: "note",
+                        value: note as usize,
+                        max: MAX_NOTE as usize,
+                    },
+                );
+            }
+            if let Some(velocity) = step.velocity
+                && velocity > MAX_VELOCITY
+            {
+                report(
+                    Grid::VELOCITY_COLUMN,
+                    Problem::OutOfRange {
+                        what: "velocity",
+                        value: velocity as usize,
+                        max: MAX_VELOCITY as usize,
+                    },
+                );
+            }
+
+            for (i, fx) in step.fx.iter().enumerate() {
+                let col = Grid::FIRST_FX_COLUMN + i;
+                match fx {
+                    Some(fx) if fx.kind == FxKind::Hop && fx.value as usize >= length => report(
+                        col,
+                        Problem::OutOfRange {
+                            what: "hop",
+                            value: fx.value as usize,
+                            max: length - 1,
+                        },
+                    ),
+                    Some(fx) if fx.kind == FxKind::Table && !tables.contains_key(&fx.value) => {
+                        report(col, Problem::MissingTable(fx.value))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
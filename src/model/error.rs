@@ -0,0 +1,45 @@
+use crate::types::SongDimensions;
+use std::fmt;
+
+/*
+ * Why the song turned down an edit.
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SongError {
+    // An index past the end of a pattern, chain, phrase, etc.
+    OutOfRange {
+        what: &'static str,
+        index: usize,
+        len: usize,
+    },
+    // Dimensions outside the limits in types
+    InvalidDimensions(SongDimensions),
+    // A length that isn't between 1 and max
+    InvalidLength {
+        what: &'static str,
+        length: usize,
+        max: usize,
+    },
+    // Every chain or phrase ID is taken
+    NoFreeId(&'static str),
+}
+
+impl fmt::Display for SongError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SongError::OutOfRange { what, index, len } => {
+                write!(f, "{what} index {index} out of range, there are {len}")
+            }
+            SongError::InvalidDimensions(dimensions) => {
+                write!(f, "invalid song dimensions: {dimensions:?}")
+            }
+            SongError::InvalidLength { what, length, max } => {
+                write!(f, "{what} length {length} must be from 1 to {max}")
+            }
+            SongError::NoFreeId(what) => write!(f, "no {what} IDs left"),
+        }
+    }
+}
+
+impl std::error::Error for SongError {}
@@ -1,3 +1,7 @@
+mod block;
+mod check;
+mod error;
 pub mod structures;
 
+pub use error::SongError;
 pub use structures::Song;
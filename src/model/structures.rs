@@ -1,8 +1,11 @@
+use super::SongError;
 use crate::types::{
-    ChainId, NUM_PHRASES_PER_CHAIN, NUM_STEPS_PER_PHRASE, NUM_TRACKS, PatternId, PhraseId, Step,
-    TrackId,
+    Cell, ChainId, ChainRow, CleanupReport, DEFAULT_TICKS_PER_STEP, DelaySettings, Grid, Groove,
+    GrooveId, MixerChannel, MixerSettings, NUM_GROOVE_STEPS, NUM_PATTERNS, NUM_TABLE_ROWS,
+    NUM_TRACKS, PatternId, PhraseId, ReverbSettings, SendBus, SendEffects, SongDimensions, Step,
+    Table, TableId, TableRow, Timing, TrackId, Usage,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 /*
  * A pattern is a single row in the song view.
@@ -10,70 +13,192 @@ use std::collections::HashMap;
  * can either be empty, or can have a chain ID.
  */
 
+#[derive(Clone)]
 pub struct Pattern {
     tracks: [Option<ChainId>; NUM_TRACKS],
 }
 
 /*
- * A chain refers to a flexibly-sized list
- * of phrases, up to perhaps 16.
+ * A chain refers to a list of phrases, each
+ * with a transpose. How many rows it has is
+ * set by the song's dimensions.
+ *
+ * Chains and phrases only store as far as
+ * they've been written to, and keep anything
+ * past the end if the song's dimensions shrink,
+ * so it comes back if they grow again.
  */
 
+#[derive(Clone)]
 pub struct Chain {
-    phrases: [Option<PhraseId>; NUM_PHRASES_PER_CHAIN],
+    phrases: Vec<Option<PhraseId>>,
+    transposes: Vec<u8>,
 }
 
 impl Chain {
     pub fn new() -> Self {
         Self {
-            phrases: [None; NUM_PHRASES_PER_CHAIN],
+            phrases: vec![],
+            transposes: vec![],
         }
     }
+
+    fn rows(&self, len: usize) -> Vec<ChainRow> {
+        (0..len)
+            .map(|i| ChainRow {
+                phrase: self.phrases.get(i).copied().flatten(),
+                transpose: self.transposes.get(i).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
 }
 
 /*
- * A phrase contains 16 steps, which might
- * represent a bar, 4 bars, a quarter bar, etc.
+ * A phrase contains a number of steps, 16
+ * by default, which might represent a bar,
+ * 4 bars, a quarter bar, etc.
+ * It plays with its track's groove unless it
+ * has one of its own.
+ *
+ * A phrase can be shorter than the song's
+ * steps per phrase, to play in 3/4 or 7/8
+ * against the other tracks. Steps past its
+ * length are kept but don't play.
  */
 
+#[derive(Clone)]
 pub struct Phrase {
-    steps: [Option<Step>; NUM_STEPS_PER_PHRASE],
+    steps: Vec<Option<Step>>,
+    groove: Option<GrooveId>,
+    // None for the song's full steps per phrase
+    length: Option<usize>,
+}
+
+/*
+ * What a grid looked like before a block edit,
+ * to put back on undo.
+ */
+
+#[derive(Clone)]
+enum Snapshot {
+    Patterns(Vec<Pattern>),
+    Chain(ChainId, Option<Chain>),
+    Phrase(PhraseId, Option<Phrase>),
 }
 
 /*
  * Song stores all necessary
- * patterns, chains and phrases.
+ * patterns, chains and phrases,
+ * along with the grooves, tables
+ * and the send effect settings.
  * The number of patterns is flexible.
+ *
+ * Edits are checked against the song's
+ * dimensions, and turned down with a
+ * SongError if they don't fit.
  */
 
+#[derive(Clone)]
 pub struct Song {
+    dimensions: SongDimensions,
     patterns: Vec<Pattern>,
     pub chains: HashMap<ChainId, Chain>,
     pub phrases: HashMap<PhraseId, Phrase>,
+    grooves: HashMap<GrooveId, Groove>,
+    ticks_per_step: u8,
+    track_grooves: [GrooveId; NUM_TRACKS],
+    tables: HashMap<TableId, Table>,
+    send_effects: SendEffects,
+    mixer: MixerSettings,
+    // Block edits, most recent last
+    history: Vec<Snapshot>,
 }
 
 impl Phrase {
     fn new() -> Self {
         Self {
-            steps: [None; NUM_STEPS_PER_PHRASE],
+            steps: vec![],
+            groove: None,
+            length: None,
         }
     }
+
+    fn steps(&self, len: usize) -> Vec<Option<Step>> {
+        (0..len)
+            .map(|i| self.steps.get(i).copied().flatten())
+            .collect()
+    }
+}
+
+// Fail with an OutOfRange error unless index < len
+fn check_index(what: &'static str, index: usize, len: usize) -> Result<(), SongError> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(SongError::OutOfRange { what, index, len })
+    }
+}
+
+// IDs in ascending order
+fn sorted<'a>(ids: impl Iterator<Item = &'a u8>) -> Vec<u8> {
+    let mut ids: Vec<u8> = ids.copied().collect();
+    ids.sort_unstable();
+    ids
+}
+
+// The IDs that renumbering changed, in order
+fn moved(ids: HashMap<u8, u8>) -> Vec<(u8, u8)> {
+    let mut moved: Vec<(u8, u8)> = ids.into_iter().filter(|(old, new)| old != new).collect();
+    moved.sort_unstable();
+    moved
+}
+
+// Set an item in a list, growing it to fit
+fn set_growing<T: Clone + Default>(items: &mut Vec<T>, index: usize, value: T) {
+    if items.len() <= index {
+        items.resize(index + 1, T::default());
+    }
+    items[index] = value;
 }
 
 impl Song {
+    // Block edits that can be undone
+    const MAX_UNDO: usize = 64;
+
     pub fn new() -> Self {
         Self {
+            dimensions: SongDimensions::default(),
             patterns: vec![],
             chains: HashMap::new(),
             phrases: HashMap::new(),
+            grooves: HashMap::new(),
+            ticks_per_step: DEFAULT_TICKS_PER_STEP,
+            track_grooves: [0; NUM_TRACKS],
+            tables: HashMap::new(),
+            send_effects: SendEffects::default(),
+            mixer: MixerSettings::default(),
+            history: vec![],
         }
     }
 
+    pub fn get_dimensions(&self) -> SongDimensions {
+        self.dimensions
+    }
+
+    pub fn set_dimensions(&mut self, dimensions: SongDimensions) -> Result<(), SongError> {
+        if !dimensions.is_valid() {
+            return Err(SongError::InvalidDimensions(dimensions));
+        }
+
+        self.dimensions = dimensions;
+        Ok(())
+    }
+
     // Get data for all patterns.
     pub fn get_pattern_data(&self) -> Vec<Vec<Option<ChainId>>> {
         self.patterns
             .iter()
-            .map(|pattern| pattern.tracks.to_vec())
+            .map(|pattern| pattern.tracks[..self.dimensions.tracks].to_vec())
             .collect()
     }
 
@@ -82,8 +207,8 @@ impl Song {
         let index = pattern_id as usize;
         self.patterns
             .get(index)
-            .map(|pattern| pattern.tracks.to_vec())
-            .unwrap_or_else(|| vec![None; NUM_TRACKS])
+            .map(|pattern| pattern.tracks[..self.dimensions.tracks].to_vec())
+            .unwrap_or_else(|| vec![None; self.dimensions.tracks])
     }
 
     pub fn update_pattern(
@@ -91,9 +216,17 @@ impl Song {
         pattern_id: PatternId,
         track_id: TrackId,
         chain_id: Option<ChainId>,
-    ) {
+    ) -> Result<(), SongError> {
         let pattern_index = pattern_id as usize;
         let track_index = track_id as usize;
+        check_index("track", track_index, self.dimensions.tracks)?;
+        check_index("pattern", pattern_index, NUM_PATTERNS)?;
+
+        // Clearing a pattern that isn't there already
+        // leaves nothing to do
+        if chain_id.is_none() && pattern_index >= self.patterns.len() {
+            return Ok(());
+        }
 
         // Ensure the pattern exists
         while self.patterns.len() <= pattern_index {
@@ -104,14 +237,26 @@ impl Song {
 
         // Update the pattern's track with the new chain ID
         self.patterns[pattern_index].tracks[track_index] = chain_id;
+
+        // Empty patterns at the end aren't kept, so the
+        // song ends after the last one with a chain in
+        while self
+            .patterns
+            .last()
+            .is_some_and(|pattern| pattern.tracks.iter().all(Option::is_none))
+        {
+            self.patterns.pop();
+        }
+        Ok(())
     }
 
     // Get data for a particular chain
-    pub fn get_chain_data(&self, chain_id: ChainId) -> Vec<Option<PhraseId>> {
+    pub fn get_chain_data(&self, chain_id: ChainId) -> Vec<ChainRow> {
+        let len = self.dimensions.phrases_per_chain;
         self.chains
             .get(&chain_id)
-            .map(|chain| chain.phrases.iter().cloned().collect())
-            .unwrap_or_else(|| Chain::new().phrases.to_vec())
+            .map(|chain| chain.rows(len))
+            .unwrap_or_else(|| Chain::new().rows(len))
     }
 
     // Set a phrase in a chain
@@ -120,39 +265,517 @@ impl Song {
         chain_id: ChainId,
         index: usize,
         phrase_id: Option<PhraseId>,
-    ) {
-        let chain = self.chains.entry(chain_id).or_insert_with(|| Chain::new());
+    ) -> Result<(), SongError> {
+        check_index("chain row", index, self.dimensions.phrases_per_chain)?;
 
-        if index >= NUM_PHRASES_PER_CHAIN {
-            panic!(
-                "Phrase phrase index out of bounds: index = {index}, chain has {NUM_PHRASES_PER_CHAIN} steps"
-            );
+        let chain = self.chains.entry(chain_id).or_insert_with(Chain::new);
+        set_growing(&mut chain.phrases, index, phrase_id);
+        Ok(())
+    }
+
+    // Set the transpose of a row in a chain
+    pub fn set_chain_transpose(
+        &mut self,
+        chain_id: ChainId,
+        index: usize,
+        transpose: u8,
+    ) -> Result<(), SongError> {
+        check_index("chain row", index, self.dimensions.phrases_per_chain)?;
+
+        let chain = self.chains.entry(chain_id).or_insert_with(Chain::new);
+        set_growing(&mut chain.transposes, index, transpose);
+        Ok(())
+    }
+
+    // Which chains and phrases are used, and how often
+    pub fn get_usage(&self) -> Usage {
+        let mut usage = Usage {
+            pattern_chains: self
+                .patterns
+                .iter()
+                .map(|pattern| pattern.tracks.iter().flatten().copied().collect())
+                .collect(),
+            ..Default::default()
+        };
+
+        for chain_id in self.patterns.iter().flat_map(|p| p.tracks.iter().flatten()) {
+            *usage.chain_references.entry(*chain_id).or_default() += 1;
+        }
+        for phrase_id in self
+            .chains
+            .values()
+            .flat_map(|c| c.phrases.iter().flatten())
+        {
+            *usage.phrase_references.entry(*phrase_id).or_default() += 1;
         }
 
-        chain.phrases[index] = phrase_id;
+        let (used_chains, used_phrases) = self.used_ids();
+        usage.orphaned_chains = sorted(self.chains.keys().filter(|id| !used_chains.contains(id)));
+        usage.orphaned_phrases =
+            sorted(self.phrases.keys().filter(|id| !used_phrases.contains(id)));
+        usage
+    }
+
+    // Chains in patterns, and the phrases in those chains
+    fn used_ids(&self) -> (BTreeSet<ChainId>, BTreeSet<PhraseId>) {
+        let chains: BTreeSet<ChainId> = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.tracks.iter().flatten().copied())
+            .collect();
+        let phrases = chains
+            .iter()
+            .filter_map(|chain_id| self.chains.get(chain_id))
+            .flat_map(|chain| chain.phrases.iter().flatten().copied())
+            .collect();
+        (chains, phrases)
+    }
+
+    // Delete the chains and phrases the song doesn't use.
+    // Renumbering then moves what's left down to 0, 1, 2...
+    // in order, rewriting the patterns and chains to match.
+    // Undo can't go back past a cleanup.
+    pub fn cleanup(&mut self, renumber: bool) -> CleanupReport {
+        let (used_chains, used_phrases) = self.used_ids();
+        let mut report = CleanupReport {
+            removed_chains: sorted(self.chains.keys().filter(|id| !used_chains.contains(id))),
+            removed_phrases: sorted(self.phrases.keys().filter(|id| !used_phrases.contains(id))),
+            ..Default::default()
+        };
+
+        for chain_id in &report.removed_chains {
+            self.chains.remove(chain_id);
+        }
+        for phrase_id in &report.removed_phrases {
+            self.phrases.remove(phrase_id);
+        }
+        self.history.clear();
+
+        if !renumber {
+            return report;
+        }
+
+        let chain_ids: HashMap<ChainId, ChainId> = used_chains.into_iter().zip(0..).collect();
+        let phrase_ids: HashMap<PhraseId, PhraseId> = used_phrases.into_iter().zip(0..).collect();
+
+        for slot in self
+            .patterns
+            .iter_mut()
+            .flat_map(|pattern| pattern.tracks.iter_mut().flatten())
+        {
+            *slot = chain_ids[slot];
+        }
+        for slot in self
+            .chains
+            .values_mut()
+            .flat_map(|chain| chain.phrases.iter_mut().flatten())
+        {
+            *slot = phrase_ids[slot];
+        }
+        self.chains = self
+            .chains
+            .drain()
+            .map(|(chain_id, chain)| (chain_ids[&chain_id], chain))
+            .collect();
+        self.phrases = self
+            .phrases
+            .drain()
+            .map(|(phrase_id, phrase)| (phrase_ids[&phrase_id], phrase))
+            .collect();
+
+        report.renumbered_chains = moved(chain_ids);
+        report.renumbered_phrases = moved(phrase_ids);
+        report
+    }
+
+    // The lowest chain ID that isn't written to
+    // or used in a pattern
+    fn free_chain_id(&self) -> Option<ChainId> {
+        (0..=ChainId::MAX).find(|chain_id| {
+            !self.chains.contains_key(chain_id)
+                && !self
+                    .patterns
+                    .iter()
+                    .any(|pattern| pattern.tracks.contains(&Some(*chain_id)))
+        })
+    }
+
+    // The lowest phrase IDs that aren't written to
+    // or used in a chain
+    fn free_phrase_ids(&self) -> impl Iterator<Item = PhraseId> + '_ {
+        (0..=PhraseId::MAX).filter(|phrase_id| {
+            !self.phrases.contains_key(phrase_id)
+                && !self
+                    .chains
+                    .values()
+                    .any(|chain| chain.phrases.contains(&Some(*phrase_id)))
+        })
+    }
+
+    // Copy a chain to the lowest free ID. A deep clone
+    // gives the copy its own copies of the phrases too.
+    pub fn clone_chain(&mut self, chain_id: ChainId, deep: bool) -> Result<ChainId, SongError> {
+        let new_id = self.free_chain_id().ok_or(SongError::NoFreeId("chain"))?;
+        let mut chain = self
+            .chains
+            .get(&chain_id)
+            .cloned()
+            .unwrap_or_else(Chain::new);
+
+        if deep {
+            let mut used: Vec<PhraseId> = chain.phrases.iter().flatten().copied().collect();
+            used.sort_unstable();
+            used.dedup();
+
+            let free: Vec<PhraseId> = self.free_phrase_ids().take(used.len()).collect();
+            if free.len() < used.len() {
+                return Err(SongError::NoFreeId("phrase"));
+            }
+
+            for (old_id, new_phrase_id) in used.into_iter().zip(free) {
+                let phrase = self
+                    .phrases
+                    .get(&old_id)
+                    .cloned()
+                    .unwrap_or_else(Phrase::new);
+                self.phrases.insert(new_phrase_id, phrase);
+                for slot in chain.phrases.iter_mut() {
+                    if *slot == Some(old_id) {
+                        *slot = Some(new_phrase_id);
+                    }
+                }
+            }
+        }
+
+        self.chains.insert(new_id, chain);
+        Ok(new_id)
+    }
+
+    // Copy a phrase to the lowest free ID
+    pub fn clone_phrase(&mut self, phrase_id: PhraseId) -> Result<PhraseId, SongError> {
+        let new_id = self
+            .free_phrase_ids()
+            .next()
+            .ok_or(SongError::NoFreeId("phrase"))?;
+        let phrase = self
+            .phrases
+            .get(&phrase_id)
+            .cloned()
+            .unwrap_or_else(Phrase::new);
+
+        self.phrases.insert(new_id, phrase);
+        Ok(new_id)
     }
 
     // Get data for a particular phrase
     pub fn get_phrase_data(&self, phrase_id: PhraseId) -> Vec<Option<Step>> {
+        let len = self.dimensions.steps_per_phrase;
         self.phrases
             .get(&phrase_id)
-            .map(|phrase| phrase.steps.iter().cloned().collect())
-            .unwrap_or_else(|| Phrase::new().steps.to_vec())
+            .map(|phrase| phrase.steps(len))
+            .unwrap_or_else(|| vec![None; len])
     }
 
     // Update a phrase or add a new phrase
-    pub fn set_phrase_step(&mut self, phrase_id: PhraseId, index: usize, step: Option<Step>) {
-        let phrase = self
-            .phrases
-            .entry(phrase_id)
-            .or_insert_with(|| Phrase::new());
+    pub fn set_phrase_step(
+        &mut self,
+        phrase_id: PhraseId,
+        index: usize,
+        step: Option<Step>,
+    ) -> Result<(), SongError> {
+        check_index("phrase step", index, self.dimensions.steps_per_phrase)?;
+
+        let phrase = self.phrases.entry(phrase_id).or_insert_with(Phrase::new);
+        set_growing(&mut phrase.steps, index, step);
+        Ok(())
+    }
+
+    // How many steps of a phrase play
+    pub fn get_phrase_length(&self, phrase_id: PhraseId) -> usize {
+        let max = self.dimensions.steps_per_phrase;
+        self.phrases
+            .get(&phrase_id)
+            .and_then(|phrase| phrase.length)
+            .map_or(max, |length| length.min(max))
+    }
 
-        if index >= NUM_STEPS_PER_PHRASE {
-            panic!(
-                "Phrase step index out of bounds: index = {index}, phrase has {NUM_STEPS_PER_PHRASE} steps"
-            );
+    pub fn set_phrase_length(
+        &mut self,
+        phrase_id: PhraseId,
+        length: usize,
+    ) -> Result<(), SongError> {
+        let max = self.dimensions.steps_per_phrase;
+        if !(1..=max).contains(&length) {
+            return Err(SongError::InvalidLength {
+                what: "phrase",
+                length,
+                max,
+            });
         }
 
-        phrase.steps[index] = step;
+        let phrase = self.phrases.entry(phrase_id).or_insert_with(Phrase::new);
+        phrase.length = (length < max).then_some(length);
+        Ok(())
+    }
+
+    pub fn get_phrase_groove(&self, phrase_id: PhraseId) -> Option<GrooveId> {
+        self.phrases
+            .get(&phrase_id)
+            .and_then(|phrase| phrase.groove)
+    }
+
+    // Give a phrase its own groove, or None for the track's
+    pub fn set_phrase_groove(&mut self, phrase_id: PhraseId, groove_id: Option<GrooveId>) {
+        let phrase = self.phrases.entry(phrase_id).or_insert_with(Phrase::new);
+
+        phrase.groove = groove_id;
+    }
+
+    pub fn get_groove(&self, groove_id: GrooveId) -> Groove {
+        self.grooves.get(&groove_id).copied().unwrap_or_default()
+    }
+
+    // Set or clear one entry in a groove
+    pub fn set_groove_step(
+        &mut self,
+        groove_id: GrooveId,
+        index: usize,
+        ticks: Option<u8>,
+    ) -> Result<(), SongError> {
+        check_index("groove step", index, NUM_GROOVE_STEPS)?;
+
+        let groove = self.grooves.entry(groove_id).or_default();
+        groove.steps[index] = ticks;
+        Ok(())
+    }
+
+    pub fn get_ticks_per_step(&self) -> u8 {
+        self.ticks_per_step
+    }
+
+    pub fn set_ticks_per_step(&mut self, ticks_per_step: u8) {
+        self.ticks_per_step = ticks_per_step.max(1);
+    }
+
+    pub fn get_track_grooves(&self) -> [GrooveId; NUM_TRACKS] {
+        self.track_grooves
+    }
+
+    pub fn set_track_groove(
+        &mut self,
+        track_id: TrackId,
+        groove_id: GrooveId,
+    ) -> Result<(), SongError> {
+        check_index("track", track_id as usize, self.dimensions.tracks)?;
+
+        self.track_grooves[track_id as usize] = groove_id;
+        Ok(())
+    }
+
+    // How a phrase should be timed when it plays on a track
+    pub fn get_timing(&self, track_id: TrackId, phrase_id: PhraseId) -> Timing {
+        let track_groove = self
+            .track_grooves
+            .get(track_id as usize)
+            .copied()
+            .unwrap_or_default();
+        let groove_id = self.get_phrase_groove(phrase_id).unwrap_or(track_groove);
+
+        Timing {
+            ticks_per_step: self.ticks_per_step,
+            groove: self.get_groove(groove_id).ticks(),
+        }
+    }
+
+    pub fn get_table(&self, table_id: TableId) -> Table {
+        self.tables.get(&table_id).copied().unwrap_or_default()
+    }
+
+    // Every table that's been written to
+    pub fn get_tables(&self) -> HashMap<TableId, Table> {
+        self.tables.clone()
+    }
+
+    pub fn set_table_row(
+        &mut self,
+        table_id: TableId,
+        index: usize,
+        row: TableRow,
+    ) -> Result<(), SongError> {
+        check_index("table row", index, NUM_TABLE_ROWS)?;
+
+        let table = self.tables.entry(table_id).or_default();
+        table.rows[index] = row;
+        Ok(())
+    }
+
+    // How many rows and columns a grid has
+    pub(super) fn grid_size(&self, grid: Grid) -> (usize, usize) {
+        match grid {
+            Grid::Song => (NUM_PATTERNS, self.dimensions.tracks),
+            Grid::Chain(_) => (self.dimensions.phrases_per_chain, Grid::CHAIN_COLUMNS),
+            Grid::Phrase(_) => (self.dimensions.steps_per_phrase, Grid::PHRASE_COLUMNS),
+        }
+    }
+
+    pub(super) fn get_cell(&self, grid: Grid, row: usize, col: usize) -> Cell {
+        match grid {
+            Grid::Song => Cell::Chain(self.get_pattern(row as PatternId)[col]),
+            Grid::Chain(chain_id) => {
+                let chain_row = self.get_chain_data(chain_id)[row];
+                match col {
+                    0 => Cell::Phrase(chain_row.phrase),
+                    _ => Cell::Transpose(chain_row.transpose),
+                }
+            }
+            Grid::Phrase(phrase_id) => {
+                let step = self.get_phrase_data(phrase_id)[row];
+                match col {
+                    Grid::NOTE_COLUMN => Cell::Note(step.map(|s| s.kind)),
+                    Grid::INSTRUMENT_COLUMN => Cell::Instrument(step.and_then(|s| s.instrument)),
+                    Grid::VELOCITY_COLUMN => Cell::Velocity(step.and_then(|s| s.velocity)),
+                    _ => Cell::Fx(step.and_then(|s| s.fx[col - Grid::FIRST_FX_COLUMN])),
+                }
+            }
+        }
+    }
+
+    // Write a cell if it's the kind the column holds.
+    // Only the note can start a step; the other phrase
+    // columns are left alone on an empty step.
+    pub(super) fn set_cell(
+        &mut self,
+        grid: Grid,
+        row: usize,
+        col: usize,
+        cell: Cell,
+    ) -> Result<(), SongError> {
+        if !self.get_cell(grid, row, col).same_kind(&cell) {
+            return Ok(());
+        }
+
+        match (grid, cell) {
+            (Grid::Song, Cell::Chain(chain_id)) => {
+                self.update_pattern(row as PatternId, col as TrackId, chain_id)
+            }
+            (Grid::Chain(chain_id), Cell::Phrase(phrase_id)) => {
+                self.set_chain_phrase(chain_id, row, phrase_id)
+            }
+            (Grid::Chain(chain_id), Cell::Transpose(transpose)) => {
+                self.set_chain_transpose(chain_id, row, transpose)
+            }
+            (Grid::Phrase(phrase_id), cell) => {
+                let step = self.get_phrase_data(phrase_id)[row];
+                let step = match cell {
+                    Cell::Note(kind) => kind.map(|kind| Step {
+                        kind,
+                        ..step.unwrap_or(Step::of_kind(kind))
+                    }),
+                    Cell::Instrument(instrument) => step.map(|s| Step { instrument, ..s }),
+                    Cell::Velocity(velocity) => step.map(|s| Step { velocity, ..s }),
+                    Cell::Fx(fx) => step.map(|mut s| {
+                        s.fx[col - Grid::FIRST_FX_COLUMN] = fx;
+                        s
+                    }),
+                    _ => step,
+                };
+                self.set_phrase_step(phrase_id, row, step)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Remember a grid as it is, before a block edit
+    pub(super) fn save_snapshot(&mut self, grid: Grid) {
+        let snapshot = match grid {
+            Grid::Song => Snapshot::Patterns(self.patterns.clone()),
+            Grid::Chain(chain_id) => Snapshot::Chain(chain_id, self.chains.get(&chain_id).cloned()),
+            Grid::Phrase(phrase_id) => {
+                Snapshot::Phrase(phrase_id, self.phrases.get(&phrase_id).cloned())
+            }
+        };
+
+        if self.history.len() == Self::MAX_UNDO {
+            self.history.remove(0);
+        }
+        self.history.push(snapshot);
+    }
+
+    // Put back whatever the last block edit changed.
+    // Returns false if there's nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.history.pop() else {
+            return false;
+        };
+
+        match snapshot {
+            Snapshot::Patterns(patterns) => self.patterns = patterns,
+            Snapshot::Chain(chain_id, chain) => match chain {
+                Some(chain) => {
+                    self.chains.insert(chain_id, chain);
+                }
+                None => {
+                    self.chains.remove(&chain_id);
+                }
+            },
+            Snapshot::Phrase(phrase_id, phrase) => match phrase {
+                Some(phrase) => {
+                    self.phrases.insert(phrase_id, phrase);
+                }
+                None => {
+                    self.phrases.remove(&phrase_id);
+                }
+            },
+        }
+        true
+    }
+
+    // Get the delay, reverb and per-track send settings
+    pub fn get_send_effects(&self) -> SendEffects {
+        self.send_effects
+    }
+
+    pub fn set_delay(&mut self, settings: DelaySettings) {
+        self.send_effects.delay = settings;
+    }
+
+    pub fn set_reverb(&mut self, settings: ReverbSettings) {
+        self.send_effects.reverb = settings;
+    }
+
+    pub fn set_send(&mut self, track_id: TrackId, bus: SendBus, amount: f32) {
+        if let Some(sends) = self.send_effects.sends.get_mut(track_id as usize) {
+            sends.set(bus, amount);
+        }
+    }
+
+    // Get the gain, pan, mute and solo of every channel strip
+    pub fn get_mixer_settings(&self) -> MixerSettings {
+        self.mixer
+    }
+
+    pub fn set_mixer_gain(&mut self, channel: MixerChannel, gain_db: f32) {
+        if let Some(strip) = self.mixer.channel_mut(channel) {
+            strip.gain_db = gain_db;
+        }
+    }
+
+    pub fn set_mixer_pan(&mut self, channel: MixerChannel, pan: f32) {
+        if let Some(strip) = self.mixer.channel_mut(channel) {
+            strip.pan = pan.clamp(-1.0, 1.0);
+        }
+    }
+
+    pub fn set_mixer_mute(&mut self, channel: MixerChannel, mute: bool) {
+        if let Some(strip) = self.mixer.channel_mut(channel) {
+            strip.mute = mute;
+        }
+    }
+
+    // Only tracks can be soloed
+    pub fn set_mixer_solo(&mut self, track_id: TrackId, solo: bool) {
+        if let Some(strip) = self.mixer.tracks.get_mut(track_id as usize) {
+            strip.solo = solo;
+        }
     }
 }
@@ -0,0 +1,75 @@
+use super::{ChainId, Fx, InstrumentId, NUM_FX_COLUMNS, PhraseId, StepKind};
+
+/*
+ * Block edits work on a rectangle of cells in one
+ * of the editing grids: the song's patterns, a
+ * chain, or a phrase. Rows and columns are laid out
+ * as the views show them:
+ *
+ *   Song    a column per track, holding chain IDs
+ *   Chain   phrase ID, then transpose
+ *   Phrase  note, instrument, velocity, then the FX
+ *
+ * Cells remember which kind of column they came
+ * from, and a paste only writes cells into columns
+ * of the same kind, so a block can be pasted into
+ * another chain or phrase but a transpose never
+ * lands in a phrase column.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Grid {
+    Song,
+    Chain(ChainId),
+    Phrase(PhraseId),
+}
+
+impl Grid {
+    pub const CHAIN_COLUMNS: usize = 2;
+    pub const NOTE_COLUMN: usize = 0;
+    pub const INSTRUMENT_COLUMN: usize = 1;
+    pub const VELOCITY_COLUMN: usize = 2;
+    pub const FIRST_FX_COLUMN: usize = 3;
+    pub const PHRASE_COLUMNS: usize = Self::FIRST_FX_COLUMN + NUM_FX_COLUMNS;
+}
+
+// A rectangle of cells, from a top left cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Block {
+    pub grid: Grid,
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    Chain(Option<ChainId>),
+    Phrase(Option<PhraseId>),
+    Transpose(u8),
+    // Clearing the note clears the whole step
+    Note(Option<StepKind>),
+    Instrument(Option<InstrumentId>),
+    Velocity(Option<u8>),
+    Fx(Option<Fx>),
+}
+
+impl Cell {
+    // The same kind of cell, emptied
+    pub fn cleared(self) -> Cell {
+        match self {
+            Cell::Chain(_) => Cell::Chain(None),
+            Cell::Phrase(_) => Cell::Phrase(None),
+            Cell::Transpose(_) => Cell::Transpose(0),
+            Cell::Note(_) => Cell::Note(None),
+            Cell::Instrument(_) => Cell::Instrument(None),
+            Cell::Velocity(_) => Cell::Velocity(None),
+            Cell::Fx(_) => Cell::Fx(None),
+        }
+    }
+
+    pub fn same_kind(&self, other: &Cell) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
@@ -0,0 +1,44 @@
+use super::{ChainId, Grid, PhraseId, TableId};
+
+/*
+ * Something wrong with a song, found by checking it,
+ * and the cell it's in so the views can show it.
+ * A cell with no column stands for the whole row.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub grid: Grid,
+    pub row: usize,
+    pub col: Option<usize>,
+    pub problem: Problem,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Problem {
+    // A pattern has a chain that's never been written
+    MissingChain(ChainId),
+    // A chain has a phrase that's never been written
+    MissingPhrase(PhraseId),
+    // An A command starts a table that's never been written
+    MissingTable(TableId),
+    // A note, velocity or hop past what it can be
+    OutOfRange {
+        what: &'static str,
+        value: usize,
+        max: usize,
+    },
+    // A chain row's transpose takes notes in its
+    // phrase past the lowest or highest note
+    TransposeOutOfRange(i8),
+    // A pattern row with no chains before the end of
+    // the song, where song playback would stop
+    EmptyPattern,
+}
+
+impl Diagnostic {
+    // Whether this is about a cell
+    pub fn covers(&self, grid: Grid, row: usize, col: usize) -> bool {
+        self.grid == grid && self.row == row && self.col.is_none_or(|c| c == col)
+    }
+}
@@ -0,0 +1,146 @@
+use crate::types::NUM_TRACKS;
+
+/*
+ * Note lengths used to sync
+ * time-based effects to the tempo.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteDivision {
+    Whole,
+    Half,
+    DottedQuarter,
+    Quarter,
+    QuarterTriplet,
+    DottedEighth,
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    // Length in quarter-note beats
+    pub fn beats(self) -> f32 {
+        match self {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::DottedQuarter => 1.5,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::DottedEighth => 0.75,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::ThirtySecond => 0.125,
+        }
+    }
+
+    pub fn seconds(self, bpm: f32) -> f32 {
+        self.beats() * 60.0 / bpm.max(1.0)
+    }
+}
+
+/*
+ * The two send buses every track can feed.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SendBus {
+    Delay,
+    Reverb,
+}
+
+/*
+ * Tempo-synced stereo delay. Feedback and damping
+ * go from 0.0 to 1.0; the return level is linear gain
+ * on the master.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DelaySettings {
+    pub division: NoteDivision,
+    pub feedback: f32,
+    pub ping_pong: bool,
+    pub damping: f32,
+    pub return_level: f32,
+}
+
+impl Default for DelaySettings {
+    fn default() -> Self {
+        Self {
+            division: NoteDivision::DottedEighth,
+            feedback: 0.4,
+            ping_pong: true,
+            damping: 0.3,
+            return_level: 1.0,
+        }
+    }
+}
+
+/*
+ * Algorithmic reverb. Size, decay and damping go from
+ * 0.0 to 1.0; pre-delay is in seconds; the return level
+ * is linear gain on the master.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReverbSettings {
+    pub size: f32,
+    pub decay: f32,
+    pub damping: f32,
+    pub pre_delay: f32,
+    pub return_level: f32,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> Self {
+        Self {
+            size: 0.5,
+            decay: 0.5,
+            damping: 0.5,
+            pre_delay: 0.02,
+            return_level: 1.0,
+        }
+    }
+}
+
+/*
+ * How much of a track, from 0.0 to 1.0,
+ * goes to each send bus.
+ */
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TrackSends {
+    pub delay: f32,
+    pub reverb: f32,
+}
+
+impl TrackSends {
+    pub fn get(&self, bus: SendBus) -> f32 {
+        match bus {
+            SendBus::Delay => self.delay,
+            SendBus::Reverb => self.reverb,
+        }
+    }
+
+    pub fn set(&mut self, bus: SendBus, amount: f32) {
+        let amount = amount.clamp(0.0, 1.0);
+        match bus {
+            SendBus::Delay => self.delay = amount,
+            SendBus::Reverb => self.reverb = amount,
+        }
+    }
+}
+
+/*
+ * Everything about the send effects
+ * that's saved with the song.
+ */
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SendEffects {
+    pub delay: DelaySettings,
+    pub reverb: ReverbSettings,
+    pub sends: [TrackSends; NUM_TRACKS],
+}
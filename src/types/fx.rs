@@ -0,0 +1,108 @@
+/*
+ * Tracker effect commands. Each FX column on a step
+ * holds a command letter and an 8-bit value, shown
+ * as e.g. "C37". Where the value is split into two
+ * hex digits xy, x is the high nibble and y the low.
+ *
+ * Commands last for the step they're on, except that
+ * pitch slides and volume slides carry on until the
+ * next note. Ticks count from 0 at the start of the
+ * step, and how many a step has is set by its groove.
+ *
+ *   C xy  Arpeggio: cycle the note, +x and +y semitones, one per tick
+ *   U xx  Pitch slide up by xx/16 semitone every tick after the first
+ *   D xx  Pitch slide down by xx/16 semitone every tick after the first
+ *   V xy  Vibrato at speed x/32 cycle per tick, depth y/8 semitone
+ *   E xy  Volume slide: up x/64 or down y/64 every tick after the first
+ *   R xx  Retrigger the note every xx ticks
+ *   W xx  Wait xx ticks before playing the note
+ *   X xx  Cut: release the note on tick xx
+ *   K xx  Kill: silence the note on tick xx, without a release
+ *   T xx  Set the tempo to xx bpm
+ *   H xx  Hop to step xx after this one
+ *   P xy  Set instrument param x to y/15 of its range
+ *   A xx  Start table xx with the note
+ *
+ * Tables have their own take on some of these; see
+ * the table module.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FxKind {
+    Arpeggio,
+    SlideUp,
+    SlideDown,
+    Vibrato,
+    VolumeSlide,
+    Retrigger,
+    Delay,
+    Cut,
+    Kill,
+    Tempo,
+    Hop,
+    Param,
+    Table,
+}
+
+impl FxKind {
+    pub const ALL: [FxKind; 13] = [
+        FxKind::Arpeggio,
+        FxKind::SlideUp,
+        FxKind::SlideDown,
+        FxKind::Vibrato,
+        FxKind::VolumeSlide,
+        FxKind::Retrigger,
+        FxKind::Delay,
+        FxKind::Cut,
+        FxKind::Kill,
+        FxKind::Tempo,
+        FxKind::Hop,
+        FxKind::Param,
+        FxKind::Table,
+    ];
+
+    pub fn letter(self) -> char {
+        match self {
+            FxKind::Arpeggio => 'C',
+            FxKind::SlideUp => 'U',
+            FxKind::SlideDown => 'D',
+            FxKind::Vibrato => 'V',
+            FxKind::VolumeSlide => 'E',
+            FxKind::Retrigger => 'R',
+            FxKind::Delay => 'W',
+            FxKind::Cut => 'X',
+            FxKind::Kill => 'K',
+            FxKind::Tempo => 'T',
+            FxKind::Hop => 'H',
+            FxKind::Param => 'P',
+            FxKind::Table => 'A',
+        }
+    }
+
+    // The command after (or before) this one, wrapping round
+    pub fn cycle(self, delta: isize) -> FxKind {
+        let count = Self::ALL.len() as isize;
+        let index = Self::ALL.iter().position(|kind| *kind == self).unwrap() as isize;
+        Self::ALL[(index + delta).rem_euclid(count) as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fx {
+    pub kind: FxKind,
+    pub value: u8,
+}
+
+impl Fx {
+    pub fn new(kind: FxKind, value: u8) -> Self {
+        Self { kind, value }
+    }
+
+    pub fn high(&self) -> u8 {
+        self.value >> 4
+    }
+
+    pub fn low(&self) -> u8 {
+        self.value & 0x0F
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * Grooves set how many ticks each step lasts.
+ * A groove is a list of tick counts that playback
+ * steps through, one per step, starting again when
+ * it runs out and at the top of each phrase: 8 then
+ * 4 swings pairs of steps while keeping the average
+ * at 6. The list ends at the first empty entry, and
+ * an empty groove plays straight.
+ *
+ * Ticks are a fixed length, set by the tempo and the
+ * song's ticks per step: a straight step always lasts
+ * ticks_per_step ticks. Each track has a groove, and
+ * a phrase can pick its own instead.
+ */
+
+pub type GrooveId = u8;
+
+pub const NUM_GROOVE_STEPS: usize = 16;
+pub const DEFAULT_TICKS_PER_STEP: u8 = 6;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Groove {
+    pub steps: [Option<u8>; NUM_GROOVE_STEPS],
+}
+
+impl Groove {
+    // Tick counts in play order
+    pub fn ticks(&self) -> Vec<u8> {
+        self.steps.iter().map_while(|ticks| *ticks).collect()
+    }
+}
+
+/*
+ * Everything the sequencer needs to know to time
+ * a phrase on a track.
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timing {
+    pub ticks_per_step: u8,
+    pub groove: Vec<u8>,
+}
+
+impl Timing {
+    // Length in ticks of the step at a position in its phrase
+    pub fn step_ticks(&self, position: usize) -> u32 {
+        let ticks = if self.groove.is_empty() {
+            self.ticks_per_step
+        } else {
+            self.groove[position % self.groove.len()]
+        };
+        ticks.max(1) as u32
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            ticks_per_step: DEFAULT_TICKS_PER_STEP,
+            groove: vec![],
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use super::{MixerChannel, NUM_TRACKS};
+
+/*
+ * A channel strip's settings: gain in dB, pan from
+ * -1.0 (hard left) to 1.0 (hard right), mute and
+ * solo. Only tracks can be soloed.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelSettings {
+    pub gain_db: f32,
+    pub pan: f32,
+    pub mute: bool,
+    pub solo: bool,
+}
+
+impl Default for ChannelSettings {
+    fn default() -> Self {
+        Self {
+            gain_db: 0.0,
+            pan: 0.0,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+/*
+ * Everything about the mixer's channel
+ * strips that's saved with the song.
+ */
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MixerSettings {
+    pub tracks: [ChannelSettings; NUM_TRACKS],
+    pub master: ChannelSettings,
+}
+
+impl MixerSettings {
+    pub fn channel(&self, channel: MixerChannel) -> Option<&ChannelSettings> {
+        match channel {
+            MixerChannel::Track(track_id) => self.tracks.get(track_id as usize),
+            MixerChannel::Master => Some(&self.master),
+        }
+    }
+
+    pub fn channel_mut(&mut self, channel: MixerChannel) -> Option<&mut ChannelSettings> {
+        match channel {
+            MixerChannel::Track(track_id) => self.tracks.get_mut(track_id as usize),
+            MixerChannel::Master => Some(&mut self.master),
+        }
+    }
+}
@@ -1,27 +1,162 @@
+mod block;
+mod diagnostic;
+mod effects;
+mod fx;
+mod groove;
+mod mixer;
+mod notation;
+mod table;
+mod usage;
+
+pub use block::{Block, Cell, Grid};
+pub use diagnostic::{Diagnostic, Problem};
+pub use effects::{DelaySettings, NoteDivision, ReverbSettings, SendBus, SendEffects, TrackSends};
+pub use fx::{Fx, FxKind};
+pub use groove::{DEFAULT_TICKS_PER_STEP, Groove, GrooveId, NUM_GROOVE_STEPS, Timing};
+pub use mixer::{ChannelSettings, MixerSettings};
+pub use notation::{Accidentals, Notation};
+pub use table::{NUM_TABLE_ROWS, Table, TableId, TableRow};
+pub use usage::{CleanupReport, Usage};
+
 pub type PatternId = u8;
 pub type TrackId = u8;
 pub type ChainId = u8;
 pub type PhraseId = u8;
+pub type InstrumentId = u8;
 pub type NoteId = u8; // MIDI note number
 pub type Note = u8;
 
+// The mixer has a channel for each track, so
+// songs can't have more tracks than this
 pub const NUM_TRACKS: usize = 8;
-pub const NUM_PHRASES_PER_CHAIN: usize = 4;
-pub const NUM_STEPS_PER_PHRASE: usize = 16;
+// Notes played by hand sound on a track of their
+// own past the song's, so they never cut one off
+pub const AUDITION_TRACK: TrackId = NUM_TRACKS as TrackId;
+// Pattern IDs and step numbers are bytes
+pub const NUM_PATTERNS: usize = 256;
+pub const MAX_PHRASES_PER_CHAIN: usize = 256;
+pub const MAX_STEPS_PER_PHRASE: usize = 256;
+pub const NUM_FX_COLUMNS: usize = 2;
+pub const MAX_VELOCITY: u8 = 0x7F;
+// Notes are MIDI note numbers
+pub const MAX_NOTE: Note = 0x7F;
+
+/*
+ * How many tracks a song has, and how long its
+ * chains and phrases are. Each song picks its own,
+ * within the limits above; songs that don't say
+ * get the defaults.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SongDimensions {
+    pub tracks: usize,
+    pub phrases_per_chain: usize,
+    pub steps_per_phrase: usize,
+}
+
+impl SongDimensions {
+    pub fn is_valid(&self) -> bool {
+        (1..=NUM_TRACKS).contains(&self.tracks)
+            && (1..=MAX_PHRASES_PER_CHAIN).contains(&self.phrases_per_chain)
+            && (1..=MAX_STEPS_PER_PHRASE).contains(&self.steps_per_phrase)
+    }
+}
+
+impl Default for SongDimensions {
+    fn default() -> Self {
+        Self {
+            tracks: NUM_TRACKS,
+            phrases_per_chain: 4,
+            steps_per_phrase: 16,
+        }
+    }
+}
+
+/*
+ * A channel strip in the mixer: one
+ * per track, plus the master.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MixerChannel {
+    Track(TrackId),
+    Master,
+}
+
+/*
+ * A row in a chain: the phrase to play, and how
+ * far to transpose every note in it. Transpose is
+ * in semitones, held as a signed byte so F4 is -12.
+ */
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChainRow {
+    pub phrase: Option<PhraseId>,
+    pub transpose: u8,
+}
+
+impl ChainRow {
+    pub fn semitones(&self) -> i8 {
+        self.transpose as i8
+    }
+}
+
+/*
+ * What a step does to the track's note: play a new
+ * one, release the one playing, or cut it dead
+ * without its release.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepKind {
+    Note(Note),
+    Off,
+    Kill,
+}
 
 /*
  * Each step represents a note
  * or command.
+ *
+ * An empty instrument column carries on with the
+ * track's last instrument; an empty velocity column
+ * plays at full velocity. Off and kill steps only
+ * use their FX.
  */
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Step {
-    pub note: u8,
+    pub kind: StepKind,
     pub len: u8,
+    pub instrument: Option<InstrumentId>,
+    pub velocity: Option<u8>,
+    pub fx: [Option<Fx>; NUM_FX_COLUMNS],
 }
 
 impl Step {
     pub fn new(note: Note, len: u8) -> Self {
-        Self { note, len }
+        Self {
+            len,
+            ..Self::of_kind(StepKind::Note(note))
+        }
+    }
+
+    pub fn of_kind(kind: StepKind) -> Self {
+        Self {
+            kind,
+            len: 0,
+            instrument: None,
+            velocity: None,
+            fx: [None; NUM_FX_COLUMNS],
+        }
+    }
+
+    // The note it plays, if it plays one
+    pub fn note(&self) -> Option<Note> {
+        match self.kind {
+            StepKind::Note(note) => Some(note),
+            _ => None,
+        }
     }
 }
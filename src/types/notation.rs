@@ -0,0 +1,55 @@
+use super::Note;
+
+/*
+ * How notes are written out: with sharps or flats,
+ * and which octave number middle C (MIDI note 60)
+ * is in. Scientific pitch puts it in octave 4, some
+ * hardware in 3 and some trackers in 5.
+ *
+ * Names are the pitch class then the octave, e.g.
+ * C-4 or F#3, with a dash where there's no sharp or
+ * flat so they line up. They're always three wide,
+ * so the few notes below octave 0 or above 9 are
+ * named in the nearest octave that fits.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Accidentals {
+    Sharps,
+    Flats,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Notation {
+    pub accidentals: Accidentals,
+    pub middle_c_octave: i8,
+}
+
+impl Notation {
+    pub const MIDDLE_C_OCTAVES: [i8; 3] = [3, 4, 5];
+
+    const SHARPS: [&str; 12] = [
+        "C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-",
+    ];
+    const FLATS: [&str; 12] = [
+        "C-", "Db", "D-", "Eb", "E-", "F-", "Gb", "G-", "Ab", "A-", "Bb", "B-",
+    ];
+
+    pub fn name(&self, note: Note) -> String {
+        let names = match self.accidentals {
+            Accidentals::Sharps => Self::SHARPS,
+            Accidentals::Flats => Self::FLATS,
+        };
+        let octave = ((note / 12) as i8 - 5 + self.middle_c_octave).clamp(0, 9);
+        format!("{}{}", names[(note % 12) as usize], octave)
+    }
+}
+
+impl Default for Notation {
+    fn default() -> Self {
+        Self {
+            accidentals: Accidentals::Sharps,
+            middle_c_octave: 4,
+        }
+    }
+}
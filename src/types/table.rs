@@ -0,0 +1,53 @@
+use super::{Fx, NUM_FX_COLUMNS};
+
+/*
+ * Tables are little programs that run alongside a
+ * note, one row per tick, starting again from the
+ * top with every new note. They're started by the
+ * note's instrument, or by an A command on the step.
+ *
+ * Each row transposes the note by a number of
+ * semitones, held as a signed byte so F4 is -12,
+ * and can set the volume from 00 to 7F; an empty
+ * volume keeps what it was. The FX columns act on
+ * the tick their row plays:
+ *
+ *   U xx  Pitch up by xx/16 semitone
+ *   D xx  Pitch down by xx/16 semitone
+ *   E xy  Volume up x/64 or down y/64
+ *   R xx  Retrigger the note
+ *   X xx  Release the note
+ *   K xx  Kill the note and stop the table
+ *   T xx  Set the tempo to xx bpm
+ *   P xy  Set instrument param x to y/15 of its range
+ *   A xx  Carry on with table xx, from the top
+ *   H xy  Hop to row y, x times before carrying on
+ *         past it; with x of 0 it loops for good
+ *
+ * Other commands do nothing in a table. A table
+ * that reaches its last row without hopping stops
+ * there, leaving the transpose and volume as the
+ * last row set them.
+ */
+
+pub type TableId = u8;
+
+pub const NUM_TABLE_ROWS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TableRow {
+    pub transpose: u8,
+    pub volume: Option<u8>,
+    pub fx: [Option<Fx>; NUM_FX_COLUMNS],
+}
+
+impl TableRow {
+    pub fn semitones(&self) -> i8 {
+        self.transpose as i8
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Table {
+    pub rows: [TableRow; NUM_TABLE_ROWS],
+}
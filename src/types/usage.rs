@@ -0,0 +1,35 @@
+use super::{ChainId, PhraseId};
+use std::collections::BTreeMap;
+
+/*
+ * Which chains and phrases a song uses. A chain is
+ * used if a pattern has it, and a phrase if a used
+ * chain has it; anything else is orphaned and could
+ * be cleaned up. Reference counts take in every
+ * chain, used or not.
+ */
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Usage {
+    // The chains on each pattern row, in track order
+    pub pattern_chains: Vec<Vec<ChainId>>,
+    // How many pattern cells have each chain
+    pub chain_references: BTreeMap<ChainId, usize>,
+    // How many chain rows have each phrase
+    pub phrase_references: BTreeMap<PhraseId, usize>,
+    pub orphaned_chains: Vec<ChainId>,
+    pub orphaned_phrases: Vec<PhraseId>,
+}
+
+/*
+ * What a cleanup did: the IDs it deleted, and if it
+ * renumbered, each ID kept with the one it moved to.
+ */
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CleanupReport {
+    pub removed_chains: Vec<ChainId>,
+    pub removed_phrases: Vec<PhraseId>,
+    pub renumbered_chains: Vec<(ChainId, ChainId)>,
+    pub renumbered_phrases: Vec<(PhraseId, PhraseId)>,
+}
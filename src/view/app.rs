@@ -1,33 +1,91 @@
 use eframe::{
     App,
     egui::{
-        CentralPanel, Context, Direction, FontData, FontDefinitions, FontFamily, Frame, InputState,
-        Layout, Margin, Ui,
+        CentralPanel, Color32, Context, Direction, FontData, FontDefinitions, FontFamily, Frame,
+        InputState, Layout, Margin, RichText, Ui,
     },
 };
 
+use crate::engine::Playhead;
 use crate::messaging::Action;
+use crate::model::SongError;
 use crate::view::Chain;
+use crate::view::Groove;
+use crate::view::Instrument;
+use crate::view::Mixer;
+use crate::view::PatchEditor;
 use crate::view::Phrase;
 use crate::view::Song;
+use crate::view::Table;
 use crate::view::View;
-use crossbeam::channel::Sender;
+use crate::view::keyboard::Keyboard;
+use crate::view::view::get_playhead;
+use crossbeam::channel::{Receiver, Sender, bounded};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum ViewMode {
     Song,
     Chain,
     Phrase,
+    Instrument,
+    Patch,
+    Mixer,
+    Groove,
+    Table,
 }
 
+/*
+ * Getting about between views. The screens sit in a
+ * row, from the song down to instruments and tables,
+ * and Shift+Left/Right moves along it: right opens
+ * what's under the cursor, or the one last opened
+ * there, and left goes back up. Backspace goes back
+ * to the view before, however it was reached. Ctrl+P
+ * opens an instrument's patch from its screen.
+ *
+ * Each view is kept once opened, one per ID, so the
+ * cursor is where it was left, and is refreshed on
+ * coming back in case it was edited from elsewhere.
+ * Tables and grooves switch ID themselves, so there's
+ * one view of each.
+ */
+const SCREENS: [ViewMode; 5] = [
+    ViewMode::Song,
+    ViewMode::Chain,
+    ViewMode::Phrase,
+    ViewMode::Instrument,
+    ViewMode::Table,
+];
+const MAX_HISTORY: usize = 64;
+// How long a rejected edit's message stays up
+const ERROR_TIME: Duration = Duration::from_secs(3);
+
 pub struct UiApp {
     tx: Sender<Action>,
     font_loaded: bool,
-    song_view: Rc<RefCell<dyn View>>,
     view: Rc<RefCell<dyn View>>,
     mode: ViewMode,
+    id: u8,
+    views: HashMap<(ViewMode, u8), Rc<RefCell<dyn View>>>,
+    // The ID each screen was last opened with
+    last_ids: HashMap<ViewMode, u8>,
+    // Where Backspace goes back to
+    history: Vec<(ViewMode, u8)>,
+    // Ctrl+L turns the keyboard into a piano in any view
+    live: bool,
+    keyboard: Keyboard,
+    // Ctrl+F keeps the playing row in sight
+    follow: bool,
+    playhead: Arc<Playhead>,
+    // Edits the song turned down, and the last one's
+    // message with when it came
+    errors: Receiver<SongError>,
+    error: Option<(String, Instant)>,
 }
 
 fn load_custom_font(ctx: &Context) {
@@ -53,45 +111,230 @@ fn load_custom_font(ctx: &Context) {
 
 impl UiApp {
     pub fn new(tx: Sender<Action>) -> Self {
-        let view = Rc::new(RefCell::new(Song::new(tx.clone())));
+        let view: Rc<RefCell<dyn View>> = Rc::new(RefCell::new(Song::new(tx.clone())));
         Self {
             tx: tx.clone(),
             font_loaded: false,
-            song_view: view.clone(),
+            views: HashMap::from([((ViewMode::Song, 0), view.clone())]),
             view,
             mode: ViewMode::Song,
+            id: 0,
+            last_ids: HashMap::from([(ViewMode::Song, 0)]),
+            history: vec![],
+            live: false,
+            keyboard: Keyboard::new(tx.clone()),
+            follow: false,
+            playhead: get_playhead(&tx),
+            errors: Self::fetch_errors(&tx),
+            error: None,
         }
     }
 
+    fn fetch_errors(tx: &Sender<Action>) -> Receiver<SongError> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetEditErrors { reply_to: reply_tx })
+            .unwrap();
+        reply_rx.recv().unwrap()
+    }
+
+    // A view shows its edits before the song takes
+    // them, so fetch its data again if one was turned
+    // down, and say why
+    fn check_errors(&mut self) {
+        let Some(err) = self.errors.try_iter().last() else {
+            return;
+        };
+        self.error = Some((format!("REJECTED: {err}").to_uppercase(), Instant::now()));
+        self.view.borrow_mut().refresh();
+    }
+
     pub fn draw(&mut self, ui: &mut Ui) {
+        let modes = [(self.live, "LIVE"), (self.follow, "FOLLOW")];
+        let modes: Vec<_> = modes
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, name)| *name)
+            .collect();
+        if !modes.is_empty() {
+            ui.label(
+                RichText::new(modes.join(" "))
+                    .size(12.0)
+                    .color(Color32::RED),
+            );
+        }
+        if let Some((message, since)) = &self.error {
+            if since.elapsed() < ERROR_TIME {
+                ui.label(RichText::new(message).size(12.0).color(Color32::RED));
+                ui.ctx().request_repaint_after(ERROR_TIME - since.elapsed());
+            } else {
+                self.error = None;
+            }
+        }
         self.view.borrow_mut().draw(ui);
     }
 
+    // Go to a view, remembering where we were
+    fn show(&mut self, mode: ViewMode, id: u8) {
+        let id = match mode {
+            ViewMode::Song | ViewMode::Mixer | ViewMode::Groove | ViewMode::Table => 0,
+            ViewMode::Chain | ViewMode::Phrase | ViewMode::Instrument | ViewMode::Patch => id,
+        };
+        if (mode, id) == (self.mode, self.id) {
+            return;
+        }
+
+        self.history.push((self.mode, self.id));
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.open(mode, id);
+    }
+
+    // Switch to a view, the one kept for it if there is one
+    fn open(&mut self, mode: ViewMode, id: u8) {
+        let tx = self.tx.clone();
+        let view = match self.views.get(&(mode, id)) {
+            Some(view) => {
+                view.borrow_mut().refresh();
+                view.clone()
+            }
+            None => {
+                let view: Rc<RefCell<dyn View>> = match mode {
+                    ViewMode::Song => Rc::new(RefCell::new(Song::new(tx))),
+                    ViewMode::Chain => Rc::new(RefCell::new(Chain::new(tx, id))),
+                    ViewMode::Phrase => Rc::new(RefCell::new(Phrase::new(tx, id))),
+                    ViewMode::Instrument => Rc::new(RefCell::new(Instrument::new(tx, id))),
+                    ViewMode::Patch => Rc::new(RefCell::new(PatchEditor::new(tx, id))),
+                    ViewMode::Mixer => Rc::new(RefCell::new(Mixer::new(tx))),
+                    ViewMode::Groove => Rc::new(RefCell::new(Groove::new(tx, id))),
+                    ViewMode::Table => Rc::new(RefCell::new(Table::new(tx, id))),
+                };
+                self.views.insert((mode, id), view.clone());
+                view
+            }
+        };
+
+        self.view = view;
+        self.mode = mode;
+        self.id = id;
+        self.last_ids.insert(mode, id);
+    }
+
+    fn back(&mut self) {
+        if let Some((mode, id)) = self.history.pop() {
+            self.open(mode, id);
+        }
+    }
+
+    // Move along the row of screens. Going right opens the
+    // chain, phrase or instrument under the cursor if there is one.
+    fn step_screen(&mut self, right: bool) {
+        let Some(index) = SCREENS.iter().position(|&mode| mode == self.mode) else {
+            return;
+        };
+        let index = if right {
+            index + 1
+        } else {
+            match index.checked_sub(1) {
+                Some(index) => index,
+                None => return,
+            }
+        };
+        let Some(&mode) = SCREENS.get(index) else {
+            return;
+        };
+
+        let selected = match (self.mode, mode) {
+            (ViewMode::Song, ViewMode::Chain) | (ViewMode::Chain, ViewMode::Phrase) => {
+                self.view.borrow().get_selection()
+            }
+            (ViewMode::Phrase, ViewMode::Instrument) => self.view.borrow().get_instrument(),
+            _ => None,
+        };
+        let id = match mode {
+            ViewMode::Chain | ViewMode::Phrase | ViewMode::Instrument => {
+                selected.or(self.last_ids.get(&mode).copied())
+            }
+            _ => Some(0),
+        };
+        if let Some(id) = id {
+            self.show(mode, id);
+        }
+    }
+
     pub fn handle_event(&mut self, input: &InputState) {
-        if input.key_pressed(egui::Key::Enter) {
+        let shift_only = input.modifiers.shift && !input.modifiers.command && !input.modifiers.alt;
+        let on_screen_row = SCREENS.contains(&self.mode);
+
+        // Ctrl+G opens the grooves, Ctrl+T the tables and
+        // Ctrl+M the mixer from anywhere
+        if input.modifiers.command && input.key_pressed(egui::Key::G) {
+            self.show(ViewMode::Groove, 0);
+            return;
+        } else if input.modifiers.command && input.key_pressed(egui::Key::T) {
+            self.show(ViewMode::Table, 0);
+            return;
+        } else if input.modifiers.command && input.key_pressed(egui::Key::M) {
+            self.show(ViewMode::Mixer, 0);
+            return;
+        } else if input.modifiers.command
+            && input.key_pressed(egui::Key::P)
+            && self.mode == ViewMode::Instrument
+        {
+            self.show(ViewMode::Patch, self.id);
+            return;
+        } else if shift_only && on_screen_row && input.key_pressed(egui::Key::ArrowRight) {
+            self.step_screen(true);
+            return;
+        } else if shift_only && on_screen_row && input.key_pressed(egui::Key::ArrowLeft) {
+            self.step_screen(false);
+            return;
+        } else if input.key_pressed(egui::Key::Backspace) {
+            self.back();
+            return;
+        } else if input.key_pressed(egui::Key::Enter) {
             let selected_value = self.view.borrow().get_selection();
 
             match self.mode {
                 ViewMode::Song => {
                     if let Some(chain_id) = selected_value {
-                        self.mode = ViewMode::Chain;
-                        self.view = Rc::new(RefCell::new(Chain::new(self.tx.clone(), chain_id)));
+                        self.show(ViewMode::Chain, chain_id);
                     }
                 }
                 ViewMode::Chain => {
                     if let Some(phrase_id) = selected_value {
-                        self.mode = ViewMode::Phrase;
-                        self.view = Rc::new(RefCell::new(Phrase::new(self.tx.clone(), phrase_id)));
+                        self.show(ViewMode::Phrase, phrase_id);
                     }
                 }
-                ViewMode::Phrase => {
-                    self.view = self.song_view.clone();
-                    self.mode = ViewMode::Song;
+                ViewMode::Phrase
+                | ViewMode::Instrument
+                | ViewMode::Patch
+                | ViewMode::Mixer
+                | ViewMode::Groove
+                | ViewMode::Table => {
+                    self.show(ViewMode::Song, 0);
                 }
             }
+            return;
+        } else if input.modifiers.command && input.key_pressed(egui::Key::L) {
+            self.live = !self.live;
+        } else if input.modifiers.command && input.key_pressed(egui::Key::F) {
+            self.follow = !self.follow;
         } else if input.key_pressed(egui::Key::Space) {
         }
 
+        // Live play plays the instrument last under the cursor,
+        // and keeps the keys it uses from the view
+        if self.live {
+            if let Some(instrument) = self.view.borrow().get_instrument() {
+                self.keyboard.set_instrument(instrument);
+            }
+            self.keyboard.release(input);
+            if let (_, true) = self.keyboard.handle_keys(input) {
+                return;
+            }
+        }
+
         self.view.borrow_mut().handle_event(input);
     }
 }
@@ -104,6 +347,15 @@ impl App for UiApp {
         }
 
         ctx.input(|i| self.handle_event(i));
+        self.check_errors();
+
+        // Redraw while playing, for the playhead to move
+        if self.playhead.is_playing() {
+            if self.follow {
+                self.view.borrow_mut().follow();
+            }
+            ctx.request_repaint_after(Duration::from_millis(20));
+        }
 
         CentralPanel::default().show(ctx, |ui| {
             ui.with_layout(Layout::centered_and_justified(Direction::TopDown), |ui| {
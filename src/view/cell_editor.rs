@@ -0,0 +1,167 @@
+use eframe::egui::{InputState, Key};
+
+use std::collections::HashMap;
+
+/*
+ * Editing the value in a cell, shared by the song,
+ * chain, phrase and table views:
+ *
+ *   Shift+Up/Down     up or down by one
+ *   Shift+PgUp/PgDn   up or down by a bigger step
+ *   0-9, A-F          type the value, high digit first
+ *   Insert            put back the last value the
+ *                     column was given
+ *   Escape            forget a half-typed value
+ *
+ * The first digit typed waits at the cursor until
+ * the second comes, and is forgotten if the cursor
+ * moves. Clearing is Delete, from the selection.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellEdit {
+    // Shift+Up/Down
+    Nudge(isize),
+    // Shift+PageUp/PageDown, by a step the view picks
+    Jump(isize),
+    // Typed in or put back
+    Set(u8),
+}
+
+impl CellEdit {
+    // The cell's new value, from nothing up to max.
+    // Going down past zero empties the cell.
+    pub fn apply(self, value: Option<u8>, jump: isize, max: usize) -> Option<u8> {
+        let delta = match self {
+            CellEdit::Nudge(delta) => delta,
+            CellEdit::Jump(direction) => direction * jump,
+            CellEdit::Set(value) => return Some(value.min(max as u8)),
+        };
+
+        match value {
+            Some(v) => {
+                let new = v as isize + delta;
+                if new < 0 {
+                    None
+                } else {
+                    Some(new.min(max as isize) as u8)
+                }
+            }
+            None => {
+                if delta == 1 {
+                    Some(0)
+                } else if delta > 0 {
+                    Some(delta.min(max as isize) as u8)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+pub struct CellEditor {
+    // The first digit typed and the cell it's for
+    pending: Option<(usize, usize, u8)>,
+    // The last value given to each column
+    last: HashMap<usize, u8>,
+}
+
+impl CellEditor {
+    const DIGITS: [Key; 16] = [
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            pending: None,
+            last: HashMap::new(),
+        }
+    }
+
+    // The edit for the cell at the cursor, if a key for
+    // one was pressed. Columns that aren't hex, like
+    // notes, don't take typed digits.
+    pub fn handle_keys(
+        &mut self,
+        input: &InputState,
+        row: usize,
+        col: usize,
+        hex: bool,
+    ) -> Option<CellEdit> {
+        if self.pending.is_some_and(|(r, c, _)| (r, c) != (row, col))
+            || input.key_pressed(Key::Escape)
+            || input.key_pressed(Key::Delete)
+        {
+            self.pending = None;
+        }
+
+        if input.modifiers.command || input.modifiers.alt {
+            return None;
+        }
+
+        if input.modifiers.shift {
+            return if input.key_pressed(Key::ArrowUp) {
+                Some(CellEdit::Nudge(1))
+            } else if input.key_pressed(Key::ArrowDown) {
+                Some(CellEdit::Nudge(-1))
+            } else if input.key_pressed(Key::PageUp) {
+                Some(CellEdit::Jump(1))
+            } else if input.key_pressed(Key::PageDown) {
+                Some(CellEdit::Jump(-1))
+            } else {
+                None
+            };
+        }
+
+        if input.key_pressed(Key::Insert) {
+            return self.last.get(&col).map(|&value| CellEdit::Set(value));
+        }
+
+        if !hex {
+            return None;
+        }
+        let digit = Self::DIGITS
+            .iter()
+            .position(|&key| input.key_pressed(key))? as u8;
+        match self.pending.take() {
+            Some((_, _, high)) => Some(CellEdit::Set(high << 4 | digit)),
+            None => {
+                self.pending = Some((row, col, digit));
+                None
+            }
+        }
+    }
+
+    // Keep a value the view gave a column, for Insert
+    pub fn remember(&mut self, col: usize, value: u8) {
+        self.last.insert(col, value);
+    }
+
+    // The digit typed so far in a cell, if any
+    pub fn pending(&self, row: usize, col: usize) -> Option<u8> {
+        self.pending
+            .filter(|&(r, c, _)| (r, c) == (row, col))
+            .map(|(_, _, digit)| digit)
+    }
+
+    // A cell with a digit typed, e.g. 3_
+    pub fn render_pending(digit: u8) -> String {
+        format!("{:X}_", digit)
+    }
+}
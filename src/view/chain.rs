@@ -1,41 +1,81 @@
 use eframe::egui::{Color32, InputState, Key, RichText, Ui};
 
-use super::view::View;
+use super::cell_editor::{CellEdit, CellEditor};
+use super::selection::Selection;
+use super::view::{View, check_grid, get_playhead, playhead_label, scroll_to};
+use crate::engine::Playhead;
 use crate::messaging::Action;
-use crate::types::NUM_PHRASES_PER_CHAIN;
-use crate::types::{ChainId, PhraseId};
+use crate::types::{ChainId, ChainRow, Diagnostic, Grid, NUM_TRACKS};
 use crossbeam::channel::Sender;
 use crossbeam::channel::bounded;
 use std::borrow::Cow;
+use std::sync::Arc;
 
 pub struct Chain {
     tx: Sender<Action>,
 
     chain_id: ChainId,
+    visible_rows: usize,
 
     // Selection state
     selected_row: usize,
+    selected_col: usize,
+    min_row: usize,
+    selection: Selection,
+    editor: CellEditor,
 
     // Data
-    values: Vec<Option<PhraseId>>,
+    values: Vec<ChainRow>,
+    // Problems the song checker found in the chain
+    diagnostics: Vec<Diagnostic>,
+    playhead: Arc<Playhead>,
 }
 
 impl View for Chain {
     fn handle_event(&mut self, input: &InputState) {
-        let shift_down = input.modifiers.shift;
+        let grid = Grid::Chain(self.chain_id);
+        let (row, col) = (self.selected_row, self.selected_col);
+        let edit = self.editor.handle_keys(input, row, col, true);
 
-        if shift_down {
-            self.change_selection(input);
+        if self.selection.handle_keys(input, &self.tx, grid, row, col) {
+            self.reload();
+            self.check();
+        } else if input.key_pressed(Key::Space) {
+            self.tx.send(Action::PlayChain(self.chain_id)).unwrap();
+        } else if input.modifiers.command && input.key_pressed(Key::D) {
+            self.clone_phrase();
+        } else if let Some(edit) = edit {
+            self.edit_cell(edit);
         } else {
             self.move_selection(input);
         }
     }
 
     fn get_selection(&self) -> Option<u8> {
-        self.values[self.selected_row]
+        self.values[self.selected_row].phrase
+    }
+
+    fn refresh(&mut self) {
+        self.reload();
+        self.check();
+    }
+
+    fn follow(&mut self) {
+        let Some(row) = (0..NUM_TRACKS)
+            .filter_map(|track_id| self.playhead.track(track_id)?.chain)
+            .find(|&(chain_id, _)| chain_id == self.chain_id)
+            .map(|(_, row)| row)
+        else {
+            return;
+        };
+        if row < self.min_row || row >= self.min_row + self.visible_rows {
+            self.min_row = row.min(self.values.len().saturating_sub(self.visible_rows));
+        }
     }
 
     fn draw(&mut self, ui: &mut Ui) {
+        let max_row = (self.min_row + self.visible_rows).min(self.values.len());
+
         ui.vertical_centered(|ui| {
             ui.label(RichText::new("CHAIN").heading().color(Color32::LIGHT_BLUE));
         });
@@ -43,7 +83,7 @@ impl View for Chain {
 
         // Grid for table layout
         egui::Grid::new("chain_grid")
-            .num_columns(3)
+            .num_columns(4)
             .min_col_width(40.0)
             .max_col_width(f32::INFINITY)
             .show(ui, |ui| {
@@ -51,25 +91,56 @@ impl View for Chain {
                 ui.label(""); // Empty ">" column
                 ui.label(""); // Empty row label column
                 ui.label(format!("{:0x}", self.chain_id));
+                ui.label("T");
                 ui.end_row();
 
-                // Body rows
-                for i in 0..NUM_PHRASES_PER_CHAIN {
-                    ui.label(RichText::new(" ").color(Color32::YELLOW));
+                // Body rows, a window onto as many as
+                // the song's chains have
+                let playing: Vec<_> = (0..NUM_TRACKS)
+                    .filter_map(|track_id| self.playhead.track(track_id)?.chain)
+                    .filter(|&(chain_id, _)| chain_id == self.chain_id)
+                    .map(|(_, row)| row)
+                    .collect();
+                for i in self.min_row..max_row {
+                    ui.label(playhead_label(playing.contains(&i)));
                     ui.label(RichText::new(format!("{:02X}", i)).color(Color32::GREEN));
 
-                    let cell = if self.values.len() < i {
-                        self.render_cell(None)
-                    } else {
-                        self.render_cell(self.values[i])
-                    };
-
-                    let is_selected = i == self.selected_row;
-                    let text = RichText::new(cell).size(12.0);
-                    if is_selected {
-                        ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
-                    } else {
-                        ui.label(text.color(Color32::WHITE));
+                    let row = self.values.get(i).copied().unwrap_or_default();
+                    let cells = [
+                        self.render_cell(row.phrase),
+                        self.render_cell(Some(row.transpose)),
+                    ];
+
+                    for (col, cell) in cells.into_iter().enumerate() {
+                        let pending = self.editor.pending(i, col);
+                        let cell = match pending {
+                            Some(digit) => Cow::Owned(CellEditor::render_pending(digit)),
+                            None => cell,
+                        };
+                        let is_selected = i == self.selected_row && col == self.selected_col;
+                        let text = RichText::new(cell).size(12.0);
+                        if pending.is_some() {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::ORANGE));
+                        } else if is_selected {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                        } else if self.selection.contains(
+                            i,
+                            col,
+                            (self.selected_row, self.selected_col),
+                        ) {
+                            ui.label(
+                                text.color(Color32::WHITE)
+                                    .background_color(Color32::DARK_BLUE),
+                            );
+                        } else if self
+                            .diagnostics
+                            .iter()
+                            .any(|d| d.covers(Grid::Chain(self.chain_id), i, col))
+                        {
+                            ui.label(text.color(Color32::RED));
+                        } else {
+                            ui.label(text.color(Color32::WHITE));
+                        }
                     }
 
                     ui.end_row();
@@ -80,6 +151,8 @@ impl View for Chain {
 
 impl Chain {
     const MAX_CELL_VALUE: usize = 0xFF;
+    const PHRASE_COLUMN: usize = 0;
+    const TRANSPOSE_COLUMN: usize = 1;
     const BIG_CELL_INCREMENT: isize = 0x10;
     const EMPTY_CELL_DISPLAY: &str = "--";
 
@@ -94,21 +167,62 @@ impl Chain {
 
         let chain_data = reply_rx.recv().unwrap();
 
+        let diagnostics = check_grid(&tx, Grid::Chain(chain_id));
+        let playhead = get_playhead(&tx);
+
         Self {
             tx,
+            diagnostics,
+            playhead,
             chain_id,
             values: chain_data,
             selected_row: 0,
+            selected_col: 0,
+            visible_rows: 16,
+            min_row: 0,
+            selection: Selection::new(),
+            editor: CellEditor::new(),
         }
     }
 
+    // Fetch the chain again after a block edit
+    fn reload(&mut self) {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::GetChainData {
+                chain_id: self.chain_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+        self.values = reply_rx.recv().unwrap();
+
+        // The chain may have got shorter meanwhile
+        self.selected_row = self.selected_row.min(self.values.len().saturating_sub(1));
+        self.min_row = scroll_to(self.selected_row, self.min_row, self.visible_rows);
+    }
+
+    // Look for problems again after an edit, which
+    // can fix them or make new ones
+    fn check(&mut self) {
+        self.diagnostics = check_grid(&self.tx, Grid::Chain(self.chain_id));
+    }
+
     fn move_selection(&mut self, input: &InputState) {
+        self.selection
+            .update(input, self.selected_row, self.selected_col);
         if input.key_pressed(Key::ArrowDown) {
-            self.selected_row = (self.selected_row + 1).min(NUM_PHRASES_PER_CHAIN - 1);
+            self.selected_row = (self.selected_row + 1).min(self.values.len().saturating_sub(1));
         }
         if input.key_pressed(Key::ArrowUp) {
             self.selected_row = self.selected_row.saturating_sub(1);
         }
+        if input.key_pressed(Key::ArrowRight) {
+            self.selected_col = Self::TRANSPOSE_COLUMN;
+        }
+        if input.key_pressed(Key::ArrowLeft) {
+            self.selected_col = Self::PHRASE_COLUMN;
+        }
+        self.min_row = scroll_to(self.selected_row, self.min_row, self.visible_rows);
     }
 
     fn render_cell(&self, value: Option<u8>) -> Cow<'_, str> {
@@ -118,55 +232,77 @@ impl Chain {
         }
     }
 
-    fn apply_delta(value: Option<u8>, delta: isize) -> Option<u8> {
-        match value {
-            Some(v) => {
-                let new = v as isize + delta;
-                if new < 0 {
-                    None
-                } else {
-                    Some(new.min(Self::MAX_CELL_VALUE as isize) as u8)
-                }
-            }
-            None => {
-                if delta == 1 {
-                    return Some(0);
-                } else if delta > 0 {
-                    Some(delta.min(Self::MAX_CELL_VALUE as isize) as u8)
-                } else {
-                    None
-                }
-            }
-        }
-    }
+    // Ctrl+D replaces the selected row's phrase with a copy of it
+    fn clone_phrase(&mut self) {
+        let row = self.selected_row;
+        let Some(phrase_id) = self.values[row].phrase else {
+            return;
+        };
 
-    fn change_selection(&mut self, input: &egui::InputState) {
-        let delta = if input.key_pressed(Key::ArrowUp) {
-            Some(1)
-        } else if input.key_pressed(Key::ArrowDown) {
-            Some(-1)
-        } else if input.key_pressed(Key::ArrowRight) {
-            Some(Self::BIG_CELL_INCREMENT)
-        } else if input.key_pressed(Key::ArrowLeft) {
-            Some(-Self::BIG_CELL_INCREMENT)
-        } else {
-            None
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::ClonePhrase {
+                phrase_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+        let Ok(new_id) = reply_rx.recv().unwrap() else {
+            return;
         };
 
-        let Some(d) = delta else { return };
+        self.values[row].phrase = Some(new_id);
+        self.tx
+            .send(Action::SetChainPhrase {
+                chain_id: self.chain_id,
+                index: row,
+                phrase_id: Some(new_id),
+            })
+            .unwrap();
+        self.check();
+    }
 
+    // Change the phrase or transpose at the cursor
+    fn edit_cell(&mut self, edit: CellEdit) {
         let row = self.selected_row;
+        let col = self.selected_col;
         let cell = &mut self.values[row];
 
-        let new_value = Self::apply_delta(*cell, d);
-        *cell = new_value;
+        // Transpose is a signed byte, so it wraps
+        // round from 00 down to FF (-1)
+        if col == Self::TRANSPOSE_COLUMN {
+            cell.transpose = match edit {
+                CellEdit::Set(value) => value,
+                CellEdit::Nudge(delta) => cell.transpose.wrapping_add_signed(delta as i8),
+                CellEdit::Jump(direction) => cell
+                    .transpose
+                    .wrapping_add_signed((direction * Self::BIG_CELL_INCREMENT) as i8),
+            };
+            self.editor.remember(col, cell.transpose);
+
+            self.tx
+                .send(Action::SetChainTranspose {
+                    chain_id: self.chain_id,
+                    index: row,
+                    transpose: cell.transpose,
+                })
+                .unwrap();
+            self.check();
+            return;
+        }
+
+        let new_value = edit.apply(cell.phrase, Self::BIG_CELL_INCREMENT, Self::MAX_CELL_VALUE);
+        cell.phrase = new_value;
+        if let Some(value) = new_value {
+            self.editor.remember(col, value);
+        }
 
         self.tx
             .send(Action::SetChainPhrase {
                 chain_id: self.chain_id,
-                index: row as usize,
+                index: row,
                 phrase_id: new_value,
             })
             .unwrap();
+        self.check();
     }
 }
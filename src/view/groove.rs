@@ -0,0 +1,224 @@
+use eframe::egui::{Color32, InputState, Key, RichText, Ui};
+
+use super::view::View;
+use crate::messaging::Action;
+use crate::types::{GrooveId, NUM_GROOVE_STEPS};
+use crossbeam::channel::Sender;
+use crossbeam::channel::bounded;
+use std::borrow::Cow;
+
+/*
+ * Edits the tick counts in a groove, and the
+ * song's ticks per step.
+ *
+ * Shift+arrows change the selected tick count,
+ * Ctrl+Left/Right picks the groove to edit and
+ * Ctrl+Up/Down changes the ticks per step.
+ */
+
+pub struct Groove {
+    tx: Sender<Action>,
+
+    groove_id: GrooveId,
+
+    // Selection state
+    selected_row: usize,
+
+    // Data
+    ticks_per_step: u8,
+    values: Vec<Option<u8>>,
+}
+
+impl View for Groove {
+    fn handle_event(&mut self, input: &InputState) {
+        if input.modifiers.shift {
+            self.change_selection(input);
+        } else if input.modifiers.command {
+            self.change_settings(input);
+        } else {
+            self.move_selection(input);
+        }
+    }
+
+    fn get_selection(&self) -> Option<u8> {
+        Some(self.groove_id)
+    }
+
+    fn refresh(&mut self) {
+        self.values = Self::fetch_groove(&self.tx, self.groove_id);
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::GetTicksPerStep { reply_to: reply_tx })
+            .unwrap();
+        self.ticks_per_step = reply_rx.recv().unwrap();
+    }
+
+    fn draw(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("GROOVE").heading().color(Color32::LIGHT_BLUE));
+        });
+        ui.add_space(20.0);
+
+        ui.label(
+            RichText::new(format!("TICKS/STEP {:02X}", self.ticks_per_step))
+                .size(12.0)
+                .color(Color32::LIGHT_BLUE),
+        );
+        ui.add_space(10.0);
+
+        // Grid for table layout
+        egui::Grid::new("groove_grid")
+            .num_columns(3)
+            .min_col_width(40.0)
+            .max_col_width(f32::INFINITY)
+            .show(ui, |ui| {
+                // Header row
+                ui.label(""); // Empty ">" column
+                ui.label(""); // Empty row label column
+                ui.label(format!("{:0x}", self.groove_id));
+                ui.end_row();
+
+                // Body rows
+                for i in 0..NUM_GROOVE_STEPS {
+                    ui.label(RichText::new(" ").color(Color32::YELLOW));
+                    ui.label(RichText::new(format!("{:02X}", i)).color(Color32::GREEN));
+
+                    let cell = self.render_cell(self.values[i]);
+                    let is_selected = i == self.selected_row;
+                    let text = RichText::new(cell).size(12.0);
+                    if is_selected {
+                        ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                    } else {
+                        ui.label(text.color(Color32::WHITE));
+                    }
+
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl Groove {
+    const MAX_CELL_VALUE: usize = 0xFF;
+    const BIG_CELL_INCREMENT: isize = 0x10;
+    const EMPTY_CELL_DISPLAY: &str = "--";
+
+    pub fn new(tx: Sender<Action>, groove_id: GrooveId) -> Self {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTicksPerStep { reply_to: reply_tx })
+            .unwrap();
+        let ticks_per_step = reply_rx.recv().unwrap();
+
+        Self {
+            values: Self::fetch_groove(&tx, groove_id),
+            tx,
+            groove_id,
+            ticks_per_step,
+            selected_row: 0,
+        }
+    }
+
+    fn fetch_groove(tx: &Sender<Action>, groove_id: GrooveId) -> Vec<Option<u8>> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetGroove {
+            groove_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        reply_rx.recv().unwrap().steps.to_vec()
+    }
+
+    fn move_selection(&mut self, input: &InputState) {
+        if input.key_pressed(Key::ArrowDown) {
+            self.selected_row = (self.selected_row + 1).min(NUM_GROOVE_STEPS - 1);
+        }
+        if input.key_pressed(Key::ArrowUp) {
+            self.selected_row = self.selected_row.saturating_sub(1);
+        }
+    }
+
+    fn render_cell(&self, value: Option<u8>) -> Cow<'_, str> {
+        match value {
+            None => Cow::Borrowed(Self::EMPTY_CELL_DISPLAY),
+            Some(v) => Cow::Owned(format!("{:02X}", v)),
+        }
+    }
+
+    // A groove entry of zero ticks would be skipped
+    // over, so counts start at 1
+    fn apply_delta(value: Option<u8>, delta: isize) -> Option<u8> {
+        match value {
+            Some(v) => {
+                let new = v as isize + delta;
+                if new < 1 {
+                    None
+                } else {
+                    Some(new.min(Self::MAX_CELL_VALUE as isize) as u8)
+                }
+            }
+            None => {
+                if delta > 0 {
+                    Some(delta.min(Self::MAX_CELL_VALUE as isize) as u8)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // Switch groove, or change the ticks per step
+    fn change_settings(&mut self, input: &InputState) {
+        if input.key_pressed(Key::ArrowRight) {
+            self.groove_id = self.groove_id.wrapping_add(1);
+            self.values = Self::fetch_groove(&self.tx, self.groove_id);
+        } else if input.key_pressed(Key::ArrowLeft) {
+            self.groove_id = self.groove_id.wrapping_sub(1);
+            self.values = Self::fetch_groove(&self.tx, self.groove_id);
+        } else if input.key_pressed(Key::ArrowUp) {
+            self.ticks_per_step = self.ticks_per_step.saturating_add(1);
+            self.send_ticks_per_step();
+        } else if input.key_pressed(Key::ArrowDown) {
+            self.ticks_per_step = self.ticks_per_step.saturating_sub(1).max(1);
+            self.send_ticks_per_step();
+        }
+    }
+
+    fn send_ticks_per_step(&self) {
+        self.tx
+            .send(Action::SetTicksPerStep {
+                ticks_per_step: self.ticks_per_step,
+            })
+            .unwrap();
+    }
+
+    fn change_selection(&mut self, input: &egui::InputState) {
+        let delta = if input.key_pressed(Key::ArrowUp) {
+            Some(1)
+        } else if input.key_pressed(Key::ArrowDown) {
+            Some(-1)
+        } else if input.key_pressed(Key::ArrowRight) {
+            Some(Self::BIG_CELL_INCREMENT)
+        } else if input.key_pressed(Key::ArrowLeft) {
+            Some(-Self::BIG_CELL_INCREMENT)
+        } else {
+            None
+        };
+
+        let Some(d) = delta else { return };
+
+        let row = self.selected_row;
+        let cell = &mut self.values[row];
+
+        let new_value = Self::apply_delta(*cell, d);
+        *cell = new_value;
+
+        self.tx
+            .send(Action::SetGrooveStep {
+                groove_id: self.groove_id,
+                index: row,
+                ticks: new_value,
+            })
+            .unwrap();
+    }
+}
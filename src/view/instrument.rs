@@ -0,0 +1,284 @@
+use eframe::egui::{Color32, InputState, Key, RichText, Ui};
+
+use super::cell_editor::{CellEdit, CellEditor};
+use super::keyboard::Keyboard;
+use super::view::{View, node_name};
+use crate::engine::audio::{NodeDef, NodeId, Patch};
+use crate::messaging::Action;
+use crate::types::InstrumentId;
+use crossbeam::channel::Sender;
+use crossbeam::channel::bounded;
+
+/*
+ * An instrument's patch, node by node, with the
+ * settings each node has:
+ *
+ *   Shift+Up/Down     change by a small step
+ *   Shift+PgUp/PgDn   change by a bigger step
+ *
+ * Changes are heard from the next note. The keyboard
+ * plays the instrument like a piano to try them out,
+ * with - and = moving the octave.
+ */
+
+pub struct Instrument {
+    tx: Sender<Action>,
+
+    instrument_id: InstrumentId,
+
+    // Selection state
+    selected_row: usize,
+    editor: CellEditor,
+
+    // Data
+    patch: Option<Patch>,
+    keyboard: Keyboard,
+}
+
+// A node setting and how far and fast it can be changed
+struct Field {
+    name: &'static str,
+    min: f32,
+    max: f32,
+    step: f32,
+    big_step: f32,
+}
+
+impl Field {
+    const fn new(name: &'static str, min: f32, max: f32, step: f32, big_step: f32) -> Self {
+        Self {
+            name,
+            min,
+            max,
+            step,
+            big_step,
+        }
+    }
+}
+
+impl View for Instrument {
+    fn handle_event(&mut self, input: &InputState) {
+        let edit = self.editor.handle_keys(input, self.selected_row, 0, false);
+
+        self.keyboard.release(input);
+        self.keyboard.set_instrument(self.instrument_id);
+
+        // Keys the keyboard plays are left alone
+        let (_, used) = self.keyboard.handle_keys(input);
+        if !used {
+            if let Some(edit) = edit {
+                self.edit_field(edit);
+            } else {
+                self.move_selection(input);
+            }
+        }
+    }
+
+    fn get_selection(&self) -> Option<u8> {
+        Some(self.instrument_id)
+    }
+
+    fn get_instrument(&self) -> Option<InstrumentId> {
+        Some(self.instrument_id)
+    }
+
+    fn refresh(&mut self) {
+        self.patch = Self::fetch_patch(&self.tx, self.instrument_id);
+    }
+
+    fn draw(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(
+                RichText::new("INSTRUMENT")
+                    .heading()
+                    .color(Color32::LIGHT_BLUE),
+            );
+        });
+        ui.add_space(20.0);
+
+        ui.label(
+            RichText::new(format!(
+                "ID {:02X}  OCTAVE {}",
+                self.instrument_id,
+                self.keyboard.get_base_note() / 12
+            ))
+            .size(12.0)
+            .color(Color32::GRAY),
+        );
+        ui.add_space(10.0);
+
+        let Some(patch) = &self.patch else {
+            ui.label(
+                RichText::new("NO INSTRUMENT")
+                    .size(12.0)
+                    .color(Color32::DARK_GRAY),
+            );
+            return;
+        };
+
+        egui::Grid::new("instrument_grid")
+            .num_columns(4)
+            .min_col_width(40.0)
+            .max_col_width(f32::INFINITY)
+            .show(ui, |ui| {
+                for (i, (node, field)) in Self::rows(patch).into_iter().enumerate() {
+                    let def = &patch.nodes[node];
+
+                    // The node's number and type, on its first row
+                    if field.is_none_or(|field| field == 0) {
+                        ui.label(RichText::new(format!("{:02X}", node)).color(Color32::GREEN));
+                        ui.label(RichText::new(node_name(def)).size(12.0));
+                    } else {
+                        ui.label("");
+                        ui.label("");
+                    }
+
+                    let (name, value) = match field {
+                        Some(field) => (
+                            Self::fields(def)[field].name,
+                            format!("{:.2}", Self::get_field(def, field)),
+                        ),
+                        None => ("", String::new()),
+                    };
+                    ui.label(RichText::new(name).size(12.0).color(Color32::GRAY));
+
+                    let text = RichText::new(value).size(12.0);
+                    if i == self.selected_row {
+                        ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                    } else {
+                        ui.label(text.color(Color32::WHITE));
+                    }
+
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl Instrument {
+    const LFO: &[Field] = &[
+        Field::new("FREQ", 0.0, 20.0, 0.05, 1.0),
+        Field::new("DEPTH", 0.0, 1000.0, 0.1, 10.0),
+        Field::new("OFFSET", -1000.0, 1000.0, 0.1, 10.0),
+    ];
+    // Times in seconds, sustain a level from 0.0 to 1.0
+    const ADSR: &[Field] = &[
+        Field::new("ATTACK", 0.0, 10.0, 0.01, 0.1),
+        Field::new("DECAY", 0.0, 10.0, 0.01, 0.1),
+        Field::new("SUSTAIN", 0.0, 1.0, 0.01, 0.1),
+        Field::new("RELEASE", 0.0, 10.0, 0.01, 0.1),
+    ];
+
+    pub fn new(tx: Sender<Action>, instrument_id: InstrumentId) -> Self {
+        Self {
+            patch: Self::fetch_patch(&tx, instrument_id),
+            keyboard: Keyboard::new(tx.clone()),
+            tx,
+            instrument_id,
+            selected_row: 0,
+            editor: CellEditor::new(),
+        }
+    }
+
+    fn fetch_patch(tx: &Sender<Action>, instrument_id: InstrumentId) -> Option<Patch> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetInstrument {
+            instrument_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        reply_rx.recv().unwrap()
+    }
+
+    // A row for each setting, or one for a node with none
+    fn rows(patch: &Patch) -> Vec<(NodeId, Option<usize>)> {
+        let mut rows = vec![];
+        for (node, def) in patch.nodes.iter().enumerate() {
+            match Self::fields(def).len() {
+                0 => rows.push((node, None)),
+                n => rows.extend((0..n).map(|field| (node, Some(field)))),
+            }
+        }
+        rows
+    }
+
+    // Sine is the only oscillator there is so far, and
+    // effects are set with the param FX command
+    fn fields(def: &NodeDef) -> &'static [Field] {
+        match def {
+            NodeDef::Lfo(_) => Self::LFO,
+            NodeDef::Adsr(_) => Self::ADSR,
+            _ => &[],
+        }
+    }
+
+    fn get_field(def: &NodeDef, field: usize) -> f32 {
+        match (def, field) {
+            (NodeDef::Lfo(lfo), 0) => lfo.freq,
+            (NodeDef::Lfo(lfo), 1) => lfo.depth,
+            (NodeDef::Lfo(lfo), 2) => lfo.offset,
+            (NodeDef::Adsr(adsr), 0) => adsr.attack,
+            (NodeDef::Adsr(adsr), 1) => adsr.decay,
+            (NodeDef::Adsr(adsr), 2) => adsr.sustain,
+            (NodeDef::Adsr(adsr), 3) => adsr.release,
+            _ => 0.0,
+        }
+    }
+
+    fn set_field(def: &mut NodeDef, field: usize, value: f32) {
+        match (def, field) {
+            (NodeDef::Lfo(lfo), 0) => lfo.freq = value,
+            (NodeDef::Lfo(lfo), 1) => lfo.depth = value,
+            (NodeDef::Lfo(lfo), 2) => lfo.offset = value,
+            (NodeDef::Adsr(adsr), 0) => adsr.attack = value,
+            (NodeDef::Adsr(adsr), 1) => adsr.decay = value,
+            (NodeDef::Adsr(adsr), 2) => adsr.sustain = value,
+            (NodeDef::Adsr(adsr), 3) => adsr.release = value,
+            _ => {}
+        }
+    }
+
+    fn move_selection(&mut self, input: &InputState) {
+        let rows = self
+            .patch
+            .as_ref()
+            .map_or(0, |patch| Self::rows(patch).len());
+        if input.key_pressed(Key::ArrowDown) {
+            self.selected_row = (self.selected_row + 1).min(rows.saturating_sub(1));
+        }
+        if input.key_pressed(Key::ArrowUp) {
+            self.selected_row = self.selected_row.saturating_sub(1);
+        }
+    }
+
+    fn edit_field(&mut self, edit: CellEdit) {
+        let Some(patch) = &mut self.patch else {
+            return;
+        };
+        let Some(&(node, Some(index))) = Self::rows(patch).get(self.selected_row) else {
+            return;
+        };
+
+        let def = &mut patch.nodes[node];
+        let field = &Self::fields(def)[index];
+        let delta = match edit {
+            CellEdit::Nudge(direction) => direction as f32 * field.step,
+            CellEdit::Jump(direction) => direction as f32 * field.big_step,
+            CellEdit::Set(_) => return,
+        };
+
+        // Keep to whole steps, so repeated nudges don't drift
+        let value = Self::get_field(def, index) + delta;
+        let value = ((value / field.step).round() * field.step).clamp(field.min, field.max);
+        Self::set_field(def, index, value);
+
+        self.tx
+            .send(Action::SetInstrumentNode {
+                instrument_id: self.instrument_id,
+                node,
+                def: def.clone(),
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use eframe::egui::{InputState, Key};
+
+use crate::messaging::Action;
+use crate::types::{AUDITION_TRACK, InstrumentId, MAX_NOTE, MAX_VELOCITY, Note};
+use crossbeam::channel::Sender;
+
+/*
+ * The computer keyboard as a piano, two octaves of
+ * it across the bottom two rows and the top two:
+ *
+ *    2 3   5 6 7          S D   G H J
+ *   Q W E R T Y U        Z X C V B N M
+ *
+ * with the bottom rows starting at the keyboard's
+ * octave and the top rows an octave up. - and =
+ * move the octave down and up.
+ *
+ * Keys held down are heard straight away on the
+ * audition track, apart from the song's, and let
+ * go when the key comes up.
+ */
+
+pub struct Keyboard {
+    tx: Sender<Action>,
+    octave: u8,
+    instrument: InstrumentId,
+    // The key playing, if one is held down
+    held: Option<Key>,
+}
+
+impl Keyboard {
+    const MAX_OCTAVE: u8 = 9;
+
+    // Semitones up from the keyboard's octave
+    const KEYS: [(Key, u8); 24] = [
+        (Key::Z, 0),
+        (Key::S, 1),
+        (Key::X, 2),
+        (Key::D, 3),
+        (Key::C, 4),
+        (Key::V, 5),
+        (Key::G, 6),
+        (Key::B, 7),
+        (Key::H, 8),
+        (Key::N, 9),
+        (Key::J, 10),
+        (Key::M, 11),
+        (Key::Q, 12),
+        (Key::Num2, 13),
+        (Key::W, 14),
+        (Key::Num3, 15),
+        (Key::E, 16),
+        (Key::R, 17),
+        (Key::Num5, 18),
+        (Key::T, 19),
+        (Key::Num6, 20),
+        (Key::Y, 21),
+        (Key::Num7, 22),
+        (Key::U, 23),
+    ];
+
+    pub fn new(tx: Sender<Action>) -> Self {
+        Self {
+            tx,
+            octave: 5,
+            instrument: 0,
+            held: None,
+        }
+    }
+
+    // The note on Z, from middle C to start with
+    pub fn get_base_note(&self) -> Note {
+        self.octave * 12
+    }
+
+    pub fn set_instrument(&mut self, instrument: InstrumentId) {
+        self.instrument = instrument;
+    }
+
+    // Let go of the note when its key comes up. Call
+    // every frame, wherever the cursor is.
+    pub fn release(&mut self, input: &InputState) {
+        if let Some(key) = self.held
+            && input.key_released(key)
+        {
+            self.held = None;
+            self.tx
+                .send(Action::NoteOff {
+                    track_id: AUDITION_TRACK,
+                })
+                .unwrap();
+        }
+    }
+
+    // Play a note or change octave. Returns the note if a
+    // piano key went down, and whether any key was used,
+    // so the view can leave it alone.
+    pub fn handle_keys(&mut self, input: &InputState) -> (Option<Note>, bool) {
+        if input.modifiers.command || input.modifiers.alt {
+            return (None, false);
+        }
+
+        if input.key_pressed(Key::Minus) {
+            self.octave = self.octave.saturating_sub(1);
+            return (None, true);
+        }
+        if input.key_pressed(Key::Equals) {
+            self.octave = (self.octave + 1).min(Self::MAX_OCTAVE);
+            return (None, true);
+        }
+
+        let Some(&(key, semitones)) = Self::KEYS.iter().find(|(key, _)| input.key_pressed(*key))
+        else {
+            return (None, false);
+        };
+
+        let note = self.octave * 12 + semitones;
+        if note > MAX_NOTE {
+            return (None, true);
+        }
+
+        self.held = Some(key);
+        self.tx
+            .send(Action::NoteOn {
+                track_id: AUDITION_TRACK,
+                instrument_id: self.instrument,
+                note,
+                velocity: MAX_VELOCITY,
+            })
+            .unwrap();
+        (Some(note), true)
+    }
+}
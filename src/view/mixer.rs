@@ -0,0 +1,279 @@
+use eframe::egui::{Color32, InputState, Key, Rect, RichText, Sense, Ui, Vec2, pos2, vec2};
+
+use super::cell_editor::{CellEdit, CellEditor};
+use super::view::View;
+use crate::engine::audio::{ChannelStrip, MixerMeters};
+use crate::messaging::Action;
+use crate::types::{MixerChannel, MixerSettings, NUM_TRACKS, TrackId};
+use crossbeam::channel::Sender;
+use crossbeam::channel::bounded;
+use std::sync::Arc;
+use std::time::Duration;
+
+/*
+ * A strip for each track and one for the master,
+ * with gain, pan, mute and solo, and meters showing
+ * the peak level as a line over the RMS level. The
+ * arrows move between strips and settings, and
+ * Shift+Up/Down and Shift+PgUp/PgDn change them, as
+ * in the other views. Mute and solo flip either way.
+ */
+
+pub struct Mixer {
+    tx: Sender<Action>,
+
+    // Selection state
+    selected_row: usize,
+    selected_col: usize,
+    editor: CellEditor,
+
+    // Data
+    settings: MixerSettings,
+    meters: Arc<MixerMeters>,
+}
+
+impl View for Mixer {
+    fn handle_event(&mut self, input: &InputState) {
+        let (row, col) = (self.selected_row, self.selected_col);
+        let edit = self.editor.handle_keys(input, row, col, false);
+
+        if let Some(edit) = edit {
+            self.edit_cell(edit);
+        } else {
+            self.move_selection(input);
+        }
+    }
+
+    fn get_selection(&self) -> Option<u8> {
+        None
+    }
+
+    fn refresh(&mut self) {
+        self.settings = Self::fetch_settings(&self.tx);
+    }
+
+    fn draw(&mut self, ui: &mut Ui) {
+        // The meters move whether or not anything else does
+        ui.ctx().request_repaint_after(Duration::from_millis(30));
+
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("MIXER").heading().color(Color32::LIGHT_BLUE));
+        });
+        ui.add_space(20.0);
+
+        egui::Grid::new("mixer_grid")
+            .num_columns(1 + Self::NUM_CHANNELS)
+            .min_col_width(40.0)
+            .max_col_width(f32::INFINITY)
+            .show(ui, |ui| {
+                // Header row
+                ui.label("");
+                for col in 0..Self::NUM_CHANNELS {
+                    let name = match Self::channel(col) {
+                        MixerChannel::Track(track_id) => format!("{:X}", track_id),
+                        MixerChannel::Master => "M".to_string(),
+                    };
+                    ui.label(RichText::new(name).color(Color32::GREEN));
+                }
+                ui.end_row();
+
+                // Meters
+                ui.label("");
+                for col in 0..Self::NUM_CHANNELS {
+                    let (peak, rms) = self.levels(Self::channel(col));
+                    let (rect, _) = ui.allocate_exact_size(Self::METER_SIZE, Sense::hover());
+                    let painter = ui.painter();
+                    painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+
+                    let x = rect.center().x;
+                    let rms_y = rect.bottom() - rect.height() * Self::meter_height(rms);
+                    painter.rect_filled(
+                        Rect::from_min_max(pos2(x - 6.0, rms_y), pos2(x + 6.0, rect.bottom())),
+                        0.0,
+                        Color32::GREEN,
+                    );
+
+                    let peak_y = rect.bottom() - rect.height() * Self::meter_height(peak);
+                    let color = if peak >= 1.0 {
+                        Color32::RED
+                    } else {
+                        Color32::YELLOW
+                    };
+                    painter.hline((x - 8.0)..=(x + 8.0), peak_y, (2.0, color));
+                }
+                ui.end_row();
+
+                // Settings
+                for (row, name) in Self::ROWS.iter().enumerate() {
+                    ui.label(RichText::new(*name).size(12.0).color(Color32::GRAY));
+                    for col in 0..Self::NUM_CHANNELS {
+                        let cell = self.render_cell(row, Self::channel(col));
+                        let text = RichText::new(cell).size(12.0);
+                        if row == self.selected_row && col == self.selected_col {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                        } else {
+                            ui.label(text.color(Color32::WHITE));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl Mixer {
+    // The tracks, then the master
+    const NUM_CHANNELS: usize = NUM_TRACKS + 1;
+    const ROWS: [&str; 4] = ["VOL", "PAN", "MUTE", "SOLO"];
+    const GAIN_ROW: usize = 0;
+    const PAN_ROW: usize = 1;
+    const MUTE_ROW: usize = 2;
+    const SOLO_ROW: usize = 3;
+
+    const GAIN_STEP: f32 = 0.5;
+    const BIG_GAIN_STEP: f32 = 6.0;
+    const PAN_STEP: f32 = 0.05;
+    const BIG_PAN_STEP: f32 = 0.25;
+    // The bottom of the meters, in dB
+    const METER_FLOOR: f32 = -60.0;
+    const METER_SIZE: Vec2 = vec2(40.0, 100.0);
+
+    pub fn new(tx: Sender<Action>) -> Self {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetMixerMeters { reply_to: reply_tx })
+            .unwrap();
+        let meters = reply_rx.recv().unwrap();
+
+        Self {
+            settings: Self::fetch_settings(&tx),
+            meters,
+            tx,
+            selected_row: 0,
+            selected_col: 0,
+            editor: CellEditor::new(),
+        }
+    }
+
+    fn fetch_settings(tx: &Sender<Action>) -> MixerSettings {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetMixerSettings { reply_to: reply_tx })
+            .unwrap();
+        reply_rx.recv().unwrap()
+    }
+
+    fn channel(col: usize) -> MixerChannel {
+        if col < NUM_TRACKS {
+            MixerChannel::Track(col as TrackId)
+        } else {
+            MixerChannel::Master
+        }
+    }
+
+    // The louder side's peak and RMS, linear
+    fn levels(&self, channel: MixerChannel) -> (f32, f32) {
+        let (peak, rms) = match channel {
+            MixerChannel::Track(track_id) => {
+                (self.meters.track(track_id), self.meters.track_rms(track_id))
+            }
+            MixerChannel::Master => (self.meters.master(), self.meters.master_rms()),
+        };
+        (peak.0.max(peak.1), rms.0.max(rms.1))
+    }
+
+    // How far up the meter a level reaches, from 0.0 to 1.0
+    fn meter_height(level: f32) -> f32 {
+        let db = 20.0 * level.max(1e-6).log10();
+        ((db - Self::METER_FLOOR) / -Self::METER_FLOOR).clamp(0.0, 1.0)
+    }
+
+    fn render_cell(&self, row: usize, channel: MixerChannel) -> String {
+        let strip = self.settings.channel(channel).copied().unwrap_or_default();
+        match row {
+            Self::GAIN_ROW => format!("{:+.1}", strip.gain_db),
+            Self::PAN_ROW => {
+                let pan = (strip.pan * 100.0).round() as i32;
+                match pan {
+                    0 => "C".to_string(),
+                    p if p < 0 => format!("L{}", -p),
+                    p => format!("R{}", p),
+                }
+            }
+            Self::MUTE_ROW => if strip.mute { "M" } else { "-" }.to_string(),
+            Self::SOLO_ROW if channel == MixerChannel::Master => "".to_string(),
+            _ => if strip.solo { "S" } else { "-" }.to_string(),
+        }
+    }
+
+    fn move_selection(&mut self, input: &InputState) {
+        if input.key_pressed(Key::ArrowDown) {
+            self.selected_row = (self.selected_row + 1).min(Self::ROWS.len() - 1);
+        }
+        if input.key_pressed(Key::ArrowUp) {
+            self.selected_row = self.selected_row.saturating_sub(1);
+        }
+        if input.key_pressed(Key::ArrowRight) {
+            self.selected_col = (self.selected_col + 1).min(Self::NUM_CHANNELS - 1);
+        }
+        if input.key_pressed(Key::ArrowLeft) {
+            self.selected_col = self.selected_col.saturating_sub(1);
+        }
+    }
+
+    fn edit_cell(&mut self, edit: CellEdit) {
+        let channel = Self::channel(self.selected_col);
+        let Some(strip) = self.settings.channel_mut(channel) else {
+            return;
+        };
+        let (direction, big) = match edit {
+            CellEdit::Nudge(direction) => (direction as f32, false),
+            CellEdit::Jump(direction) => (direction as f32, true),
+            CellEdit::Set(_) => return,
+        };
+
+        let action = match self.selected_row {
+            Self::GAIN_ROW => {
+                let step = if big {
+                    Self::BIG_GAIN_STEP
+                } else {
+                    Self::GAIN_STEP
+                };
+                strip.gain_db = (strip.gain_db + direction * step)
+                    .clamp(ChannelStrip::MIN_GAIN_DB, ChannelStrip::MAX_GAIN_DB);
+                Action::SetMixerGain {
+                    channel,
+                    gain_db: strip.gain_db,
+                }
+            }
+            Self::PAN_ROW => {
+                let step = if big {
+                    Self::BIG_PAN_STEP
+                } else {
+                    Self::PAN_STEP
+                };
+                strip.pan = (strip.pan + direction * step).clamp(-1.0, 1.0);
+                Action::SetMixerPan {
+                    channel,
+                    pan: strip.pan,
+                }
+            }
+            Self::MUTE_ROW => {
+                strip.mute = !strip.mute;
+                Action::SetMixerMute {
+                    channel,
+                    mute: strip.mute,
+                }
+            }
+            _ => {
+                let MixerChannel::Track(track_id) = channel else {
+                    return;
+                };
+                strip.solo = !strip.solo;
+                Action::SetMixerSolo {
+                    track_id,
+                    solo: strip.solo,
+                }
+            }
+        };
+        self.tx.send(action).unwrap();
+    }
+}
@@ -1,11 +1,24 @@
 mod app;
+mod cell_editor;
 mod chain;
+mod groove;
+mod instrument;
+mod keyboard;
+mod mixer;
+mod patch_editor;
 mod phrase;
+mod selection;
 mod song;
+mod table;
 mod view;
 
 pub use app::UiApp;
 pub use chain::Chain;
+pub use groove::Groove;
+pub use instrument::Instrument;
+pub use mixer::Mixer;
+pub use patch_editor::PatchEditor;
 pub use phrase::Phrase;
 pub use song::Song;
+pub use table::Table;
 pub use view::View;
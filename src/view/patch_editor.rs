@@ -0,0 +1,207 @@
+use eframe::egui::{
+    Align2, Color32, FontId, InputState, Rect, RichText, Sense, Stroke, Ui, Vec2, vec2,
+};
+
+use super::keyboard::Keyboard;
+use super::view::{View, node_name};
+use crate::engine::audio::{NodeDef, NodeId, ParamId, Patch, param};
+use crate::messaging::Action;
+use crate::types::InstrumentId;
+use crossbeam::channel::Sender;
+use crossbeam::channel::bounded;
+
+/*
+ * An instrument's patch drawn as boxes, one per node,
+ * in patch order. Boxes can be dragged about.
+ *
+ * LFOs and envelopes are drawn with an arrow to the
+ * node they modulate, labelled with the setting.
+ *
+ * There are no wires between nodes: the engine sums
+ * the sources and runs the effects in patch order,
+ * so there's nothing for a wire to change until it
+ * can route them. The keyboard plays the instrument
+ * like a piano meanwhile.
+ */
+
+pub struct PatchEditor {
+    tx: Sender<Action>,
+
+    instrument_id: InstrumentId,
+
+    // Data
+    patch: Option<Patch>,
+    // Where each node's box is, from the editor's top left
+    positions: Vec<Vec2>,
+    keyboard: Keyboard,
+}
+
+impl View for PatchEditor {
+    fn handle_event(&mut self, input: &InputState) {
+        self.keyboard.release(input);
+        self.keyboard.set_instrument(self.instrument_id);
+        self.keyboard.handle_keys(input);
+    }
+
+    fn get_selection(&self) -> Option<u8> {
+        Some(self.instrument_id)
+    }
+
+    fn get_instrument(&self) -> Option<InstrumentId> {
+        Some(self.instrument_id)
+    }
+
+    fn refresh(&mut self) {
+        self.patch = Self::fetch_patch(&self.tx, self.instrument_id);
+        let nodes = self.patch.as_ref().map_or(0, |patch| patch.nodes.len());
+        if nodes != self.positions.len() {
+            self.positions = Self::layout(nodes);
+        }
+    }
+
+    fn draw(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("PATCH").heading().color(Color32::LIGHT_BLUE));
+        });
+        ui.add_space(20.0);
+
+        ui.label(
+            RichText::new(format!("ID {:02X}", self.instrument_id))
+                .size(12.0)
+                .color(Color32::GRAY),
+        );
+        ui.add_space(10.0);
+
+        let Some(patch) = &self.patch else {
+            ui.label(
+                RichText::new("NO INSTRUMENT")
+                    .size(12.0)
+                    .color(Color32::DARK_GRAY),
+            );
+            return;
+        };
+
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+        let origin = response.rect.min;
+        let boxes: Vec<Rect> = self
+            .positions
+            .iter()
+            .map(|position| Rect::from_min_size(origin + *position, Self::BOX_SIZE))
+            .collect();
+        let font = FontId::proportional(8.0);
+
+        // Arrows go under the boxes
+        for (node, def) in patch.nodes.iter().enumerate() {
+            if let Some((target, param)) = Self::modulation(def)
+                && let Some(to) = boxes.get(target)
+            {
+                let from = boxes[node].center_bottom();
+                let to = to.center_top();
+                painter.arrow(from, to - from, Stroke::new(1.5, Color32::YELLOW));
+                painter.text(
+                    from.lerp(to, 0.5),
+                    Align2::LEFT_CENTER,
+                    Self::param_name(param),
+                    font.clone(),
+                    Color32::YELLOW,
+                );
+            }
+        }
+
+        for (node, rect) in boxes.iter().enumerate() {
+            let body = ui.interact(*rect, response.id.with(("node", node)), Sense::drag());
+            if body.dragged() {
+                self.positions[node] += body.drag_delta();
+            }
+
+            painter.rect_filled(*rect, 4.0, Color32::DARK_GRAY);
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                format!("{:02X} {}", node, node_name(&patch.nodes[node])),
+                font.clone(),
+                Color32::WHITE,
+            );
+        }
+    }
+}
+
+impl PatchEditor {
+    const BOX_SIZE: Vec2 = vec2(120.0, 40.0);
+    const BOXES_PER_ROW: usize = 4;
+
+    pub fn new(tx: Sender<Action>, instrument_id: InstrumentId) -> Self {
+        let patch = Self::fetch_patch(&tx, instrument_id);
+        let nodes = patch.as_ref().map_or(0, |patch| patch.nodes.len());
+
+        Self {
+            keyboard: Keyboard::new(tx.clone()),
+            tx,
+            instrument_id,
+            patch,
+            positions: Self::layout(nodes),
+        }
+    }
+
+    fn fetch_patch(tx: &Sender<Action>, instrument_id: InstrumentId) -> Option<Patch> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetInstrument {
+            instrument_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        reply_rx.recv().unwrap()
+    }
+
+    // Boxes in rows, in patch order, with room between
+    // them for the modulation arrows
+    fn layout(nodes: usize) -> Vec<Vec2> {
+        (0..nodes)
+            .map(|node| {
+                let col = (node % Self::BOXES_PER_ROW) as f32;
+                let row = (node / Self::BOXES_PER_ROW) as f32;
+                vec2(
+                    10.0 + col * (Self::BOX_SIZE.x + 60.0),
+                    10.0 + row * (Self::BOX_SIZE.y + 60.0),
+                )
+            })
+            .collect()
+    }
+
+    // The node and setting a modulator drives, if it is one
+    fn modulation(def: &NodeDef) -> Option<(NodeId, ParamId)> {
+        match def {
+            NodeDef::Lfo(lfo) => Some((lfo.target_node, lfo.target_param)),
+            NodeDef::Adsr(adsr) => Some((adsr.target_node, adsr.target_param)),
+            _ => None,
+        }
+    }
+
+    fn param_name(param: ParamId) -> &'static str {
+        match param {
+            param::AMPLITUDE => "AMP",
+            param::FREQUENCY => "FREQ",
+            param::MIX => "MIX",
+            param::DRIVE => "DRIVE",
+            param::BITS => "BITS",
+            param::DOWNSAMPLE => "DOWNSAMPLE",
+            param::RATE => "RATE",
+            param::DEPTH => "DEPTH",
+            param::DELAY => "DELAY",
+            param::FEEDBACK => "FEEDBACK",
+            param::LOW_FREQ => "LOW FREQ",
+            param::LOW_GAIN => "LOW GAIN",
+            param::MID_FREQ => "MID FREQ",
+            param::MID_GAIN => "MID GAIN",
+            param::HIGH_FREQ => "HIGH FREQ",
+            param::HIGH_GAIN => "HIGH GAIN",
+            param::THRESHOLD => "THRESHOLD",
+            param::RATIO => "RATIO",
+            param::ATTACK => "ATTACK",
+            param::RELEASE => "RELEASE",
+            param::MAKEUP => "MAKEUP",
+            _ => "?",
+        }
+    }
+}
@@ -1,32 +1,97 @@
 use eframe::egui::{Color32, InputState, Key, RichText, Ui};
 
-use super::view::View;
+use super::cell_editor::{CellEdit, CellEditor};
+use super::keyboard::Keyboard;
+use super::selection::Selection;
+use super::view::{View, check_grid, get_playhead, playhead_label, scroll_to};
+use crate::engine::Playhead;
 use crate::messaging::Action;
-use crate::types::{ChainId, NUM_STEPS_PER_PHRASE, Note, PhraseId, Step};
+use crate::types::{
+    Accidentals, ChainId, Diagnostic, Fx, FxKind, Grid, GrooveId, InstrumentId, MAX_NOTE,
+    MAX_VELOCITY, NUM_FX_COLUMNS, NUM_TRACKS, Notation, Note, PhraseId, Step, StepKind,
+};
 use crossbeam::channel::Sender;
 use crossbeam::channel::bounded;
 use std::borrow::Cow;
+use std::sync::Arc;
+
+/*
+ * Notes are shown by name, e.g. C-4 or F#3, in the
+ * user's notation. Ctrl+O and Ctrl+K make the step
+ * a note off or a kill; Ctrl+N switches between
+ * sharps and flats and Ctrl+Shift+N moves middle C
+ * to the next octave number.
+ *
+ * In the note column the keyboard plays like a
+ * piano, writing each note and moving the cursor
+ * down by the edit step, which [ and ] change.
+ */
 
 pub struct Phrase {
     tx: Sender<Action>,
 
     phrase_id: PhraseId,
+    visible_rows: usize,
 
     // Selection state
     selected_row: usize,
+    selected_col: usize,
+    min_row: usize,
+    selection: Selection,
+    editor: CellEditor,
 
     // Data
     values: Vec<Option<Step>>,
+    // The phrase's own groove, if it doesn't use the track's
+    groove: Option<GrooveId>,
+    // Steps that play; the rest are greyed out
+    length: usize,
+    // Problems the song checker found in the phrase
+    diagnostics: Vec<Diagnostic>,
+    playhead: Arc<Playhead>,
+    notation: Notation,
+    keyboard: Keyboard,
+    // Rows to move down after entering a note
+    edit_step: usize,
 }
 
 impl View for Phrase {
     fn handle_event(&mut self, input: &InputState) {
         let shift_down = input.modifiers.shift;
 
-        if input.key_pressed(Key::Space) {
+        let grid = Grid::Phrase(self.phrase_id);
+        let (row, col) = (self.selected_row, self.selected_col);
+        // Notes come from the keyboard rather than hex
+        let edit = self
+            .editor
+            .handle_keys(input, row, col, col != Self::NOTE_COLUMN);
+
+        self.keyboard.release(input);
+        self.keyboard.set_instrument(self.current_instrument());
+
+        if self.selection.handle_keys(input, &self.tx, grid, row, col) {
+            self.reload();
+            self.check();
+        } else if col == Self::NOTE_COLUMN
+            && let (note, true) = self.keyboard.handle_keys(input)
+        {
+            if let Some(note) = note {
+                self.enter_note(note);
+            }
+        } else if input.key_pressed(Key::OpenBracket) {
+            self.edit_step = self.edit_step.saturating_sub(1);
+        } else if input.key_pressed(Key::CloseBracket) {
+            self.edit_step = (self.edit_step + 1).min(Self::MAX_EDIT_STEP);
+        } else if input.key_pressed(Key::Space) {
             self.tx.send(Action::PlayPhrase(self.phrase_id)).unwrap();
-        } else if shift_down {
-            self.change_selection(input);
+        } else if input.modifiers.command && input.key_pressed(Key::N) {
+            self.change_notation(shift_down);
+        } else if input.modifiers.command && shift_down {
+            self.change_length(input);
+        } else if let Some(edit) = edit {
+            self.edit_cell(edit);
+        } else if input.modifiers.command {
+            self.change_command(input);
         } else {
             self.move_selection(input);
         }
@@ -36,15 +101,55 @@ impl View for Phrase {
         Some(self.selected_row as u8)
     }
 
+    fn get_instrument(&self) -> Option<InstrumentId> {
+        Some(self.current_instrument())
+    }
+
+    fn refresh(&mut self) {
+        self.reload();
+        self.groove = Self::fetch_groove(&self.tx, self.phrase_id);
+        self.length = Self::fetch_length(&self.tx, self.phrase_id);
+        self.notation = Self::fetch_notation(&self.tx);
+        self.check();
+    }
+
+    fn follow(&mut self) {
+        let Some(row) = (0..NUM_TRACKS)
+            .filter_map(|track_id| self.playhead.track(track_id))
+            .find(|position| position.phrase == self.phrase_id)
+            .map(|position| position.step)
+        else {
+            return;
+        };
+        if row < self.min_row || row >= self.min_row + self.visible_rows {
+            self.min_row = row.min(self.values.len().saturating_sub(self.visible_rows));
+        }
+    }
+
     fn draw(&mut self, ui: &mut Ui) {
+        let max_row = (self.min_row + self.visible_rows).min(self.values.len());
+
         ui.vertical_centered(|ui| {
             ui.label(RichText::new("PHRASE").heading().color(Color32::LIGHT_BLUE));
         });
         ui.add_space(20.0);
 
+        ui.label(
+            RichText::new(format!(
+                "GROOVE {}  LENGTH {:02X}  KEYS {}  STEP {:X}",
+                self.render_cell(self.groove),
+                self.length,
+                self.notation.name(self.keyboard.get_base_note()),
+                self.edit_step
+            ))
+            .size(12.0)
+            .color(Color32::LIGHT_BLUE),
+        );
+        ui.add_space(10.0);
+
         // Grid for table layout
         egui::Grid::new("chain_grid")
-            .num_columns(3)
+            .num_columns(2 + Self::NUM_COLUMNS)
             .min_col_width(40.0)
             .max_col_width(f32::INFINITY)
             .show(ui, |ui| {
@@ -52,20 +157,71 @@ impl View for Phrase {
                 ui.label(""); // Empty ">" column
                 ui.label(""); // Empty row label column
                 ui.label(format!("{:0x}", self.phrase_id));
+                ui.label("I");
+                ui.label("V");
+                for _ in 0..NUM_FX_COLUMNS {
+                    ui.label("FX");
+                }
                 ui.end_row();
 
-                // Body rows
-                for i in 0..NUM_STEPS_PER_PHRASE {
-                    ui.label(RichText::new(" ").color(Color32::YELLOW));
+                // Body rows, a window onto as many as
+                // the song's phrases have
+                let playing: Vec<_> = (0..NUM_TRACKS)
+                    .filter_map(|track_id| self.playhead.track(track_id))
+                    .filter(|position| position.phrase == self.phrase_id)
+                    .map(|position| position.step)
+                    .collect();
+                for i in self.min_row..max_row {
+                    ui.label(playhead_label(playing.contains(&i)));
                     ui.label(RichText::new(format!("{:02X}", i)).color(Color32::GREEN));
 
-                    let cell = self.render_cell(self.values[i]);
-                    let is_selected = i == self.selected_row;
-                    let text = RichText::new(cell).size(12.0);
-                    if is_selected {
-                        ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                    let step = self.values[i];
+                    let mut cells = vec![
+                        self.render_note(step.map(|s| s.kind)),
+                        self.render_cell(step.and_then(|s| s.instrument)),
+                        self.render_cell(step.and_then(|s| s.velocity)),
+                    ];
+                    for fx in 0..NUM_FX_COLUMNS {
+                        cells.push(self.render_fx(step.and_then(|s| s.fx[fx])));
+                    }
+
+                    // Steps past the phrase's length don't play
+                    let color = if i < self.length {
+                        Color32::WHITE
                     } else {
-                        ui.label(text.color(Color32::WHITE));
+                        Color32::DARK_GRAY
+                    };
+
+                    for (col, cell) in cells.into_iter().enumerate() {
+                        let pending = self.editor.pending(i, col);
+                        let cell = match pending {
+                            Some(digit) => Cow::Owned(CellEditor::render_pending(digit)),
+                            None => cell,
+                        };
+                        let is_selected = i == self.selected_row && col == self.selected_col;
+                        let text = RichText::new(cell).size(12.0);
+                        if pending.is_some() {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::ORANGE));
+                        } else if is_selected {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                        } else if self.selection.contains(
+                            i,
+                            col,
+                            (self.selected_row, self.selected_col),
+                        ) {
+                            ui.label(
+                                text.color(Color32::WHITE)
+                                    .background_color(Color32::DARK_BLUE),
+                            );
+                        } else if self
+                            .diagnostics
+                            .iter()
+                            .any(|d| d.covers(Grid::Phrase(self.phrase_id), i, col))
+                        {
+                            ui.label(text.color(Color32::RED));
+                        } else {
+                            ui.label(text.color(color));
+                        }
                     }
 
                     ui.end_row();
@@ -76,8 +232,19 @@ impl View for Phrase {
 
 impl Phrase {
     const MAX_CELL_VALUE: usize = 0xFF;
+    const MAX_VELOCITY_VALUE: usize = MAX_VELOCITY as usize;
+
+    // Note, instrument, velocity and the FX commands
+    const NUM_COLUMNS: usize = 3 + NUM_FX_COLUMNS;
+    const NOTE_COLUMN: usize = 0;
+    const INSTRUMENT_COLUMN: usize = 1;
+    const VELOCITY_COLUMN: usize = 2;
+    const FIRST_FX_COLUMN: usize = 3;
     const BIG_CELL_INCREMENT: isize = 0x10;
+    const OCTAVE: isize = 12;
+    const MAX_EDIT_STEP: usize = 0x10;
     const EMPTY_CELL_DISPLAY: &str = "--";
+    const EMPTY_FX_DISPLAY: &str = "---";
 
     pub fn new(tx: Sender<Action>, phrase_id: ChainId) -> Self {
         let (reply_tx, reply_rx) = bounded(1); // one-shot channel
@@ -89,89 +256,325 @@ impl Phrase {
 
         let phrase_data = reply_rx.recv().unwrap();
 
+        let groove = Self::fetch_groove(&tx, phrase_id);
+        let length = Self::fetch_length(&tx, phrase_id);
+        let notation = Self::fetch_notation(&tx);
+
+        let diagnostics = check_grid(&tx, Grid::Phrase(phrase_id));
+        let playhead = get_playhead(&tx);
+
         Self {
+            keyboard: Keyboard::new(tx.clone()),
+            edit_step: 1,
             tx,
+            diagnostics,
+            playhead,
             phrase_id,
             values: phrase_data,
+            groove,
+            length,
+            notation,
             selected_row: 0,
+            selected_col: 0,
+            visible_rows: 16,
+            min_row: 0,
+            selection: Selection::new(),
+            editor: CellEditor::new(),
         }
     }
 
+    fn fetch_groove(tx: &Sender<Action>, phrase_id: PhraseId) -> Option<GrooveId> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseGroove {
+            phrase_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        reply_rx.recv().unwrap()
+    }
+
+    fn fetch_length(tx: &Sender<Action>, phrase_id: PhraseId) -> usize {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetPhraseLength {
+            phrase_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+        reply_rx.recv().unwrap()
+    }
+
+    fn fetch_notation(tx: &Sender<Action>) -> Notation {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetNotation { reply_to: reply_tx }).unwrap();
+        reply_rx.recv().unwrap()
+    }
+
+    // Fetch the steps again after a block edit
+    fn reload(&mut self) {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::GetPhraseData {
+                phrase_id: self.phrase_id,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+        self.values = reply_rx.recv().unwrap();
+
+        // The phrase may have got shorter meanwhile
+        self.selected_row = self.selected_row.min(self.values.len().saturating_sub(1));
+        self.min_row = scroll_to(self.selected_row, self.min_row, self.visible_rows);
+    }
+
+    // The instrument the step at the cursor plays,
+    // carried on from the steps above if it's empty
+    fn current_instrument(&self) -> InstrumentId {
+        self.values[..=self.selected_row]
+            .iter()
+            .rev()
+            .find_map(|step| step.and_then(|s| s.instrument))
+            .unwrap_or(0)
+    }
+
+    // Write a note from the keyboard and move on
+    fn enter_note(&mut self, note: Note) {
+        self.editor.remember(Self::NOTE_COLUMN, note);
+        let step = self.values[self.selected_row];
+        self.set_step(Some(Step {
+            kind: StepKind::Note(note),
+            ..step.unwrap_or(Step::new(note, 0))
+        }));
+        self.selected_row =
+            (self.selected_row + self.edit_step).min(self.values.len().saturating_sub(1));
+        self.min_row = scroll_to(self.selected_row, self.min_row, self.visible_rows);
+    }
+
+    // Look for problems again after an edit, which
+    // can fix them or make new ones
+    fn check(&mut self) {
+        self.diagnostics = check_grid(&self.tx, Grid::Phrase(self.phrase_id));
+    }
+
     fn move_selection(&mut self, input: &InputState) {
+        self.selection
+            .update(input, self.selected_row, self.selected_col);
         if input.key_pressed(Key::ArrowDown) {
-            self.selected_row = (self.selected_row + 1).min(NUM_STEPS_PER_PHRASE - 1);
+            self.selected_row = (self.selected_row + 1).min(self.values.len().saturating_sub(1));
         }
         if input.key_pressed(Key::ArrowUp) {
             self.selected_row = self.selected_row.saturating_sub(1);
         }
+        if input.key_pressed(Key::ArrowRight) {
+            self.selected_col = (self.selected_col + 1).min(Self::NUM_COLUMNS - 1);
+        }
+        if input.key_pressed(Key::ArrowLeft) {
+            self.selected_col = self.selected_col.saturating_sub(1);
+        }
+        self.min_row = scroll_to(self.selected_row, self.min_row, self.visible_rows);
     }
 
-    fn render_cell(&self, value: Option<Step>) -> Cow<'_, str> {
+    fn render_cell(&self, value: Option<u8>) -> Cow<'_, str> {
         match value {
             None => Cow::Borrowed(Self::EMPTY_CELL_DISPLAY),
-            Some(v) => Cow::Owned(format!("{:02X}", v.note)),
+            Some(v) => Cow::Owned(format!("{:02X}", v)),
         }
     }
 
-    fn apply_delta(value: Option<Step>, delta: isize) -> Option<Step> {
-        match value {
-            Some(v) => {
-                let new = v.note as isize + delta;
-                if new < 0 {
-                    None
-                } else {
-                    Some(Step {
-                        note: new.min(Self::MAX_CELL_VALUE as isize) as Note,
-                        len: 0,
-                    })
-                }
-            }
-            None => {
-                if delta == 1 {
-                    return Some(Step { note: 0, len: 0 });
-                } else if delta > 0 {
-                    Some(Step {
-                        note: delta.min(Self::MAX_CELL_VALUE as isize) as Note,
-                        len: 0,
-                    })
-                } else {
-                    None
-                }
-            }
+    fn render_note(&self, kind: Option<StepKind>) -> Cow<'_, str> {
+        match kind {
+            None => Cow::Borrowed(Self::EMPTY_FX_DISPLAY),
+            Some(StepKind::Note(note)) => Cow::Owned(self.notation.name(note)),
+            Some(StepKind::Off) => Cow::Borrowed("OFF"),
+            Some(StepKind::Kill) => Cow::Borrowed("KIL"),
+        }
+    }
+
+    // Command letter then hex value, e.g. C37
+    fn render_fx(&self, fx: Option<Fx>) -> Cow<'_, str> {
+        match fx {
+            None => Cow::Borrowed(Self::EMPTY_FX_DISPLAY),
+            Some(fx) => Cow::Owned(format!("{}{:02X}", fx.kind.letter(), fx.value)),
+        }
+    }
+
+    // Change one column of a step. Clearing the note
+    // clears the step; the other columns need a note.
+    // Changing an off or kill starts again from no note.
+    // Notes jump by octaves rather than 16.
+    fn edit_step(step: Option<Step>, col: usize, edit: CellEdit) -> Option<Step> {
+        let jump = Self::BIG_CELL_INCREMENT;
+        match col {
+            Self::NOTE_COLUMN => edit
+                .apply(step.and_then(|s| s.note()), Self::OCTAVE, MAX_NOTE as usize)
+                .map(|note| Step {
+                    kind: StepKind::Note(note),
+                    ..step.unwrap_or(Step::new(note, 0))
+                }),
+            Self::INSTRUMENT_COLUMN => step.map(|s| Step {
+                instrument: edit.apply(s.instrument, jump, Self::MAX_CELL_VALUE),
+                ..s
+            }),
+            Self::VELOCITY_COLUMN => step.map(|s| Step {
+                velocity: edit.apply(s.velocity, jump, Self::MAX_VELOCITY_VALUE),
+                ..s
+            }),
+            _ => step.map(|mut s| {
+                let fx = &mut s.fx[col - Self::FIRST_FX_COLUMN];
+                let kind = fx.map_or(FxKind::ALL[0], |fx| fx.kind);
+                *fx = edit
+                    .apply(fx.map(|fx| fx.value), jump, Self::MAX_CELL_VALUE)
+                    .map(|value| Fx::new(kind, value));
+                s
+            }),
+        }
+    }
+
+    // The value in one column of a step
+    fn cell_value(step: Option<Step>, col: usize) -> Option<u8> {
+        let step = step?;
+        match col {
+            Self::NOTE_COLUMN => step.note(),
+            Self::INSTRUMENT_COLUMN => step.instrument,
+            Self::VELOCITY_COLUMN => step.velocity,
+            _ => step.fx[col - Self::FIRST_FX_COLUMN].map(|fx| fx.value),
         }
     }
 
-    fn change_selection(&mut self, input: &egui::InputState) {
-        // Figure out how much to increase or decrease
-        // the note by.
+    // Change which command an FX column holds, with
+    // Left/Right the phrase's groove, or with O or K
+    // make the step a note off or kill
+    fn change_command(&mut self, input: &InputState) {
+        let kind = if input.key_pressed(Key::O) {
+            Some(StepKind::Off)
+        } else if input.key_pressed(Key::K) {
+            Some(StepKind::Kill)
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            let step = self.values[self.selected_row];
+            self.set_step(Some(Step {
+                kind,
+                ..step.unwrap_or(Step::of_kind(kind))
+            }));
+            return;
+        }
+
         let delta = if input.key_pressed(Key::ArrowUp) {
-            Some(1)
+            1
         } else if input.key_pressed(Key::ArrowDown) {
-            Some(-1)
+            -1
         } else if input.key_pressed(Key::ArrowRight) {
-            Some(Self::BIG_CELL_INCREMENT)
+            self.change_groove(1);
+            return;
         } else if input.key_pressed(Key::ArrowLeft) {
-            Some(-Self::BIG_CELL_INCREMENT)
+            self.change_groove(-1);
+            return;
         } else {
-            None
+            return;
         };
 
-        let Some(d) = delta else { return };
+        if self.selected_col < Self::FIRST_FX_COLUMN {
+            return;
+        }
 
-        let row = self.selected_row;
-        let cell = &mut self.values[row];
+        let new_value = self.values[self.selected_row].map(|mut s| {
+            let fx = &mut s.fx[self.selected_col - Self::FIRST_FX_COLUMN];
+            *fx = Some(match fx {
+                Some(fx) => Fx::new(fx.kind.cycle(delta), fx.value),
+                None => Fx::new(FxKind::ALL[0], 0),
+            });
+            s
+        });
 
-        // cell is an Option<Step>
+        self.set_step(new_value);
+    }
 
-        let new_value = Self::apply_delta(*cell, d);
-        *cell = new_value;
+    fn change_groove(&mut self, delta: isize) {
+        self.groove = CellEdit::Nudge(delta).apply(
+            self.groove,
+            Self::BIG_CELL_INCREMENT,
+            Self::MAX_CELL_VALUE,
+        );
+
+        self.tx
+            .send(Action::SetPhraseGroove {
+                phrase_id: self.phrase_id,
+                groove_id: self.groove,
+            })
+            .unwrap();
+    }
+
+    // Ctrl+Shift+Up/Down lengthens or shortens the phrase,
+    // from a single step up to the song's full length
+    fn change_length(&mut self, input: &InputState) {
+        let length = if input.key_pressed(Key::ArrowUp) {
+            self.length + 1
+        } else if input.key_pressed(Key::ArrowDown) {
+            self.length - 1
+        } else {
+            return;
+        };
+
+        if !(1..=self.values.len()).contains(&length) {
+            return;
+        }
+        self.length = length;
+
+        self.tx
+            .send(Action::SetPhraseLength {
+                phrase_id: self.phrase_id,
+                length,
+            })
+            .unwrap();
+        // Hops past the new end are out of range
+        self.check();
+    }
+
+    // Ctrl+N flips between sharps and flats, and with
+    // Shift moves middle C to the next octave number
+    fn change_notation(&mut self, shift_down: bool) {
+        let notation = &mut self.notation;
+        if shift_down {
+            let octaves = Notation::MIDDLE_C_OCTAVES;
+            let index = octaves
+                .iter()
+                .position(|&octave| octave == notation.middle_c_octave)
+                .map_or(0, |index| (index + 1) % octaves.len());
+            notation.middle_c_octave = octaves[index];
+        } else {
+            notation.accidentals = match notation.accidentals {
+                Accidentals::Sharps => Accidentals::Flats,
+                Accidentals::Flats => Accidentals::Sharps,
+            };
+        }
+
+        self.tx
+            .send(Action::SetNotation {
+                notation: self.notation,
+            })
+            .unwrap();
+    }
+
+    fn set_step(&mut self, step: Option<Step>) {
+        let row = self.selected_row;
+        self.values[row] = step;
 
         self.tx
             .send(Action::SetPhraseStep {
                 phrase_id: self.phrase_id,
-                index: row as usize,
-                step: new_value,
+                index: row,
+                step,
             })
             .unwrap();
+        self.check();
+    }
+
+    fn edit_cell(&mut self, edit: CellEdit) {
+        let col = self.selected_col;
+        let step = Self::edit_step(self.values[self.selected_row], col, edit);
+        if let Some(value) = Self::cell_value(step, col) {
+            self.editor.remember(col, value);
+        }
+        self.set_step(step);
     }
 }
@@ -0,0 +1,117 @@
+use eframe::egui::{InputState, Key};
+
+use crate::messaging::Action;
+use crate::types::{Block, Grid};
+use crossbeam::channel::Sender;
+
+/*
+ * Block selection, shared by the song, chain and
+ * phrase views. Holding Alt while moving the cursor
+ * marks out a block from where it started; moving
+ * without Alt lets it go.
+ *
+ *   Ctrl+C        copy
+ *   Ctrl+X        cut
+ *   Ctrl+V        paste at the cursor
+ *   Ctrl+Shift+V  paste, moving the rows below down
+ *   Delete        clear
+ *   Ctrl+Z        undo the last cut, paste or clear
+ *
+ * With no block marked they work on the cell under
+ * the cursor. The clipboard is in the engine, so
+ * what's copied in one view can be pasted in another.
+ */
+
+pub struct Selection {
+    // Where the block was started
+    anchor: Option<(usize, usize)>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self { anchor: None }
+    }
+
+    // Call with the cursor before the arrow keys move it
+    pub fn update(&mut self, input: &InputState, row: usize, col: usize) {
+        let moving = [
+            Key::ArrowUp,
+            Key::ArrowDown,
+            Key::ArrowLeft,
+            Key::ArrowRight,
+        ]
+        .into_iter()
+        .any(|key| input.key_pressed(key));
+        if !moving {
+            return;
+        }
+
+        if input.modifiers.alt {
+            self.anchor.get_or_insert((row, col));
+        } else {
+            self.anchor = None;
+        }
+    }
+
+    // The block from where it was started to the cursor
+    pub fn block(&self, grid: Grid, row: usize, col: usize) -> Block {
+        let (anchor_row, anchor_col) = self.anchor.unwrap_or((row, col));
+        Block {
+            grid,
+            row: row.min(anchor_row),
+            col: col.min(anchor_col),
+            rows: row.abs_diff(anchor_row) + 1,
+            cols: col.abs_diff(anchor_col) + 1,
+        }
+    }
+
+    // Whether a cell is in the marked block
+    pub fn contains(&self, row: usize, col: usize, cursor: (usize, usize)) -> bool {
+        if self.anchor.is_none() {
+            return false;
+        }
+
+        let block = self.block(Grid::Song, cursor.0, cursor.1);
+        (block.row..block.row + block.rows).contains(&row)
+            && (block.col..block.col + block.cols).contains(&col)
+    }
+
+    // Send the edit for a block key, if one was pressed.
+    // Returns true if it was, so the view can reload.
+    pub fn handle_keys(
+        &mut self,
+        input: &InputState,
+        tx: &Sender<Action>,
+        grid: Grid,
+        row: usize,
+        col: usize,
+    ) -> bool {
+        let block = self.block(grid, row, col);
+        let command = input.modifiers.command;
+
+        let action = if command && input.key_pressed(Key::C) {
+            Action::CopyBlock { block }
+        } else if command && input.key_pressed(Key::X) {
+            Action::CutBlock { block }
+        } else if command && input.key_pressed(Key::V) {
+            Action::PasteBlock {
+                grid,
+                row,
+                col,
+                insert: input.modifiers.shift,
+            }
+        } else if command && input.key_pressed(Key::Z) {
+            Action::Undo
+        } else if input.key_pressed(Key::Delete) {
+            Action::ClearBlock { block }
+        } else {
+            return false;
+        };
+
+        if !matches!(action, Action::CopyBlock { .. }) {
+            self.anchor = None;
+        }
+        tx.send(action).unwrap();
+        true
+    }
+}
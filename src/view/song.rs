@@ -1,13 +1,14 @@
 use eframe::egui::{Color32, InputState, Key, RichText, Ui};
 
-use super::view::View;
+use super::cell_editor::{CellEdit, CellEditor};
+use super::selection::Selection;
+use super::view::{View, check_grid, get_playhead, playhead_label};
+use crate::engine::Playhead;
 use crate::messaging::Action;
-use crate::types::ChainId;
-use crossbeam::channel::Sender;
+use crate::types::{ChainId, Diagnostic, Grid, GrooveId, NUM_PATTERNS, NUM_TRACKS};
+use crossbeam::channel::{Sender, bounded};
 use std::borrow::Cow;
-
-const ROWS: usize = 256;
-const COLS: usize = 8;
+use std::sync::Arc;
 
 pub struct Song {
     tx: Sender<Action>,
@@ -17,19 +18,53 @@ pub struct Song {
     selected_row: usize,
     selected_col: usize,
     min_row: usize,
+    selection: Selection,
+    editor: CellEditor,
 
-    // Data
+    // Data, with a column for each of the song's tracks
+    tracks: usize,
     values: Vec<Vec<Option<ChainId>>>,
+    grooves: [GrooveId; NUM_TRACKS],
+    // What the last usage check or cleanup found
+    status: String,
+    // Problems the song checker found in the patterns
+    diagnostics: Vec<Diagnostic>,
+    playhead: Arc<Playhead>,
 }
 
 impl View for Song {
     fn handle_event(&mut self, input: &InputState) {
         let shift_down = input.modifiers.shift;
 
-        if input.key_pressed(Key::Enter) {
+        let (row, col) = (self.selected_row, self.selected_col);
+        let edit = self.editor.handle_keys(input, row, col, true);
+
+        if self
+            .selection
+            .handle_keys(input, &self.tx, Grid::Song, row, col)
+        {
+            self.reload();
+            self.check();
+        } else if input.key_pressed(Key::Enter) {
             println!("Enter");
-        } else if shift_down {
-            self.change_selection(input);
+        } else if input.key_pressed(Key::Space) {
+            self.tx
+                .send(Action::PlaySong(self.selected_row as u8))
+                .unwrap();
+        } else if input.modifiers.command && input.key_pressed(Key::E) {
+            self.check_song();
+        } else if input.modifiers.command && input.key_pressed(Key::U) {
+            self.check_usage();
+        } else if input.modifiers.command && input.key_pressed(Key::K) {
+            // Ctrl+K cleans up, Ctrl+Shift+K renumbers as well
+            self.cleanup(shift_down);
+        } else if input.modifiers.command && input.key_pressed(Key::D) {
+            // Ctrl+D clones the chain, Ctrl+Shift+D its phrases too
+            self.clone_chain(shift_down);
+        } else if let Some(edit) = edit {
+            self.edit_cell(edit);
+        } else if input.modifiers.command {
+            self.change_groove(input);
         } else {
             self.move_selection(input);
         }
@@ -39,24 +74,51 @@ impl View for Song {
         self.values[self.selected_row][self.selected_col]
     }
 
+    fn refresh(&mut self) {
+        self.grooves = Self::fetch_grooves(&self.tx);
+        // The song may have fewer tracks than it did
+        self.tracks = Self::fetch_tracks(&self.tx);
+        self.selected_col = self.selected_col.min(self.tracks - 1);
+        self.reload();
+        self.check();
+    }
+
+    fn follow(&mut self) {
+        let Some(row) = self.playhead.pattern_row() else {
+            return;
+        };
+        if row < self.min_row || row >= self.min_row + self.visible_rows {
+            self.min_row = row.min(NUM_PATTERNS.saturating_sub(self.visible_rows));
+        }
+    }
+
     fn draw(&mut self, ui: &mut Ui) {
-        let max_row = (self.min_row + self.visible_rows).min(ROWS);
+        let max_row = (self.min_row + self.visible_rows).min(NUM_PATTERNS);
 
         ui.vertical_centered(|ui| {
             ui.label(RichText::new("SONG").heading().color(Color32::LIGHT_BLUE));
         });
         ui.add_space(20.0);
 
+        if !self.status.is_empty() {
+            ui.label(
+                RichText::new(&self.status)
+                    .size(12.0)
+                    .color(Color32::LIGHT_BLUE),
+            );
+            ui.add_space(10.0);
+        }
+
         // Grid for table layout
         egui::Grid::new("song_grid")
-            .num_columns(2 + COLS) // ">" column + row label + COLS data columns
+            .num_columns(2 + self.tracks) // ">" column + row label + a column per track
             .min_col_width(40.0) // Allow exact column widths
             .max_col_width(f32::INFINITY)
             .show(ui, |ui| {
                 // Header row
                 ui.label(""); // Empty ">" column
                 ui.label(""); // Empty row label column
-                for track in 0..COLS {
+                for track in 0..self.tracks {
                     ui.label(
                         RichText::new(format!("{}", track))
                             .strong()
@@ -66,16 +128,46 @@ impl View for Song {
                 }
                 ui.end_row();
 
+                // Each track's groove
+                ui.label("");
+                ui.label(RichText::new("G").size(12.0).color(Color32::LIGHT_BLUE));
+                for groove in &self.grooves[..self.tracks] {
+                    ui.label(
+                        RichText::new(format!("{:02X}", groove))
+                            .size(12.0)
+                            .color(Color32::LIGHT_BLUE),
+                    );
+                }
+                ui.end_row();
+
                 // Body rows
+                let playing_row = self.playhead.pattern_row();
                 for i in self.min_row..max_row {
-                    ui.label(RichText::new(" ").color(Color32::YELLOW));
+                    ui.label(playhead_label(playing_row == Some(i)));
                     ui.label(RichText::new(format!("{:02X}", i)).color(Color32::GREEN));
-                    for j in 0..COLS {
-                        let cell = self.render_cell(self.values[i][j]);
+                    for j in 0..self.tracks {
+                        let pending = self.editor.pending(i, j);
+                        let cell = match pending {
+                            Some(digit) => Cow::Owned(CellEditor::render_pending(digit)),
+                            None => self.render_cell(self.values[i][j]),
+                        };
                         let is_selected = i == self.selected_row && j == self.selected_col;
                         let text = RichText::new(cell).size(12.0); // Consistent font size
-                        if is_selected {
+                        if pending.is_some() {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::ORANGE));
+                        } else if is_selected {
                             ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                        } else if self.selection.contains(
+                            i,
+                            j,
+                            (self.selected_row, self.selected_col),
+                        ) {
+                            ui.label(
+                                text.color(Color32::WHITE)
+                                    .background_color(Color32::DARK_BLUE),
+                            );
+                        } else if self.diagnostics.iter().any(|d| d.covers(Grid::Song, i, j)) {
+                            ui.label(text.color(Color32::RED));
                         } else {
                             ui.label(text.color(Color32::WHITE));
                         }
@@ -92,22 +184,77 @@ impl Song {
     const EMPTY_CELL_DISPLAY: &str = "--";
 
     pub fn new(tx: Sender<Action>) -> Self {
-        Self {
+        let grooves = Self::fetch_grooves(&tx);
+
+        let tracks = Self::fetch_tracks(&tx);
+        let diagnostics = check_grid(&tx, Grid::Song);
+        let playhead = get_playhead(&tx);
+
+        let mut song = Self {
             tx,
-            values: vec![vec![None; COLS]; ROWS],
+            diagnostics,
+            playhead,
+            grooves,
+            tracks,
+            values: vec![vec![None; tracks]; NUM_PATTERNS],
             selected_row: 0,
             selected_col: 0,
             visible_rows: 16,
             min_row: 0,
+            selection: Selection::new(),
+            editor: CellEditor::new(),
+            status: String::new(),
+        };
+        // Flag a bad song as soon as it's opened
+        song.check_song();
+        song
+    }
+
+    fn fetch_grooves(tx: &Sender<Action>) -> [GrooveId; NUM_TRACKS] {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTrackGrooves { reply_to: reply_tx })
+            .unwrap();
+        reply_rx.recv().unwrap()
+    }
+
+    fn fetch_tracks(tx: &Sender<Action>) -> usize {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetSongDimensions { reply_to: reply_tx })
+            .unwrap();
+        reply_rx.recv().unwrap().tracks
+    }
+
+    // Fetch the patterns again after a block edit
+    fn reload(&mut self) {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::GetPatternData { reply_to: reply_tx })
+            .unwrap();
+
+        let patterns = reply_rx.recv().unwrap();
+        for (row, values) in self.values.iter_mut().enumerate() {
+            *values = patterns
+                .get(row)
+                .cloned()
+                .unwrap_or_else(|| vec![None; self.tracks]);
         }
     }
 
+    // Look for problems again after an edit, which
+    // can fix them or make new ones
+    fn check(&mut self) {
+        self.diagnostics = check_grid(&self.tx, Grid::Song);
+    }
+
     fn move_selection(&mut self, input: &InputState) {
+        self.selection
+            .update(input, self.selected_row, self.selected_col);
         if input.key_pressed(Key::ArrowDown) {
-            self.selected_row = (self.selected_row + 1).min(ROWS - 1);
+            self.selected_row = (self.selected_row + 1).min(NUM_PATTERNS - 1);
 
             if self.selected_row >= self.min_row + self.visible_rows {
-                self.min_row = (self.min_row + 1).min(ROWS.saturating_sub(self.visible_rows));
+                self.min_row =
+                    (self.min_row + 1).min(NUM_PATTERNS.saturating_sub(self.visible_rows));
             }
         } else if input.key_pressed(Key::ArrowUp) {
             self.selected_row = self.selected_row.saturating_sub(1);
@@ -116,7 +263,7 @@ impl Song {
                 self.min_row = self.min_row.saturating_sub(1);
             }
         } else if input.key_pressed(Key::ArrowRight) {
-            self.selected_col = (self.selected_col + 1).min(COLS - 1);
+            self.selected_col = (self.selected_col + 1).min(self.tracks - 1);
         } else if input.key_pressed(Key::ArrowLeft) {
             self.selected_col = self.selected_col.saturating_sub(1);
         }
@@ -129,49 +276,119 @@ impl Song {
         }
     }
 
-    fn apply_delta(value: Option<u8>, delta: isize) -> Option<u8> {
-        match value {
-            Some(v) => {
-                let new = v as isize + delta;
-                if new < 0 {
-                    None
-                } else {
-                    Some(new.min(Self::MAX_CELL_VALUE as isize) as u8)
-                }
-            }
-            None => {
-                if delta == 1 {
-                    return Some(0);
-                } else if delta > 0 {
-                    Some(delta.min(Self::MAX_CELL_VALUE as isize) as u8)
-                } else {
-                    None
-                }
-            }
+    // Change the groove of the selected track
+    fn change_groove(&mut self, input: &InputState) {
+        let groove = &mut self.grooves[self.selected_col];
+        if input.key_pressed(Key::ArrowUp) {
+            *groove = groove.wrapping_add(1);
+        } else if input.key_pressed(Key::ArrowDown) {
+            *groove = groove.wrapping_sub(1);
+        } else {
+            return;
         }
+
+        self.tx
+            .send(Action::SetTrackGroove {
+                track_id: self.selected_col as u8,
+                groove_id: *groove,
+            })
+            .unwrap();
     }
 
-    fn change_selection(&mut self, input: &egui::InputState) {
-        let delta = if input.key_pressed(Key::ArrowUp) {
-            Some(1)
-        } else if input.key_pressed(Key::ArrowDown) {
-            Some(-1)
-        } else if input.key_pressed(Key::ArrowRight) {
-            Some(Self::BIG_CELL_INCREMENT)
-        } else if input.key_pressed(Key::ArrowLeft) {
-            Some(-Self::BIG_CELL_INCREMENT)
-        } else {
-            None
+    // Count the problems in the whole song
+    fn check_song(&mut self) {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::CheckSong { reply_to: reply_tx })
+            .unwrap();
+        let diagnostics = reply_rx.recv().unwrap();
+
+        self.status = format!("{} PROBLEMS", diagnostics.len());
+    }
+
+    // Count the chains and phrases nothing plays
+    fn check_usage(&mut self) {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::GetUsage { reply_to: reply_tx })
+            .unwrap();
+        let usage = reply_rx.recv().unwrap();
+
+        self.status = format!(
+            "UNUSED {} CHAINS {} PHRASES",
+            usage.orphaned_chains.len(),
+            usage.orphaned_phrases.len()
+        );
+    }
+
+    fn cleanup(&mut self, renumber: bool) {
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::Cleanup {
+                renumber,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+        let report = reply_rx.recv().unwrap();
+
+        self.status = format!(
+            "REMOVED {} CHAINS {} PHRASES",
+            report.removed_chains.len(),
+            report.removed_phrases.len()
+        );
+        if renumber {
+            self.status += &format!(
+                ", RENUMBERED {} CHAINS {} PHRASES",
+                report.renumbered_chains.len(),
+                report.renumbered_phrases.len()
+            );
+            self.reload();
+        }
+        self.check();
+    }
+
+    // Replace the selected chain with a copy of it
+    fn clone_chain(&mut self, deep: bool) {
+        let row = self.selected_row;
+        let col = self.selected_col;
+        let Some(chain_id) = self.values[row][col] else {
+            return;
         };
 
-        let Some(d) = delta else { return };
+        let (reply_tx, reply_rx) = bounded(1);
+        self.tx
+            .send(Action::CloneChain {
+                chain_id,
+                deep,
+                reply_to: reply_tx,
+            })
+            .unwrap();
+        let Ok(new_id) = reply_rx.recv().unwrap() else {
+            return;
+        };
 
+        self.values[row][col] = Some(new_id);
+        self.tx
+            .send(Action::SetPatternValue {
+                pattern_id: row as u8,
+                track_id: col as u8,
+                chain_id: Some(new_id),
+            })
+            .unwrap();
+        self.check();
+    }
+
+    // Change the chain at the cursor
+    fn edit_cell(&mut self, edit: CellEdit) {
         let row = self.selected_row;
         let col = self.selected_col;
         let cell = &mut self.values[row][col];
 
-        let new_value = Self::apply_delta(*cell, d);
+        let new_value = edit.apply(*cell, Self::BIG_CELL_INCREMENT, Self::MAX_CELL_VALUE);
         *cell = new_value;
+        if let Some(value) = new_value {
+            self.editor.remember(col, value);
+        }
 
         self.tx
             .send(Action::SetPatternValue {
@@ -180,5 +397,6 @@ impl Song {
                 chain_id: new_value,
             })
             .unwrap();
+        self.check();
     }
 }
@@ -0,0 +1,272 @@
+use eframe::egui::{Color32, InputState, Key, RichText, Ui};
+
+use super::cell_editor::{CellEdit, CellEditor};
+use super::view::View;
+use crate::messaging::Action;
+use crate::types::{Fx, FxKind, MAX_VELOCITY, NUM_FX_COLUMNS, NUM_TABLE_ROWS, TableId, TableRow};
+use crossbeam::channel::Sender;
+use crossbeam::channel::bounded;
+use std::borrow::Cow;
+
+/*
+ * Edits a table's rows. Cells are changed or typed
+ * in as in the other grids, Ctrl+Up/Down changes
+ * which command an FX column holds and Ctrl+Left/
+ * Right picks the table to edit.
+ */
+
+pub struct Table {
+    tx: Sender<Action>,
+
+    table_id: TableId,
+
+    // Selection state
+    selected_row: usize,
+    selected_col: usize,
+    editor: CellEditor,
+
+    // Data
+    values: Vec<TableRow>,
+}
+
+impl View for Table {
+    fn handle_event(&mut self, input: &InputState) {
+        let (row, col) = (self.selected_row, self.selected_col);
+        let edit = self.editor.handle_keys(input, row, col, true);
+
+        if let Some(edit) = edit {
+            self.edit_cell(edit);
+        } else if input.modifiers.command {
+            self.change_command(input);
+        } else {
+            self.move_selection(input);
+        }
+    }
+
+    fn get_selection(&self) -> Option<u8> {
+        Some(self.table_id)
+    }
+
+    fn refresh(&mut self) {
+        self.values = Self::fetch_table(&self.tx, self.table_id);
+    }
+
+    fn draw(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("TABLE").heading().color(Color32::LIGHT_BLUE));
+        });
+        ui.add_space(20.0);
+
+        // Grid for table layout
+        egui::Grid::new("table_grid")
+            .num_columns(2 + Self::NUM_COLUMNS)
+            .min_col_width(40.0)
+            .max_col_width(f32::INFINITY)
+            .show(ui, |ui| {
+                // Header row
+                ui.label(""); // Empty ">" column
+                ui.label(""); // Empty row label column
+                ui.label(format!("{:0x}", self.table_id));
+                ui.label("V");
+                for _ in 0..NUM_FX_COLUMNS {
+                    ui.label("FX");
+                }
+                ui.end_row();
+
+                // Body rows
+                for i in 0..NUM_TABLE_ROWS {
+                    ui.label(RichText::new(" ").color(Color32::YELLOW));
+                    ui.label(RichText::new(format!("{:02X}", i)).color(Color32::GREEN));
+
+                    let row = self.values[i];
+                    let mut cells = vec![
+                        self.render_cell(Some(row.transpose)),
+                        self.render_cell(row.volume),
+                    ];
+                    for fx in row.fx {
+                        cells.push(self.render_fx(fx));
+                    }
+
+                    for (col, cell) in cells.into_iter().enumerate() {
+                        let pending = self.editor.pending(i, col);
+                        let cell = match pending {
+                            Some(digit) => Cow::Owned(CellEditor::render_pending(digit)),
+                            None => cell,
+                        };
+                        let is_selected = i == self.selected_row && col == self.selected_col;
+                        let text = RichText::new(cell).size(12.0);
+                        if pending.is_some() {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::ORANGE));
+                        } else if is_selected {
+                            ui.label(text.color(Color32::BLACK).background_color(Color32::YELLOW));
+                        } else {
+                            ui.label(text.color(Color32::WHITE));
+                        }
+                    }
+
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl Table {
+    const MAX_CELL_VALUE: usize = 0xFF;
+    const MAX_VOLUME_VALUE: usize = MAX_VELOCITY as usize;
+
+    // Transpose, volume and the FX commands
+    const NUM_COLUMNS: usize = 2 + NUM_FX_COLUMNS;
+    const TRANSPOSE_COLUMN: usize = 0;
+    const VOLUME_COLUMN: usize = 1;
+    const FIRST_FX_COLUMN: usize = 2;
+    const BIG_CELL_INCREMENT: isize = 0x10;
+    const EMPTY_CELL_DISPLAY: &str = "--";
+    const EMPTY_FX_DISPLAY: &str = "---";
+
+    pub fn new(tx: Sender<Action>, table_id: TableId) -> Self {
+        Self {
+            values: Self::fetch_table(&tx, table_id),
+            tx,
+            table_id,
+            selected_row: 0,
+            selected_col: 0,
+            editor: CellEditor::new(),
+        }
+    }
+
+    fn fetch_table(tx: &Sender<Action>, table_id: TableId) -> Vec<TableRow> {
+        let (reply_tx, reply_rx) = bounded(1);
+        tx.send(Action::GetTableData {
+            table_id,
+            reply_to: reply_tx,
+        })
+        .unwrap();
+
+        reply_rx.recv().unwrap().rows.to_vec()
+    }
+
+    fn move_selection(&mut self, input: &InputState) {
+        if input.key_pressed(Key::ArrowDown) {
+            self.selected_row = (self.selected_row + 1).min(NUM_TABLE_ROWS - 1);
+        }
+        if input.key_pressed(Key::ArrowUp) {
+            self.selected_row = self.selected_row.saturating_sub(1);
+        }
+        if input.key_pressed(Key::ArrowRight) {
+            self.selected_col = (self.selected_col + 1).min(Self::NUM_COLUMNS - 1);
+        }
+        if input.key_pressed(Key::ArrowLeft) {
+            self.selected_col = self.selected_col.saturating_sub(1);
+        }
+    }
+
+    fn render_cell(&self, value: Option<u8>) -> Cow<'_, str> {
+        match value {
+            None => Cow::Borrowed(Self::EMPTY_CELL_DISPLAY),
+            Some(v) => Cow::Owned(format!("{:02X}", v)),
+        }
+    }
+
+    // Command letter then hex value, e.g. H20
+    fn render_fx(&self, fx: Option<Fx>) -> Cow<'_, str> {
+        match fx {
+            None => Cow::Borrowed(Self::EMPTY_FX_DISPLAY),
+            Some(fx) => Cow::Owned(format!("{}{:02X}", fx.kind.letter(), fx.value)),
+        }
+    }
+
+    // Change one column of a row. Transpose is a signed
+    // byte, so it wraps round from 00 down to FF (-1).
+    fn edit_row(row: TableRow, col: usize, edit: CellEdit) -> TableRow {
+        let jump = Self::BIG_CELL_INCREMENT;
+        match col {
+            Self::TRANSPOSE_COLUMN => TableRow {
+                transpose: match edit {
+                    CellEdit::Set(value) => value,
+                    CellEdit::Nudge(delta) => row.transpose.wrapping_add_signed(delta as i8),
+                    CellEdit::Jump(direction) => {
+                        row.transpose.wrapping_add_signed((direction * jump) as i8)
+                    }
+                },
+                ..row
+            },
+            Self::VOLUME_COLUMN => TableRow {
+                volume: edit.apply(row.volume, jump, Self::MAX_VOLUME_VALUE),
+                ..row
+            },
+            _ => {
+                let mut row = row;
+                let fx = &mut row.fx[col - Self::FIRST_FX_COLUMN];
+                let kind = fx.map_or(FxKind::ALL[0], |fx| fx.kind);
+                *fx = edit
+                    .apply(fx.map(|fx| fx.value), jump, Self::MAX_CELL_VALUE)
+                    .map(|value| Fx::new(kind, value));
+                row
+            }
+        }
+    }
+
+    // The value in one column of a row
+    fn cell_value(row: TableRow, col: usize) -> Option<u8> {
+        match col {
+            Self::TRANSPOSE_COLUMN => Some(row.transpose),
+            Self::VOLUME_COLUMN => row.volume,
+            _ => row.fx[col - Self::FIRST_FX_COLUMN].map(|fx| fx.value),
+        }
+    }
+
+    // Change which command an FX column holds, or
+    // with Left/Right, which table is being edited
+    fn change_command(&mut self, input: &InputState) {
+        let delta = if input.key_pressed(Key::ArrowUp) {
+            1
+        } else if input.key_pressed(Key::ArrowDown) {
+            -1
+        } else if input.key_pressed(Key::ArrowRight) {
+            self.table_id = self.table_id.wrapping_add(1);
+            self.values = Self::fetch_table(&self.tx, self.table_id);
+            return;
+        } else if input.key_pressed(Key::ArrowLeft) {
+            self.table_id = self.table_id.wrapping_sub(1);
+            self.values = Self::fetch_table(&self.tx, self.table_id);
+            return;
+        } else {
+            return;
+        };
+
+        if self.selected_col < Self::FIRST_FX_COLUMN {
+            return;
+        }
+
+        let mut row = self.values[self.selected_row];
+        let fx = &mut row.fx[self.selected_col - Self::FIRST_FX_COLUMN];
+        *fx = Some(match fx {
+            Some(fx) => Fx::new(fx.kind.cycle(delta), fx.value),
+            None => Fx::new(FxKind::ALL[0], 0),
+        });
+
+        self.set_row(row);
+    }
+
+    fn set_row(&mut self, row: TableRow) {
+        let index = self.selected_row;
+        self.values[index] = row;
+
+        self.tx
+            .send(Action::SetTableRow {
+                table_id: self.table_id,
+                index,
+                row,
+            })
+            .unwrap();
+    }
+
+    fn edit_cell(&mut self, edit: CellEdit) {
+        let col = self.selected_col;
+        let row = Self::edit_row(self.values[self.selected_row], col, edit);
+        if let Some(value) = Self::cell_value(row, col) {
+            self.editor.remember(col, value);
+        }
+        self.set_row(row);
+    }
+}
@@ -1,7 +1,75 @@
-use egui::{InputState, Ui};
+use egui::{Color32, InputState, RichText, Ui};
+
+use crate::engine::Playhead;
+use crate::engine::audio::NodeDef;
+use crate::messaging::Action;
+use crate::types::{Diagnostic, Grid, InstrumentId};
+use crossbeam::channel::{Sender, bounded};
+use std::sync::Arc;
 
 pub trait View {
     fn handle_event(&mut self, input: &InputState);
     fn draw(&mut self, ui: &mut Ui);
     fn get_selection(&self) -> Option<u8>;
+
+    // The instrument under the cursor, for views that
+    // have one, which live play takes up
+    fn get_instrument(&self) -> Option<InstrumentId> {
+        None
+    }
+
+    // Scroll to keep the playing row in sight, for
+    // views that don't show every row at once
+    fn follow(&mut self) {}
+
+    // Fetch everything shown again, when the view is
+    // come back to after edits made elsewhere
+    fn refresh(&mut self) {}
+}
+
+pub fn get_playhead(tx: &Sender<Action>) -> Arc<Playhead> {
+    let (reply_tx, reply_rx) = bounded(1);
+    tx.send(Action::GetPlayhead { reply_to: reply_tx }).unwrap();
+    reply_rx.recv().unwrap()
+}
+
+// The label for the ">" column, marking a row that's playing
+pub fn playhead_label(playing: bool) -> RichText {
+    RichText::new(if playing { ">" } else { " " }).color(Color32::YELLOW)
+}
+
+// What a patch node is, as the instrument views show it
+pub fn node_name(def: &NodeDef) -> &'static str {
+    match def {
+        NodeDef::Sine(_) => "SINE",
+        NodeDef::Lfo(_) => "LFO",
+        NodeDef::Adsr(_) => "ADSR",
+        NodeDef::Distortion(_) => "DISTORTION",
+        NodeDef::Bitcrusher(_) => "BITCRUSHER",
+        NodeDef::Chorus(_) => "CHORUS",
+        NodeDef::Eq(_) => "EQ",
+        NodeDef::Compressor(_) => "COMPRESSOR",
+    }
+}
+
+// Check the song, keeping the problems in one grid
+pub fn check_grid(tx: &Sender<Action>, grid: Grid) -> Vec<Diagnostic> {
+    let (reply_tx, reply_rx) = bounded(1);
+    tx.send(Action::CheckSong { reply_to: reply_tx }).unwrap();
+
+    let mut diagnostics = reply_rx.recv().unwrap();
+    diagnostics.retain(|diagnostic| diagnostic.grid == grid);
+    diagnostics
+}
+
+// The first row to show so that the given row is in
+// sight, scrolling as little as possible
+pub fn scroll_to(row: usize, min_row: usize, visible_rows: usize) -> usize {
+    if row < min_row {
+        row
+    } else if row >= min_row + visible_rows {
+        row + 1 - visible_rows
+    } else {
+        min_row
+    }
 }